@@ -0,0 +1,201 @@
+use flust_core::diagnose::{self, Finding};
+use anyhow::Result;
+use clap::ValueEnum;
+use flust_codegen::generator::{self, Target};
+use flust_core::diagnostics::{Diagnostic, Severity};
+use flust_core::modules::ModuleGraph;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Human,
+    Json,
+    Sarif,
+}
+
+/// Finding located in a flow file, with the label of its node
+struct Report {
+    file: PathBuf,
+    label: Option<String>,
+    finding: Finding,
+}
+
+/// Parse, validate and type-check each flow and its imports without writing a project
+/// Returns whether no errors were found
+pub fn run(inputs: &[PathBuf], format: Format) -> Result<bool> {
+    let mut reports = Vec::new();
+    let mut checked = HashSet::new();
+    for input in inputs {
+        reports.extend(check_flow(input, &mut checked));
+    }
+
+    let output = match format {
+        Format::Human => human(&reports, inputs.len()),
+        Format::Json => serde_json::to_string_pretty(&json_report(&reports))?,
+        Format::Sarif => serde_json::to_string_pretty(&sarif(&reports))?,
+    };
+    println!("{}", output);
+    Ok(!reports.iter().any(|r| r.finding.diagnostic.is_error()))
+}
+
+/// Findings of a flow and its imports; flow files already in `checked` are not reported again
+fn check_flow(input: &Path, checked: &mut HashSet<PathBuf>) -> Vec<Report> {
+    let file_error = |rule, message: String| Report {
+        file: input.to_path_buf(),
+        label: None,
+        finding: Finding { rule, diagnostic: Diagnostic { severity: Severity::Error, node_id: None, message } },
+    };
+    let graph = match ModuleGraph::load(input) {
+        Ok(graph) => graph,
+        Err(error) => return vec![file_error("load", error.to_string())],
+    };
+
+    let mut reports = Vec::new();
+    let mut valid = true;
+    for result in diagnose::diagnose(&graph) {
+        valid &= !result.findings.iter().any(|f| f.diagnostic.is_error());
+        let file = std::fs::canonicalize(&result.module.path).unwrap_or_else(|_| result.module.path.clone());
+        if !checked.insert(file) {
+            continue;
+        }
+        for finding in result.findings {
+            let label = finding.diagnostic.node_id.as_ref()
+                .and_then(|id| result.module.flow.nodes.iter().find(|n| &n.id == id))
+                .and_then(|node| node.label.clone());
+            reports.push(Report { file: result.module.path.clone(), label, finding });
+        }
+    }
+    // Problems only the code generator detects, once the flow itself is valid
+    if valid
+        && let Err(error) = generator::generate_project(&graph, Target::Binary)
+    {
+        reports.push(file_error("codegen", error.to_string()));
+    }
+    reports
+}
+
+/// `app.flow.json: error [check "Positive total"]: message` lines and a summary
+fn human(reports: &[Report], files: usize) -> String {
+    let mut output = String::new();
+    for report in reports {
+        let diagnostic = &report.finding.diagnostic;
+        let severity = if diagnostic.is_error() { "error" } else { "warning" };
+        let node = match (&diagnostic.node_id, &report.label) {
+            (Some(id), Some(label)) => format!(" [{} \"{}\"]", id, label),
+            (Some(id), None) => format!(" [{}]", id),
+            _ => String::new(),
+        };
+        output.push_str(&format!("{}: {}{}: {}\n", report.file.display(), severity, node, diagnostic.message));
+    }
+    let errors = reports.iter().filter(|r| r.finding.diagnostic.is_error()).count();
+    output.push_str(&format!("{} error(s), {} warning(s) in {} flow(s)", errors, reports.len() - errors, files));
+    output
+}
+
+fn json_report(reports: &[Report]) -> Value {
+    let diagnostics: Vec<Value> = reports.iter()
+        .map(|report| json!({
+            "file": report.file.display().to_string(),
+            "rule": report.finding.rule,
+            "severity": report.finding.diagnostic.severity,
+            "node_id": report.finding.diagnostic.node_id,
+            "label": report.label,
+            "message": report.finding.diagnostic.message,
+        }))
+        .collect();
+    let errors = reports.iter().filter(|r| r.finding.diagnostic.is_error()).count();
+    json!({ "errors": errors, "warnings": reports.len() - errors, "diagnostics": diagnostics })
+}
+
+/// SARIF 2.1.0 log; nodes are logical locations inside the flow file
+fn sarif(reports: &[Report]) -> Value {
+    let results: Vec<Value> = reports.iter()
+        .map(|report| {
+            let diagnostic = &report.finding.diagnostic;
+            let mut location = json!({
+                "physicalLocation": { "artifactLocation": { "uri": report.file.display().to_string() } }
+            });
+            if let Some(node_id) = &diagnostic.node_id {
+                location["logicalLocations"] = json!([{
+                    "name": report.label.as_deref().unwrap_or(node_id),
+                    "fullyQualifiedName": node_id,
+                    "kind": "element",
+                }]);
+            }
+            json!({
+                "ruleId": format!("flust/{}", report.finding.rule),
+                "level": if diagnostic.is_error() { "error" } else { "warning" },
+                "message": { "text": diagnostic.message },
+                "locations": [location],
+            })
+        })
+        .collect();
+    json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": { "driver": { "name": "flust", "version": env!("CARGO_PKG_VERSION") } },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_name_node_and_label() {
+        let reports = vec![
+            Report {
+                file: PathBuf::from("app.flow.json"),
+                label: Some("Store total".to_string()),
+                finding: Finding { rule: "scope", diagnostic: Diagnostic::error("assign", "cannot assign twice to immutable variable 'total'") },
+            },
+            Report {
+                file: PathBuf::from("app.flow.json"),
+                label: None,
+                finding: Finding { rule: "types", diagnostic: Diagnostic::warning("sum", "unused result") },
+            },
+        ];
+
+        assert_eq!(
+            human(&reports, 1),
+            "app.flow.json: error [assign \"Store total\"]: cannot assign twice to immutable variable 'total'\n\
+             app.flow.json: warning [sum]: unused result\n\
+             1 error(s), 1 warning(s) in 1 flow(s)"
+        );
+        let log = sarif(&reports);
+        let result = &log["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "flust/scope");
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["locations"][0]["logicalLocations"][0]["fullyQualifiedName"], "assign");
+        assert_eq!(json_report(&reports)["diagnostics"][1]["severity"], "warning");
+    }
+
+    #[test]
+    fn test_analysis_errors_are_reported_once_per_file() {
+        let dir = std::env::temp_dir().join(format!("flust-check-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // A connection cycle the scope analysis cannot order
+        std::fs::write(dir.join("util.flow.json"), r#"{"nodes": [
+            {"id": "clamp", "plugin_type": "function-definition", "properties": {"function_name": "clamp"}},
+            {"id": "a", "plugin_type": "print", "parent_id": "clamp", "properties": {"message": "a"}},
+            {"id": "b", "plugin_type": "print", "parent_id": "clamp", "properties": {"message": "b"}}
+        ], "connections": [{"from": "a", "to": "b"}, {"from": "b", "to": "a"}]}"#).unwrap();
+        std::fs::write(dir.join("app.flow.json"), r#"{"modules": [{"path": "util.flow.json", "items": ["clamp"]}], "nodes": [
+            {"id": "main", "plugin_type": "function-definition", "properties": {"function_name": "main"}}
+        ], "connections": []}"#).unwrap();
+
+        let mut checked = HashSet::new();
+        let mut reports = check_flow(&dir.join("app.flow.json"), &mut checked);
+        reports.extend(check_flow(&dir.join("util.flow.json"), &mut checked));
+        let errors: Vec<&Report> = reports.iter().filter(|r| r.finding.diagnostic.is_error()).collect();
+        assert_eq!(errors.len(), 1, "{}", human(&reports, 2));
+        assert_eq!(errors[0].file, dir.join("util.flow.json"));
+        assert_eq!(errors[0].finding.rule, "scope");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
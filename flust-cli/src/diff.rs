@@ -0,0 +1,75 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use crate::merge::read;
+use flust_core::diff::{self, Change, ChangeKind, Item};
+use serde_json::{Value, json};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Human,
+    Json,
+}
+
+/// Print the semantic differences between two flow files
+/// Returns whether the flows are equivalent
+pub fn run(old: &Path, new: &Path, format: Format) -> Result<bool> {
+    let changes = diff::diff(&read(old)?, &read(new)?)?;
+    match format {
+        Format::Human => {
+            for change in &changes {
+                println!("{}", change);
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&json_changes(&changes))?),
+    }
+    Ok(changes.is_empty())
+}
+
+fn json_changes(changes: &[Change]) -> Value {
+    let changes: Vec<Value> = changes.iter()
+        .map(|change| {
+            let kind = match change.kind {
+                ChangeKind::Added => "added",
+                ChangeKind::Removed => "removed",
+                ChangeKind::Changed => "changed",
+                ChangeKind::Rewired => "rewired",
+            };
+            let node = match &change.item {
+                Item::Node { id, .. } => Some(id),
+                _ => None,
+            };
+            json!({
+                "change": kind,
+                "item": change.item.to_string(),
+                "node_id": node,
+                "before": change.before,
+                "after": change.after,
+            })
+        })
+        .collect();
+    json!(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flust_core::diff::Field;
+
+    #[test]
+    fn test_json_changes_name_the_node() {
+        let change = Change {
+            kind: ChangeKind::Changed,
+            item: Item::Node { id: "sum".to_string(), field: Some(Field::Property("value".to_string())) },
+            before: Some(json!("1")),
+            after: Some(json!("2")),
+        };
+        assert_eq!(json_changes(&[change]), json!([{
+            "change": "changed",
+            "item": "node 'sum' properties.value",
+            "node_id": "sum",
+            "before": "1",
+            "after": "2",
+        }]));
+    }
+}
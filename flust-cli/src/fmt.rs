@@ -0,0 +1,30 @@
+use anyhow::{Result, anyhow};
+use flust_core::formatter;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Rewrite flow files in canonical form, or with `check` only list those that are not
+/// Returns whether every file was already formatted
+pub fn run(inputs: &[PathBuf], check: bool) -> Result<bool> {
+    let mut formatted = true;
+    for input in inputs {
+        let content = fs::read_to_string(input)?;
+        let canonical = formatter::format_str(&content, is_yaml(input))
+            .map_err(|error| anyhow!("{}: {}", input.display(), error))?;
+        if canonical == content {
+            continue;
+        }
+        formatted = false;
+        if check {
+            println!("{} is not formatted", input.display());
+        } else {
+            fs::write(input, canonical)?;
+            println!("formatted {}", input.display());
+        }
+    }
+    Ok(formatted || !check)
+}
+
+pub fn is_yaml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml")
+}
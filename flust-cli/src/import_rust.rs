@@ -0,0 +1,555 @@
+use anyhow::Result;
+use flust_core::ir::{Connection, ConnectionType, Flow, Node};
+use proc_macro2::LineColumn;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Block, Expr, FnArg, GenericArgument, Item, ItemFn, Pat, PathArguments, ReturnType, Stmt, Type};
+
+/// Flow built from a Rust file, with notes on what could not be mapped to nodes
+pub struct RustImport {
+    pub flow: Flow,
+    pub notes: Vec<String>,
+}
+
+/// Signature of a top-level function that becomes a function-definition
+struct Signature {
+    arguments: Vec<(String, String)>,
+    return_type: Option<String>,
+    error_type: Option<String>,
+    fallible: bool,
+}
+
+/// Top-level `fn`s become function-definitions whose body is a chain of call-function nodes
+/// when it only calls other imported functions, or a legacy-code node otherwise
+/// Every other item (including generic functions) is kept verbatim in a module-code node,
+/// as are functions that could not become async: recursive ones, and those called where `.await` is not allowed
+pub fn import_rust(text: &str) -> Result<RustImport> {
+    let file = syn::parse_file(text)?;
+    let mut source = Source::new(text);
+    let mut notes = Vec::new();
+    let mut signatures = HashMap::new();
+    for item in &file.items {
+        if let Item::Fn(func) = item {
+            match signature(func, &source) {
+                Some(signature) => {
+                    signatures.insert(func.sig.ident.to_string(), signature);
+                }
+                None => notes.push(format!("fn '{}' is generic; kept as module code", func.sig.ident)),
+            }
+        }
+    }
+    for (name, reason) in synchronous(&file, &signatures) {
+        notes.push(format!("fn '{}' {}; kept as module code", name, reason));
+        signatures.remove(&name);
+    }
+    let (functions, other_items): (Vec<&Item>, Vec<&Item>) = file.items.iter()
+        .partition(|item| matches!(item, Item::Fn(func) if signatures.contains_key(&func.sig.ident.to_string())));
+    let functions: Vec<&ItemFn> = functions.into_iter()
+        .filter_map(|item| match item {
+            Item::Fn(func) => Some(func),
+            _ => None,
+        })
+        .collect();
+
+    // Generated functions are async, so calls between imported functions need `.await`
+    let mut calls = LocalCalls { source: &source, functions: &signatures, awaits: BTreeSet::new() };
+    for func in &functions {
+        calls.visit_block(&func.block);
+    }
+    source.awaits = calls.awaits;
+
+    let mut flow = Flow::default();
+    if !other_items.is_empty() {
+        let code: Vec<String> = other_items.iter().map(|item| source.text(item)).collect();
+        flow.nodes.push(node("module_items", "module-code", None, json!({ "code": code.join("\n\n") })));
+    }
+    for func in functions {
+        let name = func.sig.ident.to_string();
+        let signature = &signatures[&name];
+        let mut properties = json!({
+            "function_name": name,
+            "arguments": arguments(&signature.arguments),
+            "fallible": signature.fallible,
+        });
+        if let Some(return_type) = &signature.return_type {
+            properties["return_type"] = json!(return_type);
+        }
+        if let Some(error_type) = &signature.error_type {
+            properties["error_type"] = json!(error_type);
+        }
+        if matches!(func.vis, syn::Visibility::Public(_)) {
+            properties["export"] = json!(true);
+        }
+        let mut definition = node(&name, "function-definition", None, properties);
+        definition.label = Some(name.clone());
+        flow.nodes.push(definition);
+
+        let start = format!("{}_start", name);
+        flow.nodes.push(node(&start, "start-node", Some(&name), json!({})));
+        match plain_calls(&func.block, signature, &signatures, &source) {
+            Some(calls) => {
+                let mut previous = start;
+                for (index, call) in calls.iter().enumerate() {
+                    let id = format!("{}_call_{}", name, index + 1);
+                    flow.nodes.push(call_node(&id, &name, call, &signatures[call.target.as_str()]));
+                    let mapping = call.arguments.iter().map(|(arg, expr)| (arg.clone(), source.text(*expr))).collect();
+                    flow.connections.push(connection(&previous, &id, Some(mapping)));
+                    previous = id;
+                }
+            }
+            None => {
+                let body = format!("{}_body", name);
+                flow.nodes.push(node(&body, "legacy-code", Some(&name), json!({ "code": source.block_body(&func.block) })));
+                flow.connections.push(connection(&start, &body, None));
+            }
+        }
+    }
+    Ok(RustImport { flow, notes })
+}
+
+/// Functions that must stay synchronous, with the reason, in source order
+/// Recursive async fns need boxing (E0733) and sync closures or items cannot await them (E0277);
+/// whatever such a function calls has to stay synchronous too
+fn synchronous(file: &syn::File, signatures: &HashMap<String, Signature>) -> Vec<(String, String)> {
+    let mut callees: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut graph = CallGraph { functions: signatures, calls: BTreeSet::new(), sync_calls: BTreeSet::new(), sync_depth: 0 };
+    for item in &file.items {
+        match item {
+            Item::Fn(func) if signatures.contains_key(&func.sig.ident.to_string()) => {
+                graph.calls.clear();
+                graph.visit_block(&func.block);
+                callees.insert(func.sig.ident.to_string(), std::mem::take(&mut graph.calls));
+            }
+            // Module code is synchronous
+            item => {
+                graph.sync_depth = 1;
+                visit::visit_item(&mut graph, item);
+                graph.sync_depth = 0;
+            }
+        }
+    }
+
+    let mut reasons: BTreeMap<String, String> = BTreeMap::new();
+    for name in callees.keys() {
+        let mut stack: Vec<&String> = callees[name].iter().collect();
+        let mut seen = BTreeSet::new();
+        while let Some(callee) = stack.pop() {
+            if callee == name {
+                reasons.insert(name.clone(), "is recursive".to_string());
+                break;
+            }
+            if seen.insert(callee) {
+                stack.extend(callees.get(callee).into_iter().flatten());
+            }
+        }
+    }
+    for name in &graph.sync_calls {
+        reasons.entry(name.clone()).or_insert_with(|| "is called where it cannot be awaited (a closure or an item)".to_string());
+    }
+    let mut pending: Vec<String> = reasons.keys().cloned().collect();
+    while let Some(caller) = pending.pop() {
+        for callee in callees.get(&caller).into_iter().flatten() {
+            if !reasons.contains_key(callee) {
+                reasons.insert(callee.clone(), format!("is called by '{}', which stays synchronous", caller));
+                pending.push(callee.clone());
+            }
+        }
+    }
+
+    file.items.iter()
+        .filter_map(|item| match item {
+            Item::Fn(func) => reasons.remove_entry(&func.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `None` for functions a function-definition cannot express (generic ones)
+fn signature(func: &ItemFn, source: &Source) -> Option<Signature> {
+    if !func.sig.generics.params.is_empty() {
+        return None;
+    }
+    let mut arguments = Vec::new();
+    for input in &func.sig.inputs {
+        let FnArg::Typed(typed) = input else { return None };
+        arguments.push((source.text(&typed.pat), source.text(&typed.ty)));
+    }
+    let (return_type, error_type, fallible) = match &func.sig.output {
+        ReturnType::Default => (None, None, false),
+        ReturnType::Type(_, ty) => match result_parts(ty, source) {
+            Some((ok, error)) => (Some(ok), error, true),
+            None => (Some(source.text(ty)), None, false),
+        },
+    };
+    Some(Signature { arguments, return_type, error_type, fallible })
+}
+
+/// `Result<T, E>` split into `T` and `E`; fallible functions keep `()` so no extra `Ok(())` is appended
+fn result_parts(ty: &Type, source: &Source) -> Option<(String, Option<String>)> {
+    let Type::Path(path) = ty else { return None };
+    let last = path.path.segments.last()?;
+    if last.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(generics) = &last.arguments else { return None };
+    let mut types = generics.args.iter().filter_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(source.text(ty)),
+        _ => None,
+    });
+    Some((types.next()?, types.next()))
+}
+
+fn arguments(arguments: &[(String, String)]) -> Value {
+    Value::Array(arguments.iter().map(|(name, ty)| json!({ "name": name, "type": ty })).collect())
+}
+
+/// Call of an imported function that maps onto a call-function node
+struct Call<'a> {
+    target: String,
+    arguments: Vec<(String, &'a Expr)>,
+    return_variable: Option<String>,
+    return_type: Option<String>,
+    is_mutable: bool,
+    propagate: bool,
+}
+
+/// The calls of a body made only of `f(..);`, `let x = f(..);` or `f(..)?;` statements of imported functions
+fn plain_calls<'a>(block: &'a Block, function: &Signature, signatures: &HashMap<String, Signature>, source: &Source) -> Option<Vec<Call<'a>>> {
+    let mut calls = Vec::new();
+    for stmt in &block.stmts {
+        let call = match stmt {
+            Stmt::Local(local) => {
+                let init = local.init.as_ref().filter(|init| init.diverge.is_none())?;
+                let (pat, return_type) = match &local.pat {
+                    Pat::Type(typed) => (&*typed.pat, Some(source.text(&typed.ty))),
+                    pat => (pat, None),
+                };
+                let Pat::Ident(ident) = pat else { return None };
+                if ident.by_ref.is_some() || ident.subpat.is_some() {
+                    return None;
+                }
+                let mut call = call_of(&init.expr, signatures)?;
+                call.return_variable = Some(ident.ident.to_string());
+                call.return_type = return_type;
+                call.is_mutable = ident.mutability.is_some();
+                call
+            }
+            // A tail expression is the return value, which a call node cannot produce
+            Stmt::Expr(expr, semicolon) if semicolon.is_some() || function.return_type.is_none() => call_of(expr, signatures)?,
+            _ => return None,
+        };
+        if call.propagate && !function.fallible {
+            return None;
+        }
+        calls.push(call);
+    }
+    Some(calls)
+}
+
+fn call_of<'a>(expr: &'a Expr, signatures: &HashMap<String, Signature>) -> Option<Call<'a>> {
+    let (expr, propagate) = match strip_await(expr) {
+        Expr::Try(tried) => (strip_await(&tried.expr), true),
+        expr => (expr, false),
+    };
+    let target = local_call(expr, signatures)?;
+    let Expr::Call(call) = expr else { return None };
+    let signature = &signatures[&target];
+    if call.args.len() != signature.arguments.len() || (propagate && !signature.fallible) {
+        return None;
+    }
+    let arguments = signature.arguments.iter().map(|(name, _)| name.clone()).zip(call.args.iter()).collect();
+    Some(Call { target, arguments, return_variable: None, return_type: None, is_mutable: false, propagate })
+}
+
+fn strip_await(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Await(awaited) => &awaited.base,
+        expr => expr,
+    }
+}
+
+/// Name of the imported function called by `f(..)`; `main` is never called
+fn local_call(expr: &Expr, signatures: &HashMap<String, Signature>) -> Option<String> {
+    let Expr::Call(call) = expr else { return None };
+    let Expr::Path(path) = &*call.func else { return None };
+    let name = path.path.get_ident()?.to_string();
+    (name != "main" && signatures.contains_key(&name)).then_some(name)
+}
+
+fn call_node(id: &str, parent: &str, call: &Call, target: &Signature) -> Node {
+    let mut properties = json!({
+        "target_function": call.target,
+        "arguments": arguments(&target.arguments),
+        "declare_variable": true,
+        "is_mutable": call.is_mutable,
+        "error_handling": if call.propagate { "propagate" } else { "none" },
+    });
+    if let Some(variable) = &call.return_variable {
+        properties["return_variable"] = json!(variable);
+    }
+    if let Some(return_type) = &call.return_type {
+        properties["return_type"] = json!(return_type);
+    }
+    let mut node = node(id, "call-function", Some(parent), properties);
+    node.label = Some(call.target.clone());
+    node
+}
+
+fn node(id: &str, plugin_type: &str, parent: Option<&str>, properties: Value) -> Node {
+    Node {
+        id: id.to_string(),
+        plugin_type: plugin_type.to_string(),
+        label: None,
+        properties: serde_json::from_value(properties).unwrap_or_default(),
+        parent_id: parent.map(String::from),
+    }
+}
+
+fn connection(from: &str, to: &str, variable_mapping: Option<HashMap<String, String>>) -> Connection {
+    Connection {
+        from: from.to_string(),
+        to: to.to_string(),
+        connection_type: ConnectionType::Simple,
+        variable_mapping,
+        from_port: None,
+        to_port: None,
+    }
+}
+
+/// Source text by span, with `.await` inserted after calls of imported functions
+struct Source<'s> {
+    text: &'s str,
+    line_starts: Vec<usize>,
+    awaits: BTreeSet<usize>,
+}
+
+impl<'s> Source<'s> {
+    fn new(text: &'s str) -> Self {
+        let line_starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Source { text, line_starts, awaits: BTreeSet::new() }
+    }
+
+    /// Byte offset of a span position (line from 1, column in characters)
+    fn offset(&self, at: LineColumn) -> usize {
+        let start = self.line_starts.get(at.line.saturating_sub(1)).copied().unwrap_or(self.text.len());
+        self.text[start..].char_indices().nth(at.column).map_or(self.text.len(), |(i, _)| start + i)
+    }
+
+    fn slice(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut at = start;
+        for &insert in self.awaits.range(start + 1..=end) {
+            text.push_str(&self.text[at..insert]);
+            text.push_str(".await");
+            at = insert;
+        }
+        text.push_str(&self.text[at..end]);
+        text
+    }
+
+    fn text(&self, node: &impl Spanned) -> String {
+        let span = node.span();
+        self.slice(self.offset(span.start()), self.offset(span.end()))
+    }
+
+    /// Statements and comments between the braces, dedented
+    fn block_body(&self, block: &Block) -> String {
+        let span = block.brace_token.span;
+        let body = self.slice(self.offset(span.open().end()), self.offset(span.close().start()));
+        let lines: Vec<&str> = body.lines().skip_while(|l| l.trim().is_empty()).collect();
+        let indent = lines.iter()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.len() - l.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines: Vec<&str> = lines.iter().map(|l| l.get(indent..).unwrap_or(l.trim_start())).collect();
+        lines.join("\n").trim().to_string()
+    }
+}
+
+/// Imported functions called from a body, and those called where `.await` is not allowed
+struct CallGraph<'a> {
+    functions: &'a HashMap<String, Signature>,
+    calls: BTreeSet<String>,
+    sync_calls: BTreeSet<String>,
+    /// Synchronous closures and items entered
+    sync_depth: usize,
+}
+
+impl<'ast> Visit<'ast> for CallGraph<'_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Some(name) = local_call(expr, self.functions) {
+            if self.sync_depth > 0 {
+                self.sync_calls.insert(name.clone());
+            }
+            self.calls.insert(name);
+        }
+        match expr {
+            Expr::Closure(closure) if closure.asyncness.is_none() => {
+                self.sync_depth += 1;
+                visit::visit_expr_closure(self, closure);
+                self.sync_depth -= 1;
+            }
+            _ => visit::visit_expr(self, expr),
+        }
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        self.sync_depth += 1;
+        visit::visit_item(self, item);
+        self.sync_depth -= 1;
+    }
+}
+
+/// End offsets of imported-function calls that are not awaited yet
+struct LocalCalls<'a, 's> {
+    source: &'a Source<'s>,
+    functions: &'a HashMap<String, Signature>,
+    awaits: BTreeSet<usize>,
+}
+
+impl<'ast> Visit<'ast> for LocalCalls<'_, '_> {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            Expr::Await(awaited) if local_call(&awaited.base, self.functions).is_some() => {
+                if let Expr::Call(call) = &*awaited.base {
+                    visit::visit_expr_call(self, call);
+                }
+            }
+            Expr::Call(_) if local_call(expr, self.functions).is_some() => {
+                self.awaits.insert(self.source.offset(expr.span().end()));
+                visit::visit_expr(self, expr);
+            }
+            // `.await` is not allowed in synchronous closures
+            Expr::Closure(closure) if closure.asyncness.is_none() => {}
+            _ => visit::visit_expr(self, expr),
+        }
+    }
+
+    // Nested items are synchronous as well
+    fn visit_item(&mut self, _: &'ast Item) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flust_core::modules::ModuleGraph;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    #[test]
+    fn test_functions_become_definitions_with_calls_or_legacy_code() {
+        let source = r#"use std::fmt;
+
+/// A point
+struct Point { x: f64 }
+
+fn half(x: f64) -> f64 {
+    // halve it
+    x / 2.0
+}
+
+pub fn parse(text: &str) -> Result<f64, std::num::ParseFloatError> {
+    let value: f64 = text.parse()?;
+    Ok(half(value))
+}
+
+fn main() {
+    let h = half(3.0);
+    let mut p = parse("4")?;
+    report(half(h));
+}
+
+fn report(v: f64) {
+    println!("{}", v);
+}
+
+fn id<T>(t: T) -> T { t }
+"#;
+        let import = import_rust(source).unwrap();
+        let flow = &import.flow;
+        let node = |id: &str| flow.nodes.iter().find(|n| n.id == id).unwrap();
+
+        assert_eq!(import.notes, vec!["fn 'id' is generic; kept as module code"]);
+        assert_eq!(node("module_items").properties["code"], "use std::fmt;\n\n/// A point\nstruct Point { x: f64 }\n\nfn id<T>(t: T) -> T { t }");
+        assert_eq!(node("half").properties["arguments"], json!([{"name": "x", "type": "f64"}]));
+        assert_eq!(node("half_body").properties["code"], "// halve it\nx / 2.0");
+        assert_eq!(node("parse").properties["return_type"], "f64");
+        assert_eq!(node("parse").properties["error_type"], "std::num::ParseFloatError");
+        assert_eq!(node("parse").properties["export"], true);
+        assert_eq!(node("parse_body").properties["code"], "let value: f64 = text.parse()?;\nOk(half(value).await)");
+
+        // `main` is not fallible, so `parse(..)?` keeps it as legacy code
+        assert_eq!(node("main_body").properties["code"], "let h = half(3.0).await;\nlet mut p = parse(\"4\").await?;\nreport(half(h).await).await;");
+        assert_eq!(node("report_body").properties["code"], "println!(\"{}\", v);");
+
+        let calls = import_rust("fn a(x: i32) {}\nfn b() -> i32 { 1 }\nfn main() {\n    let mut v: i32 = b();\n    a(v + 1);\n}\n").unwrap().flow;
+        let call = calls.nodes.iter().find(|n| n.id == "main_call_1").unwrap();
+        assert_eq!(call.properties["target_function"], "b");
+        assert_eq!(call.properties["return_variable"], "v");
+        assert_eq!(call.properties["return_type"], "i32");
+        assert_eq!(call.properties["is_mutable"], true);
+        let into_a = calls.connections.iter().find(|c| c.to == "main_call_2").unwrap();
+        assert_eq!(into_a.from, "main_call_1");
+        assert_eq!(into_a.variable_mapping.as_ref().unwrap()["x"], "v + 1");
+    }
+
+    /// `cargo check` of the binary generated from the imported flow
+    fn assert_compiles(import: &RustImport, name: &str) {
+        let dir = std::env::temp_dir().join(format!("flust-import-{}-{}", name, std::process::id()));
+        let root = PathBuf::from(format!("{}.flow.json", name));
+        let graph = ModuleGraph::load_with(&root, |_| Ok(import.flow.clone())).unwrap();
+        crate::project::generate_build(&graph, &dir).unwrap();
+        let output = Command::new("cargo").args(["check", "--quiet"]).current_dir(&dir).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    #[test]
+    fn test_functions_that_cannot_be_async_stay_module_code() {
+        let source = r#"fn fact(n: u64) -> u64 {
+    if n == 0 { one() } else { n * fact(n - 1) }
+}
+
+fn one() -> u64 { 1 }
+
+fn is_even(n: u32) -> bool { if n == 0 { true } else { is_odd(n - 1) } }
+
+fn is_odd(n: u32) -> bool { if n == 0 { false } else { is_even(n - 1) } }
+
+fn double(x: i32) -> i32 { x * 2 }
+
+fn triple(x: i32) -> i32 { x * 3 }
+
+struct Scale;
+
+impl Scale {
+    fn apply(&self, x: i32) -> i32 { triple(x) }
+}
+
+fn greet() {
+    println!("hi");
+}
+
+fn main() {
+    let doubled: Vec<i32> = vec![1, 2].into_iter().map(|x| double(x)).collect();
+    greet();
+    println!("{:?} {} {} {}", doubled, fact(5), is_even(4), Scale.apply(2));
+}
+"#;
+        let import = import_rust(source).unwrap();
+        assert_eq!(import.notes, vec![
+            "fn 'fact' is recursive; kept as module code",
+            "fn 'one' is called by 'fact', which stays synchronous; kept as module code",
+            "fn 'is_even' is recursive; kept as module code",
+            "fn 'is_odd' is recursive; kept as module code",
+            "fn 'double' is called where it cannot be awaited (a closure or an item); kept as module code",
+            "fn 'triple' is called where it cannot be awaited (a closure or an item); kept as module code",
+        ]);
+        let main = import.flow.nodes.iter().find(|n| n.id == "main_body").unwrap();
+        assert!(main.properties["code"].as_str().unwrap().contains("greet().await;\nprintln!(\"{:?} {} {} {}\", doubled, fact(5), is_even(4)"));
+        assert_compiles(&import, "synchronous");
+    }
+}
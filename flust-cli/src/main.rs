@@ -1,8 +1,18 @@
-use clap::{Parser, Subcommand};
-use flust_core::parser;
-use flust_codegen::generator;
+mod check;
+mod diff;
+mod fmt;
+mod import_rust;
+mod merge;
+mod project;
+mod test_runner;
+mod watch;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use flust_core::graph;
+use flust_core::modules::ModuleGraph;
+use flust_codegen::generator::{Instrumentation, Target};
+use flust_codegen::profile;
 use std::path::PathBuf;
-use std::fs;
 use std::process::Command;
 use anyhow::Result;
 
@@ -24,39 +34,288 @@ enum Commands {
         /// Output directory for the generated project
         #[arg(short, long)]
         output: PathBuf,
+
+        /// Generate a library crate (`lib.rs`) from the exported functions instead of a binary
+        #[arg(long)]
+        lib: bool,
+
+        #[command(flatten)]
+        instrument: InstrumentArgs,
+    },
+    /// Run the test-case nodes of a flow with `cargo test`
+    Test {
+        /// Input flow file (YAML/JSON)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Directory the test project is generated into
+        #[arg(short, long, default_value = ".flust/test")]
+        output: PathBuf,
+    },
+    /// Compile, build and run a flow, exiting with the program's status
+    Run {
+        /// Input flow file (YAML/JSON)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Cached build directory, reused between runs
+        #[arg(long, default_value = ".flust/build")]
+        build_dir: PathBuf,
+
+        #[command(flatten)]
+        instrument: InstrumentArgs,
+
+        /// With --profile, also write the folded stacks of the run (flamegraph input) to this file
+        #[arg(long, requires = "profile")]
+        folded: Option<PathBuf>,
+
+        /// Arguments passed to the program (after `--`)
+        #[arg(last = true)]
+        args: Vec<String>,
     },
+    /// Parse, validate and type-check flow files without generating a project
+    Check {
+        /// Flow files to check (their imports are checked too)
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "human")]
+        format: check::Format,
+    },
+    /// Render a flow as a Graphviz DOT, Mermaid or SVG diagram
+    Graph {
+        /// Input flow file (YAML/JSON)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output format; `svg` is drawn without Graphviz
+        #[arg(long, value_enum, default_value = "dot")]
+        format: GraphFormat,
+
+        /// Write the diagram to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare two flow files semantically, ignoring order and layout (exit code 1 if they differ)
+    Diff {
+        /// Original flow file
+        old: PathBuf,
+
+        /// Changed flow file
+        new: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "human")]
+        format: diff::Format,
+    },
+    /// Three-way merge of flow files; usable as a git merge driver (`merge %O %A %B`)
+    Merge {
+        /// Common ancestor
+        base: PathBuf,
+
+        /// Our version, overwritten with the result unless `--output` is given
+        ours: PathBuf,
+
+        /// Their version
+        theirs: PathBuf,
+
+        /// Write the merged flow here instead of over `ours`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Rewrite flow files (IR or editor documents) in canonical form
+    Fmt {
+        /// Flow files to format
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Only report unformatted files, exiting with 1 if there are any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Convert the top-level functions of a Rust file into a flow
+    ImportRust {
+        /// Rust source file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Flow file to write instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Regenerate a flow whenever it, its imported flows or the plugins change
+    Watch {
+        /// Input flow file (YAML/JSON)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Plugin directory to watch
+        #[arg(long, default_value = "frontend/public/plugins")]
+        plugins: PathBuf,
+
+        /// Cached build directory the flow is generated into
+        #[arg(long, default_value = ".flust/build")]
+        build_dir: PathBuf,
+
+        /// Also build the generated project with cargo
+        #[arg(long)]
+        build: bool,
+
+        /// Build and run the program, restarting it after each change
+        #[arg(long)]
+        run: bool,
+
+        /// Arguments passed to the program (after `--`)
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+}
+
+/// Instrumentation of the generated program
+#[derive(Args)]
+struct InstrumentArgs {
+    /// Report entry and exit of every node as JSON lines on stderr (or to FLUST_TRACE_ADDR)
+    #[arg(long)]
+    trace: bool,
+
+    /// With --trace, also report the values of the variables each node writes
+    #[arg(long, requires = "trace")]
+    trace_values: bool,
+
+    /// Time every node and function call; the samples are written when main ends (to stderr or FLUST_PROFILE_OUT)
+    #[arg(long, conflicts_with = "trace")]
+    profile: bool,
+}
+
+impl InstrumentArgs {
+    fn instrumentation(&self) -> Instrumentation {
+        if self.trace {
+            Instrumentation::Trace { values: self.trace_values }
+        } else if self.profile {
+            Instrumentation::Profile
+        } else {
+            Instrumentation::None
+        }
+    }
+}
+
+/// Report of a profiled run on stderr, so it does not mix with the program's output
+fn print_profile(samples: &std::path::Path, folded: Option<&std::path::Path>) -> Result<()> {
+    let Some(samples) = std::fs::read_to_string(samples).ok().as_deref().and_then(profile::parse_samples) else {
+        eprintln!("No profile was recorded (the program exited before main returned)");
+        return Ok(());
+    };
+    let report = profile::report(&samples);
+    eprint!("{}", report);
+    if let Some(path) = folded {
+        std::fs::write(path, &report.folded)?;
+        eprintln!("Folded stacks written to {:?}", path);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+    Svg,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Compile { input, output } => {
+        Commands::Compile { input, output, lib, instrument } => {
+            let target = if *lib { Target::Library } else { Target::Binary };
             println!("Compiling {:?} to {:?}", input, output);
 
-            // 1. Parse Flow
-            let flow = parser::parse_file(input)?;
-            println!("Parsed flow with {} nodes", flow.nodes.len());
-
-            // 2. Generate Code
-            let rust_code = generator::generate_rust(&flow)?;
-
-            // 3. Create Output Project
-            if !output.exists() {
-                fs::create_dir_all(output)?;
-                Command::new("cargo")
-                    .arg("init")
-                    .arg("--bin")
-                    .current_dir(output)
-                    .output()?;
+            // 1. Parse the flow and every flow file it imports
+            let graph = ModuleGraph::load(input)?;
+            for module in &graph.modules {
+                println!("Parsed {:?} with {} nodes", module.path, module.flow.nodes.len());
             }
 
-            // 4. Write Main.rs
-            let main_rs = output.join("src").join("main.rs");
-            fs::write(main_rs, rust_code)?;
+            // 2. Generate the code (one module per flow file) into the output project
+            project::write_project(&graph, output, target, instrument.instrumentation(), None)?;
 
             println!("Compilation successful!");
         }
+        Commands::Test { input, output } => {
+            if !test_runner::run(input, output)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Run { input, build_dir, instrument, folded, args } => {
+            let graph = ModuleGraph::load(input)?;
+            let binary = project::build(&graph, build_dir, instrument.instrumentation())?;
+            // stdin, stdout and stderr are inherited by the program
+            let mut program = Command::new(&binary);
+            program.args(args);
+            let samples = build_dir.join("profile.json");
+            if instrument.profile {
+                let _ = std::fs::remove_file(&samples);
+                program.env(profile::OUTPUT_VAR, &samples);
+            }
+            let status = program.status()?;
+            if instrument.profile {
+                print_profile(&samples, folded.as_deref())?;
+            }
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        Commands::Check { inputs, format } => {
+            if !check::run(inputs, *format)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Graph { input, format, output } => {
+            let flow = flust_core::parser::parse_file(input)?;
+            let diagram = match format {
+                GraphFormat::Dot => graph::to_dot(&flow),
+                GraphFormat::Mermaid => graph::to_mermaid(&flow),
+                GraphFormat::Svg => graph::to_svg(&flow),
+            };
+            match output {
+                Some(output) => std::fs::write(output, diagram)?,
+                None => print!("{}", diagram),
+            }
+        }
+        Commands::Diff { old, new, format } => {
+            if !diff::run(old, new, *format)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Merge { base, ours, theirs, output } => {
+            if !merge::run(base, ours, theirs, output.as_deref())? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Fmt { inputs, check } => {
+            if !fmt::run(inputs, *check)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::ImportRust { input, output } => {
+            let import = import_rust::import_rust(&std::fs::read_to_string(input)?)?;
+            for note in &import.notes {
+                eprintln!("note: {}", note);
+            }
+            let flow = flust_core::formatter::format_flow(&import.flow)?;
+            match output {
+                Some(output) => std::fs::write(output, flow)?,
+                None => print!("{}", flow),
+            }
+        }
+        Commands::Watch { input, plugins, build_dir, build, run, args } => {
+            watch::watch(&watch::WatchOptions {
+                input: input.clone(),
+                plugins: plugins.clone(),
+                build_dir: build_dir.clone(),
+                build: *build,
+                run: *run,
+                args: args.clone(),
+            })?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,27 @@
+use crate::fmt::is_yaml;
+use anyhow::{Result, anyhow};
+use flust_core::{formatter, merge};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Three-way merge of flow files, written to `output` (our file by default, as git merge drivers expect)
+/// Conflicts keep our version and are listed on stderr; returns whether the merge was clean
+pub fn run(base: &Path, ours: &Path, theirs: &Path, output: Option<&Path>) -> Result<bool> {
+    let result = merge::merge(&read(base)?, &read(ours)?, &read(theirs)?)?;
+    let output = output.unwrap_or(ours);
+    fs::write(output, formatter::format_document(result.document, is_yaml(output))?)?;
+
+    for conflict in &result.conflicts {
+        eprintln!("{}", conflict);
+    }
+    if !result.conflicts.is_empty() {
+        eprintln!("{} conflict(s); our version was kept for each", result.conflicts.len());
+    }
+    Ok(result.conflicts.is_empty())
+}
+
+/// Flow document as written, in the IR or the editor layout
+pub fn read(path: &Path) -> Result<Value> {
+    formatter::parse_value(&fs::read_to_string(path)?).map_err(|error| anyhow!("{}: {}", path.display(), error))
+}
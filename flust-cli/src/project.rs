@@ -0,0 +1,155 @@
+use anyhow::{Result, anyhow};
+use flust_codegen::generator::{self, GeneratedFile, Instrumentation, Target};
+use flust_core::modules::ModuleGraph;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Fingerprint of the generated code of the last successful build
+const FINGERPRINT_FILE: &str = ".flust-fingerprint";
+
+/// Generate the crate for `graph` into `output`, creating the cargo project on first use
+/// The package is named `name`, or after the directory when `None`
+pub fn write_project(graph: &ModuleGraph, output: &Path, target: Target, instrumentation: Instrumentation, name: Option<&str>) -> Result<()> {
+    // One module per flow file
+    let files = generator::generate_project_with(graph, target, instrumentation)?;
+    init_project(output, target, name)?;
+    write_files(output, &files)
+}
+
+/// Generate the binary crate for `graph` into the cached `build_dir` without building it
+pub fn generate_build(graph: &ModuleGraph, build_dir: &Path) -> Result<()> {
+    let files = generator::generate_project(graph, Target::Binary)?;
+    init_project(build_dir, Target::Binary, Some(&graph.root().name))?;
+    write_files(build_dir, &files)
+}
+
+/// Generate and build the binary for `graph` in `build_dir`, returning the executable
+/// Cargo is skipped when the generated code matches the last successful build
+pub fn build(graph: &ModuleGraph, build_dir: &Path, instrumentation: Instrumentation) -> Result<PathBuf> {
+    let files = generator::generate_project_with(graph, Target::Binary, instrumentation)?;
+    init_project(build_dir, Target::Binary, Some(&graph.root().name))?;
+
+    let manifest = fs::read_to_string(build_dir.join("Cargo.toml"))?;
+    let package = package_name(&manifest)
+        .ok_or_else(|| anyhow!("No package name in {}", build_dir.join("Cargo.toml").display()))?;
+    let binary = build_dir.join("target").join("debug").join(format!("{}{}", package, std::env::consts::EXE_SUFFIX));
+
+    let fingerprint = fingerprint(&files);
+    let fingerprint_path = build_dir.join(FINGERPRINT_FILE);
+    if binary.exists() && fs::read_to_string(&fingerprint_path).is_ok_and(|last| last == fingerprint) {
+        return Ok(binary);
+    }
+
+    write_files(build_dir, &files)?;
+    let status = Command::new("cargo").arg("build").arg("--quiet").current_dir(build_dir).status()?;
+    if !status.success() {
+        return Err(anyhow!("cargo build failed in {}", build_dir.display()));
+    }
+    fs::write(&fingerprint_path, fingerprint)?;
+    Ok(binary)
+}
+
+/// `cargo init` the project unless it exists; the package is named after the directory by default
+/// Build directories pass the root flow's name, since names like `build` are not valid packages
+fn init_project(output: &Path, target: Target, name: Option<&str>) -> Result<()> {
+    if output.join("Cargo.toml").exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(output)?;
+    let mut init = Command::new("cargo");
+    init.arg("init").arg(if target == Target::Library { "--lib" } else { "--bin" });
+    if let Some(name) = name {
+        init.arg("--name").arg(name);
+    }
+    let result = init.current_dir(output).output()?;
+    if !result.status.success() {
+        return Err(anyhow!("cargo init failed in {}:\n{}", output.display(), String::from_utf8_lossy(&result.stderr)));
+    }
+    add_tokio_dependency(output)
+}
+
+/// The crate root and the imported modules
+fn write_files(output: &Path, files: &[GeneratedFile]) -> Result<()> {
+    for file in files {
+        fs::write(output.join("src").join(&file.path), &file.code)?;
+    }
+    Ok(())
+}
+
+/// Generated functions are async, so the project needs tokio
+fn add_tokio_dependency(project: &Path) -> Result<()> {
+    let manifest = project.join("Cargo.toml");
+    let mut content = fs::read_to_string(&manifest)?;
+    if !content.contains("tokio") {
+        if !content.contains("[dependencies]") {
+            content.push_str("\n[dependencies]\n");
+        }
+        content = content.replacen("[dependencies]\n", "[dependencies]\ntokio = { version = \"1\", features = [\"full\"] }\n", 1);
+        fs::write(&manifest, content)?;
+    }
+    Ok(())
+}
+
+/// SHA-256 of the files, stable across toolchains unlike `DefaultHasher`
+fn fingerprint(files: &[GeneratedFile]) -> String {
+    let mut hasher = Sha256::new();
+    for file in files {
+        // Length prefixes keep the boundary between path and code unambiguous
+        let path = file.path.to_string_lossy();
+        hasher.update((path.len() as u64).to_le_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update((file.code.len() as u64).to_le_bytes());
+        hasher.update(file.code.as_bytes());
+    }
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `name = "..."` of the `[package]` section
+fn package_name(manifest: &str) -> Option<&str> {
+    manifest.lines()
+        .skip_while(|line| line.trim() != "[package]")
+        .take_while(|line| line.trim() == "[package]" || !line.trim_start().starts_with('['))
+        .find_map(|line| line.trim().strip_prefix("name")?.trim_start().strip_prefix('='))
+        .map(|value| value.trim().trim_matches('"'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flust_core::ir::Flow;
+
+    #[test]
+    fn test_package_name_and_fingerprint() {
+        let manifest = "[package]\nname = \"build\"\nversion = \"0.1.0\"\n\n[dependencies]\nname = \"ignored\"\n";
+        assert_eq!(package_name(manifest), Some("build"));
+        assert_eq!(package_name("[dependencies]\nname = \"x\"\n"), None);
+
+        let main = |code: &str| vec![GeneratedFile { path: PathBuf::from("main.rs"), code: code.to_string() }];
+        assert_eq!(fingerprint(&main("fn main() {}")), fingerprint(&main("fn main() {}")));
+        assert_ne!(fingerprint(&main("fn main() {}")), fingerprint(&main("fn main() { }")));
+        // Pinned, so a toolchain update cannot change the fingerprint of unchanged code
+        assert_eq!(fingerprint(&main("fn main() {}")), "23d67b5f561add689cedc9482eb2a3deb8bfd4190f04b246b01449a3370abab0");
+    }
+
+    #[test]
+    fn test_collections_mutated_in_place_compile() {
+        let flow: Flow = serde_json::from_str(r#"{"nodes": [
+            {"id": "main", "plugin_type": "function-definition", "properties": {"function_name": "main"}},
+            {"id": "nums", "plugin_type": "vec-new", "parent_id": "main", "properties": {"element_type": "i32"}},
+            {"id": "add", "plugin_type": "push", "parent_id": "main", "properties": {"value": "4"}},
+            {"id": "show", "plugin_type": "debug", "parent_id": "main", "properties": {"variable": "nums_value"}}
+        ], "connections": [
+            {"from": "nums", "to": "add", "from_port": "result", "to_port": "list"},
+            {"from": "add", "to": "show"}
+        ]}"#).unwrap();
+        let dir = std::env::temp_dir().join(format!("flust-mutated-{}", std::process::id()));
+        let graph = ModuleGraph::load_with(Path::new("mutated.flow.json"), |_| Ok(flow.clone())).unwrap();
+
+        generate_build(&graph, &dir).unwrap();
+        let output = Command::new("cargo").args(["check", "--quiet"]).current_dir(&dir).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+}
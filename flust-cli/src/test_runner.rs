@@ -0,0 +1,136 @@
+use crate::project;
+use anyhow::{Result, anyhow};
+use flust_codegen::generator::{Instrumentation, Target};
+use flust_codegen::test_cases;
+use flust_core::modules::ModuleGraph;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Test-case node a generated test comes from
+struct Origin {
+    flow_file: String,
+    test_node: String,
+    function: String,
+    function_node: String,
+}
+
+/// Failed test with the panic output of its `---- name stdout ----` section
+#[derive(Debug, PartialEq, Eq)]
+struct Failure {
+    test: String,
+    message: String,
+}
+
+/// Generate the project with its test modules, run `cargo test` and report failures per function node
+/// Returns whether every test passed
+pub fn run(input: &Path, output: &Path) -> Result<bool> {
+    let graph = ModuleGraph::load(input)?;
+    let origins = origins(&graph)?;
+    if origins.is_empty() {
+        println!("No test-case nodes found");
+        return Ok(true);
+    }
+    // Named after the root flow: the default `.flust/test` directory is not a valid package name
+    project::write_project(&graph, output, Target::Binary, Instrumentation::None, Some(&graph.root().name))?;
+
+    // Backtraces would bury the assertion messages
+    let result = Command::new("cargo").arg("test").env("RUST_BACKTRACE", "0").current_dir(output).output()?;
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    if result.status.success() {
+        println!("{} test(s) passed", origins.len());
+        return Ok(true);
+    }
+
+    let failures = failures(&stdout);
+    if failures.is_empty() {
+        // The generated project did not build
+        return Err(anyhow!("cargo test failed:\n{}", String::from_utf8_lossy(&result.stderr)));
+    }
+    for failure in &failures {
+        match origins.get(&failure.test) {
+            Some(origin) => println!(
+                "FAILED {} (test-case '{}') in function '{}' (node '{}', {})",
+                failure.test, origin.test_node, origin.function, origin.function_node, origin.flow_file
+            ),
+            None => println!("FAILED {}", failure.test),
+        }
+        for line in failure.message.lines() {
+            println!("    {}", line);
+        }
+    }
+    println!("{} of {} test(s) failed", failures.len(), origins.len());
+    Ok(false)
+}
+
+/// Test path as printed by cargo (`tests::name`, `module::tests::name`) -> originating nodes
+fn origins(graph: &ModuleGraph) -> Result<HashMap<String, Origin>> {
+    let root = &graph.root().name;
+    let mut origins = HashMap::new();
+    for module in &graph.modules {
+        let prefix = if &module.name == root { String::new() } else { format!("{}::", module.name) };
+        for case in test_cases::test_cases(&module.flow)? {
+            let function = case.function.properties.get("function_name").and_then(|v| v.as_str()).unwrap_or("unknown");
+            origins.insert(format!("{}tests::{}", prefix, case.name), Origin {
+                flow_file: module.path.display().to_string(),
+                test_node: case.node.id.clone(),
+                function: function.to_string(),
+                function_node: case.function.id.clone(),
+            });
+        }
+    }
+    Ok(origins)
+}
+
+fn failures(stdout: &str) -> Vec<Failure> {
+    let mut failures: Vec<Failure> = stdout.lines()
+        .filter_map(|line| line.strip_prefix("test ")?.strip_suffix(" ... FAILED"))
+        .map(|test| Failure { test: test.to_string(), message: String::new() })
+        .collect();
+
+    let mut current: Option<usize> = None;
+    for line in stdout.lines() {
+        if let Some(test) = line.strip_prefix("---- ").and_then(|l| l.strip_suffix(" stdout ----")) {
+            current = failures.iter().position(|f| f.test == test);
+        } else if line == "failures:" {
+            current = None;
+        } else if let Some(index) = current
+            && !line.trim().is_empty()
+            && !line.starts_with("note: run with `RUST_BACKTRACE=1`")
+        {
+            let message = &mut failures[index].message;
+            message.push_str(line);
+            message.push('\n');
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_are_parsed_from_cargo_output() {
+        let stdout = "running 2 tests\n\
+            test tests::halves_ten ... FAILED\n\
+            test tests::half_t2 ... ok\n\
+            \n\
+            failures:\n\
+            \n\
+            ---- tests::halves_ten stdout ----\n\
+            \n\
+            thread 'tests::halves_ten' panicked at src/main.rs:12:9:\n\
+            assertion `left == right` failed\n\
+            note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n\
+            \n\
+            \n\
+            failures:\n\
+            \x20   tests::halves_ten\n";
+
+        assert_eq!(failures(stdout), vec![Failure {
+            test: "tests::halves_ten".to_string(),
+            message: "thread 'tests::halves_ten' panicked at src/main.rs:12:9:\nassertion `left == right` failed\n".to_string(),
+        }]);
+    }
+}
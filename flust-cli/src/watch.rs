@@ -0,0 +1,232 @@
+use crate::project;
+use anyhow::Result;
+use flust_codegen::generator::Instrumentation;
+use flust_core::diagnose;
+use flust_core::modules::ModuleGraph;
+use notify::{Event, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// Changes arriving this close together are handled as one
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+pub struct WatchOptions {
+    pub input: PathBuf,
+    pub plugins: PathBuf,
+    pub build_dir: PathBuf,
+    pub build: bool,
+    pub run: bool,
+    pub args: Vec<String>,
+}
+
+/// Regenerate the flow on every change of its flow files or plugins, restarting the program with `run`
+pub fn watch(options: &WatchOptions) -> Result<()> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let plugins = absolute(&options.plugins);
+    if plugins.is_dir() {
+        watcher.watch(&plugins, RecursiveMode::Recursive)?;
+    }
+
+    let mut program: Option<Child> = None;
+    let mut directories: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut watched = WatchSet::new(&options.input, &plugins);
+
+    loop {
+        stop(&mut program);
+        if let Some(graph) = rebuild(options, &mut program) {
+            watched.follow(&graph);
+        }
+
+        let wanted = watched.directories();
+        for removed in directories.difference(&wanted) {
+            let _ = watcher.unwatch(removed);
+        }
+        for added in wanted.difference(&directories) {
+            watcher.watch(added, RecursiveMode::NonRecursive)?;
+        }
+        directories = wanted;
+
+        let changed = wait_for_change(&events, |path| watched.is_relevant(path))?;
+        println!("\n↻ {} changed", changed.display());
+    }
+}
+
+/// Paths whose changes trigger a rebuild: every flow file of the import graph, and the plugins
+struct WatchSet {
+    flow_files: BTreeSet<PathBuf>,
+    plugins: PathBuf,
+}
+
+impl WatchSet {
+    /// Only the input until its imports are known
+    fn new(input: &Path, plugins: &Path) -> Self {
+        WatchSet { flow_files: BTreeSet::from([absolute(input)]), plugins: absolute(plugins) }
+    }
+
+    /// Watch the flow files of the last graph that loaded
+    fn follow(&mut self, graph: &ModuleGraph) {
+        self.flow_files = graph.modules.iter().map(|m| absolute(&m.path)).collect();
+    }
+
+    /// Directories are watched rather than files, so editors replacing a file are still seen
+    fn directories(&self) -> BTreeSet<PathBuf> {
+        self.flow_files.iter().filter_map(|f| f.parent().map(Path::to_path_buf)).collect()
+    }
+
+    fn is_relevant(&self, path: &Path) -> bool {
+        self.flow_files.contains(path) || path.starts_with(&self.plugins)
+    }
+}
+
+/// Parse, validate and generate, then build and start the program when asked
+/// Returns the loaded graph so its flow files can be watched
+fn rebuild(options: &WatchOptions, program: &mut Option<Child>) -> Option<ModuleGraph> {
+    let graph = match ModuleGraph::load(&options.input) {
+        Ok(graph) => graph,
+        Err(error) => {
+            println!("✖ {}", error);
+            return None;
+        }
+    };
+
+    let results = diagnose::diagnose(&graph);
+    for result in &results {
+        for finding in &result.findings {
+            println!("  {} ({})", finding.diagnostic, result.module.path.display());
+        }
+    }
+    if results.iter().any(|r| r.has_errors()) {
+        println!("✖ validation failed");
+        return Some(graph);
+    }
+
+    if !(options.build || options.run) {
+        match project::generate_build(&graph, &options.build_dir) {
+            Ok(()) => println!("✔ generated {} module(s) into {}", graph.modules.len(), options.build_dir.display()),
+            Err(error) => println!("✖ {}", error),
+        }
+        return Some(graph);
+    }
+    let binary = match project::build(&graph, &options.build_dir, Instrumentation::None) {
+        Ok(binary) => binary,
+        Err(error) => {
+            println!("✖ {}", error);
+            return Some(graph);
+        }
+    };
+    println!("✔ built {}", binary.display());
+
+    if options.run {
+        match Command::new(&binary).args(&options.args).spawn() {
+            Ok(child) => *program = Some(child),
+            Err(error) => println!("✖ failed to start {}: {}", binary.display(), error),
+        }
+    }
+    Some(graph)
+}
+
+/// Kill the previously started program, reporting it if it had already exited
+fn stop(program: &mut Option<Child>) {
+    let Some(mut child) = program.take() else { return };
+    match child.try_wait() {
+        Ok(Some(status)) => println!("■ program exited ({})", status),
+        _ => {
+            let _ = child.kill();
+            let _ = child.wait();
+            println!("■ program stopped");
+        }
+    }
+}
+
+/// Block until a relevant path changes, then drain the burst of events that follows
+fn wait_for_change(events: &Receiver<notify::Result<Event>>, relevant: impl Fn(&Path) -> bool) -> Result<PathBuf> {
+    let is_change = |event: &Event| event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove();
+    loop {
+        let event = events.recv()??;
+        let Some(path) = event.paths.iter().find(|p| relevant(p)).filter(|_| is_change(&event)) else { continue };
+        let path = path.clone();
+        while events.recv_timeout(DEBOUNCE).is_ok() {}
+        return Ok(path);
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flust_core::ir::{Flow, ModuleImport};
+    use notify::event::{AccessKind, EventKind, ModifyKind};
+
+    fn modified(path: &Path) -> notify::Result<Event> {
+        Ok(Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path.to_path_buf()))
+    }
+
+    #[test]
+    fn test_watch_set_and_debounced_changes() {
+        let app = Flow {
+            modules: vec![ModuleImport { path: "lib/util.flow.json".to_string(), items: Vec::new() }],
+            ..Default::default()
+        };
+        let graph = ModuleGraph::load_with(Path::new("app.flow.json"), |path| {
+            Ok(if path == Path::new("app.flow.json") { app.clone() } else { Flow::default() })
+        }).unwrap();
+        let mut watched = WatchSet::new(Path::new("app.flow.json"), Path::new("plugins"));
+        assert_eq!(watched.flow_files, BTreeSet::from([absolute(Path::new("app.flow.json"))]));
+
+        watched.follow(&graph);
+        assert!(watched.is_relevant(&absolute(Path::new("lib/util.flow.json"))));
+        assert!(watched.is_relevant(&absolute(Path::new("plugins/add/plugin.json"))));
+        assert!(!watched.is_relevant(&absolute(Path::new("notes.flow.json"))));
+        assert_eq!(watched.directories(), BTreeSet::from([std::env::current_dir().unwrap(), absolute(Path::new("lib"))]));
+
+        // Reads and unrelated files are skipped; the burst after the first change is drained
+        let (sender, events) = mpsc::channel();
+        let util = absolute(Path::new("lib/util.flow.json"));
+        sender.send(Ok(Event::new(EventKind::Access(AccessKind::Any)).add_path(util.clone()))).unwrap();
+        sender.send(modified(&absolute(Path::new("notes.flow.json")))).unwrap();
+        sender.send(modified(&util)).unwrap();
+        sender.send(modified(&util)).unwrap();
+        sender.send(modified(&absolute(Path::new("app.flow.json")))).unwrap();
+
+        assert_eq!(wait_for_change(&events, |path| watched.is_relevant(path)).unwrap(), util);
+        assert!(events.try_recv().is_err());
+
+        sender.send(Err(notify::Error::generic("watch lost"))).unwrap();
+        assert!(wait_for_change(&events, |path| watched.is_relevant(path)).is_err());
+    }
+
+    #[test]
+    fn test_rebuild_reports_without_building() {
+        let dir = std::env::temp_dir().join(format!("flust-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = WatchOptions {
+            input: dir.join("missing.flow.json"),
+            plugins: dir.join("plugins"),
+            build_dir: dir.join("build"),
+            build: true,
+            run: false,
+            args: Vec::new(),
+        };
+        let mut program = None;
+        assert!(rebuild(&options, &mut program).is_none());
+
+        // A flow that fails validation is still watched, but never built
+        let input = dir.join("app.flow.json");
+        std::fs::write(&input, r#"{"nodes": [
+            {"id": "x", "plugin_type": "let-variable", "properties": {"variable_name": "x", "value": "1"}},
+            {"id": "set", "plugin_type": "assign-variable", "properties": {"variable_name": "x", "value": "2"}}
+        ], "connections": [{"from": "x", "to": "set"}]}"#).unwrap();
+        let options = WatchOptions { input, ..options };
+        assert!(rebuild(&options, &mut program).is_some());
+        assert!(!options.build_dir.exists());
+        assert!(program.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+use flust_core::ir::Node;
+use std::collections::HashMap;
+
+/// Start of the panic message of a failed assertion node, followed by `<node id>': <message>`
+const FAILURE_PREFIX: &str = "assertion failed at node '";
+
+/// Assertion node that failed while running a generated program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionFailure {
+    pub node_id: String,
+    pub message: String,
+}
+
+pub fn is_assertion(node: &Node) -> bool {
+    matches!(node.plugin_type.as_str(), "assert" | "assert-eq" | "debug-assert")
+}
+
+/// `assert!` / `assert_eq!` / `debug_assert!` whose panic message names the node
+/// `operands` must hold `condition`, or `left` and `right` for assert-eq
+pub fn render(node: &Node, operands: &HashMap<&str, String>) -> String {
+    let operand = |name: &str| operands.get(name).map(String::as_str).unwrap_or_default();
+    let description = node.properties.get("message")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .or(node.label.as_deref())
+        .map(String::from);
+
+    let (assertion, arguments) = match node.plugin_type.as_str() {
+        "assert-eq" => ("assert_eq", format!("{}, {}", operand("left"), operand("right"))),
+        "debug-assert" => ("debug_assert", operand("condition").to_string()),
+        _ => ("assert", operand("condition").to_string()),
+    };
+    let description = description.unwrap_or_else(|| arguments.replace(", ", " == "));
+    let message = format!("{}{}': {}", FAILURE_PREFIX, node.id, description);
+    format!("{}!({}, \"{{}}\", {:?})", assertion, arguments, message)
+}
+
+/// First failed assertion node reported in the stderr of a generated program
+pub fn failed_assertion(stderr: &str) -> Option<AssertionFailure> {
+    let start = stderr.find(FAILURE_PREFIX)? + FAILURE_PREFIX.len();
+    let (node_id, rest) = stderr[start..].split_once("': ")?;
+    // assert_eq! continues with the `left:` / `right:` lines
+    let message: Vec<&str> = rest.lines()
+        .take_while(|line| !line.starts_with("note:") && !line.starts_with("stack backtrace:"))
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect();
+    Some(AssertionFailure { node_id: node_id.to_string(), message: message.join("\n") })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::node;
+    use serde_json::json;
+
+    #[test]
+    fn test_assertion_messages_name_their_node() {
+        let positive = node("positive", "assert", None, json!({"message": "reading must be positive {x}"}));
+        let operands = HashMap::from([("condition", "reading > 0.0".to_string())]);
+        assert_eq!(
+            render(&positive, &operands),
+            "assert!(reading > 0.0, \"{}\", \"assertion failed at node 'positive': reading must be positive {x}\")"
+        );
+
+        let same = node("same", "assert-eq", None, json!({"message": ""}));
+        let operands = HashMap::from([("left", "total".to_string()), ("right", "3".to_string())]);
+        assert_eq!(render(&same, &operands), "assert_eq!(total, 3, \"{}\", \"assertion failed at node 'same': total == 3\")");
+
+        let stderr = "\nthread 'main' panicked at src/main.rs:4:5:\nassertion `left == right` failed: assertion failed at node 'same': total == 3\n  left: 2\n right: 3\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        assert_eq!(failed_assertion(stderr), Some(AssertionFailure {
+            node_id: "same".to_string(),
+            message: "total == 3\n  left: 2\n right: 3".to_string(),
+        }));
+    }
+}
@@ -0,0 +1,285 @@
+use flust_core::ir::{Flow, Node};
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Channel flavour - maps to the tokio::sync module used for the channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    Mpsc,
+    Broadcast,
+    Watch,
+}
+
+impl ChannelKind {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "mpsc" => Ok(ChannelKind::Mpsc),
+            "broadcast" => Ok(ChannelKind::Broadcast),
+            "watch" => Ok(ChannelKind::Watch),
+            other => Err(anyhow!("Unknown channel kind: {}", other)),
+        }
+    }
+
+    fn module(&self) -> &'static str {
+        match self {
+            ChannelKind::Mpsc => "tokio::sync::mpsc",
+            ChannelKind::Broadcast => "tokio::sync::broadcast",
+            ChannelKind::Watch => "tokio::sync::watch",
+        }
+    }
+}
+
+/// Channel end required by a function that does not own the channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Endpoint {
+    Sender,
+    Receiver,
+}
+
+/// Channel declared by a `channel` node
+#[derive(Debug, Clone)]
+pub struct ChannelDef {
+    pub name: String,
+    pub kind: ChannelKind,
+    pub message_type: String,
+    pub capacity: u64,
+    pub initial_value: String,
+    /// Function whose body declares the channel
+    pub owner: String,
+    /// Whether the owning function sends on the channel itself
+    pub owner_sends: bool,
+}
+
+impl ChannelDef {
+    pub fn sender_name(&self) -> String {
+        format!("{}_tx", self.name)
+    }
+
+    pub fn receiver_name(&self) -> String {
+        format!("{}_rx", self.name)
+    }
+
+    /// `let (name_tx, mut name_rx) = ...;` statement for the owning scope
+    pub fn declaration(&self) -> String {
+        let constructor_arg = match self.kind {
+            ChannelKind::Watch => self.initial_value.clone(),
+            _ => self.capacity.to_string(),
+        };
+        format!(
+            "let ({}, mut {}) = {}::channel::<{}>({});",
+            self.sender_name(),
+            self.receiver_name(),
+            self.kind.module(),
+            self.message_type,
+            constructor_arg
+        )
+    }
+
+    /// Typed function parameter carrying the given end into another function
+    pub fn parameter(&self, endpoint: Endpoint) -> String {
+        match endpoint {
+            Endpoint::Sender => format!("{}: {}::Sender<{}>", self.sender_name(), self.kind.module(), self.message_type),
+            Endpoint::Receiver => format!("mut {}: {}::Receiver<{}>", self.receiver_name(), self.kind.module(), self.message_type),
+        }
+    }
+
+    /// Expression handing the given end to a called (or spawned) function
+    pub fn call_argument(&self, endpoint: Endpoint) -> String {
+        match (endpoint, self.kind) {
+            (Endpoint::Sender, _) => format!("{}.clone()", self.sender_name()),
+            (Endpoint::Receiver, ChannelKind::Mpsc) => self.receiver_name(),
+            (Endpoint::Receiver, ChannelKind::Broadcast) => format!("{}.resubscribe()", self.receiver_name()),
+            (Endpoint::Receiver, ChannelKind::Watch) => format!("{}.clone()", self.receiver_name()),
+        }
+    }
+
+    pub fn send_statement(&self, value: &str) -> String {
+        match self.kind {
+            ChannelKind::Mpsc => format!("let _ = {}.send({}).await;", self.sender_name(), value),
+            _ => format!("let _ = {}.send({});", self.sender_name(), value),
+        }
+    }
+
+    /// Receive loop wrapping an already indented body
+    pub fn receive_loop(&self, variable: &str, body: &str) -> String {
+        let rx = self.receiver_name();
+        match self.kind {
+            ChannelKind::Mpsc => format!("while let Some({}) = {}.recv().await {{\n{}}}", variable, rx, body),
+            ChannelKind::Broadcast => format!("while let Ok({}) = {}.recv().await {{\n{}}}", variable, rx, body),
+            ChannelKind::Watch => format!(
+                "while {rx}.changed().await.is_ok() {{\n    let {var} = {rx}.borrow_and_update().clone();\n{body}}}",
+                rx = rx,
+                var = variable,
+                body = body
+            ),
+        }
+    }
+}
+
+/// Channels of a flow and the channel ends each function needs as parameters
+#[derive(Debug, Default)]
+pub struct ChannelRegistry {
+    channels: HashMap<String, ChannelDef>,
+    endpoints: HashMap<String, BTreeSet<(String, Endpoint)>>,
+    /// Receive nodes placed in the function that owns their channel
+    owner_receives: HashSet<String>,
+}
+
+impl ChannelRegistry {
+    pub fn from_flow(flow: &Flow) -> Result<Self> {
+        let node_map: HashMap<&str, &Node> = flow.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+        let mut registry = ChannelRegistry::default();
+
+        for node in flow.nodes.iter().filter(|n| n.plugin_type == "channel") {
+            let name = node.properties.get("channel_name")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| anyhow!("Channel '{}' missing channel_name", node.id))?;
+            let kind = ChannelKind::parse(
+                node.properties.get("channel_kind").and_then(|v| v.as_str()).unwrap_or("mpsc"),
+            )?;
+            let message_type = node.properties.get("message_type")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .unwrap_or("String");
+            let capacity = node.properties.get("capacity")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(32);
+            let initial_value = node.properties.get("initial_value")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .unwrap_or("Default::default()");
+
+            if registry.channels.contains_key(name) {
+                return Err(anyhow!("Channel '{}' declared more than once", name));
+            }
+            registry.channels.insert(name.to_string(), ChannelDef {
+                name: name.to_string(),
+                kind,
+                message_type: message_type.to_string(),
+                capacity,
+                initial_value: initial_value.to_string(),
+                owner: owning_function(node, &node_map),
+                owner_sends: false,
+            });
+        }
+
+        // Direct uses of channel ends by send/receive nodes
+        for node in &flow.nodes {
+            let endpoint = match node.plugin_type.as_str() {
+                "channel-send" => Endpoint::Sender,
+                "channel-receive" => Endpoint::Receiver,
+                _ => continue,
+            };
+            let channel_name = registry.channel_for(node)?.name.clone();
+            let function = owning_function(node, &node_map);
+            let channel = registry.channels.get_mut(&channel_name).unwrap();
+            if channel.owner != function {
+                registry.endpoints.entry(function).or_default().insert((channel_name, endpoint));
+            } else if endpoint == Endpoint::Sender {
+                channel.owner_sends = true;
+            } else {
+                registry.owner_receives.insert(node.id.clone());
+            }
+        }
+
+        // Callers must forward the ends their callees need, unless they own the channel
+        let calls: Vec<(String, String)> = flow.nodes.iter()
+            .filter(|n| n.plugin_type == "call-function")
+            .filter_map(|n| {
+                let target = n.properties.get("target_function").and_then(|v| v.as_str())?;
+                Some((owning_function(n, &node_map), target.to_string()))
+            })
+            .collect();
+
+        loop {
+            let mut changed = false;
+            for (caller, callee) in &calls {
+                let needed: Vec<(String, Endpoint)> = registry.endpoints.get(callee)
+                    .map(|set| set.iter().cloned().collect())
+                    .unwrap_or_default();
+                for (channel, endpoint) in needed {
+                    if registry.channels[&channel].owner == *caller {
+                        continue;
+                    }
+                    changed |= registry.endpoints.entry(caller.clone()).or_default().insert((channel, endpoint));
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ChannelDef> {
+        self.channels.get(name)
+    }
+
+    /// Channel referenced by the `channel` property of a send/receive node
+    pub fn channel_for(&self, node: &Node) -> Result<&ChannelDef> {
+        let name = node.properties.get("channel")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("Node '{}' missing channel", node.id))?;
+        self.channels.get(name)
+            .ok_or_else(|| anyhow!("Node '{}' references unknown channel '{}'", node.id, name))
+    }
+
+    /// Whether the owner's sender must be dropped before this receive loop.
+    /// Otherwise the loop would never end, since the owner keeps the channel open.
+    pub fn drops_sender_before(&self, receive_node: &Node) -> bool {
+        self.owner_receives.contains(&receive_node.id)
+            && self.channel_for(receive_node).map(|c| !c.owner_sends).unwrap_or(false)
+    }
+
+    /// Extra parameters appended to a function signature
+    pub fn parameters(&self, function: &str) -> Vec<String> {
+        self.endpoints_of(function)
+            .map(|(def, endpoint)| def.parameter(endpoint))
+            .collect()
+    }
+
+    /// Extra arguments appended when calling a function
+    pub fn call_arguments(&self, function: &str) -> Vec<String> {
+        self.endpoints_of(function)
+            .map(|(def, endpoint)| def.call_argument(endpoint))
+            .collect()
+    }
+
+    /// Channels whose ends a function takes as parameters, each once
+    pub fn channels_of(&self, function: &str) -> Vec<&ChannelDef> {
+        let mut channels: Vec<&ChannelDef> = Vec::new();
+        for (channel, _) in self.endpoints_of(function) {
+            if !channels.iter().any(|c| c.name == channel.name) {
+                channels.push(channel);
+            }
+        }
+        channels
+    }
+
+    fn endpoints_of<'a>(&'a self, function: &str) -> impl Iterator<Item = (&'a ChannelDef, Endpoint)> + 'a {
+        self.endpoints.get(function)
+            .into_iter()
+            .flatten()
+            .map(|(channel, endpoint)| (&self.channels[channel], *endpoint))
+    }
+}
+
+/// Name of the function-definition enclosing a node ("main" for root nodes)
+fn owning_function(node: &Node, node_map: &HashMap<&str, &Node>) -> String {
+    let mut current = node.parent_id.as_deref();
+    while let Some(parent_id) = current {
+        match node_map.get(parent_id) {
+            Some(parent) if parent.plugin_type == "function-definition" => {
+                return parent.properties.get("function_name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown")
+                    .to_string();
+            }
+            Some(parent) => current = parent.parent_id.as_deref(),
+            None => break,
+        }
+    }
+    "main".to_string()
+}
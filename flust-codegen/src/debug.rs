@@ -0,0 +1,226 @@
+use crate::trace;
+use serde::{Deserialize, Serialize};
+
+/// Environment variable naming the local `host:port` of the debugger a program connects to
+pub const ADDRESS_VAR: &str = "FLUST_DEBUG_ADDR";
+
+/// Runtime support of debugged programs: each node asks the debugger whether to pause before it runs
+/// Pauses send the in-scope variables as a JSON line and block until the next command
+/// Without a debugger the program runs on; once the debugger goes away it stops
+const RUNTIME: &str = r#"#[allow(dead_code)]
+mod flust_debug {
+    use std::collections::{HashMap, HashSet};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+    use std::sync::{Mutex, OnceLock};
+
+    struct Session {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+        stepping: bool,
+        breakpoints: HashSet<String>,
+    }
+
+    impl Session {
+        fn connect() -> Option<Session> {
+            let writer = TcpStream::connect(std::env::var("FLUST_DEBUG_ADDR").ok()?).ok()?;
+            let reader = BufReader::new(writer.try_clone().ok()?);
+            let mut session = Session { reader, writer, stepping: false, breakpoints: HashSet::new() };
+            // The debugger answers the connection with the first command
+            session.command().then_some(session)
+        }
+
+        /// Reads `step` or `continue`, followed by the tab-separated breakpoints
+        fn command(&mut self) -> bool {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return false;
+            }
+            let mut parts = line.trim_end().split('\t');
+            self.stepping = parts.next() == Some("step");
+            self.breakpoints = parts.filter(|p| !p.is_empty()).map(String::from).collect();
+            true
+        }
+    }
+
+    fn session() -> &'static Mutex<Option<Session>> {
+        static SESSION: OnceLock<Mutex<Option<Session>>> = OnceLock::new();
+        SESSION.get_or_init(|| Mutex::new(Session::connect()))
+    }
+
+    /// Last value written to each variable, by function and name
+    fn known() -> &'static Mutex<HashMap<(&'static str, &'static str), String>> {
+        static KNOWN: OnceLock<Mutex<HashMap<(&'static str, &'static str), String>>> = OnceLock::new();
+        KNOWN.get_or_init(Default::default)
+    }
+
+    fn quote(text: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in text.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    /// Variable shown while paused: its `Debug` text when it has one, otherwise its type name
+    pub struct Value<'v, T>(pub &'v T);
+
+    pub trait Debugged {
+        fn show(&self) -> String;
+    }
+
+    impl<T: std::fmt::Debug> Debugged for Value<'_, T> {
+        fn show(&self) -> String {
+            format!("{:?}", self.0)
+        }
+    }
+
+    pub trait Opaque {
+        fn show(&self) -> String;
+    }
+
+    // Only picked when `Debugged` does not apply, as it needs one more autoref
+    impl<T> Opaque for &Value<'_, T> {
+        fn show(&self) -> String {
+            format!("<{}>", std::any::type_name::<T>())
+        }
+    }
+
+    /// Remembers the values a node or function call wrote
+    pub fn record(function: &'static str, values: &[(&'static str, String)]) {
+        let mut known = known().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (name, value) in values {
+            known.insert((function, name), value.clone());
+        }
+    }
+
+    /// Waits for the debugger when the node is a breakpoint or the last command was `step`
+    pub fn pause(node: &'static str, function: &'static str, visible: &[&'static str]) {
+        let mut session = session().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(active) = session.as_mut() else { return };
+        if !active.stepping && !active.breakpoints.contains(node) {
+            return;
+        }
+        let values: Vec<String> = {
+            let known = known().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            visible.iter()
+                .map(|name| {
+                    let value = known.get(&(function, *name)).map(|v| quote(v)).unwrap_or_else(|| "null".to_string());
+                    format!("{{\"name\":{},\"value\":{}}}", quote(name), value)
+                })
+                .collect()
+        };
+        let line = format!(
+            "{{\"flust_debug\":\"paused\",\"node\":{},\"function\":{},\"variables\":[{}]}}\n",
+            quote(node),
+            quote(function),
+            values.join(","),
+        );
+        if active.writer.write_all(line.as_bytes()).is_err() || !active.command() {
+            eprintln!("Stopped by the debugger at node '{}'", node);
+            std::process::exit(1);
+        }
+    }
+}
+
+"#;
+
+pub fn runtime() -> &'static str {
+    RUNTIME
+}
+
+/// Statement pausing before a node when the debugger asks for it
+pub fn pause(node_id: &str, function: &str, visible: &[String]) -> String {
+    let names: Vec<String> = visible.iter().map(|v| format!("{:?}", v)).collect();
+    format!("crate::flust_debug::pause({:?}, {:?}, &[{}]);", node_id, function, names.join(", "))
+}
+
+/// Statement remembering the values of `variables`, shown at later pauses of the same function
+pub fn record(function: &str, variables: &[String]) -> String {
+    if variables.is_empty() {
+        return String::new();
+    }
+    format!(
+        "{{ #[allow(unused_imports)] use crate::flust_debug::{{Debugged as _, Opaque as _}}; crate::flust_debug::record({:?}, &[{}]); }}",
+        function,
+        trace::shown_values("flust_debug", variables)
+    )
+}
+
+/// What a paused program does next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Pause again before the next node
+    Step,
+    /// Run until the next breakpoint
+    Continue,
+}
+
+/// Line answering a pause (or the program's connection)
+pub fn command(action: Action, breakpoints: &[String]) -> String {
+    let mut line = match action {
+        Action::Step => "step".to_string(),
+        Action::Continue => "continue".to_string(),
+    };
+    for breakpoint in breakpoints {
+        line.push('\t');
+        line.push_str(breakpoint);
+    }
+    line.push('\n');
+    line
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DebugVariable {
+    pub name: String,
+    /// `None` until a node of the function has written it
+    pub value: Option<String>,
+}
+
+/// Program paused before a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pause {
+    pub node: String,
+    pub function: String,
+    pub variables: Vec<DebugVariable>,
+}
+
+#[derive(Deserialize)]
+struct PauseLine {
+    #[serde(rename = "flust_debug")]
+    _event: String,
+    #[serde(flatten)]
+    pause: Pause,
+}
+
+pub fn parse_pause(line: &str) -> Option<Pause> {
+    serde_json::from_str::<PauseLine>(line).ok().map(|line| line.pause)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_protocol() {
+        let paused = parse_pause("{\"flust_debug\":\"paused\",\"node\":\"show\",\"function\":\"main\",\
+            \"variables\":[{\"name\":\"count\",\"value\":\"1\"},{\"name\":\"later\",\"value\":null}]}").unwrap();
+        assert_eq!(paused.node, "show");
+        assert_eq!(paused.variables[0], DebugVariable { name: "count".to_string(), value: Some("1".to_string()) });
+        assert_eq!(paused.variables[1].value, None);
+        assert_eq!(parse_pause("warning: not a pause"), None);
+
+        assert_eq!(command(Action::Continue, &["a".to_string(), "b".to_string()]), "continue\ta\tb\n");
+        assert_eq!(command(Action::Step, &[]), "step\n");
+        assert_eq!(pause("show", "main", &["count".to_string()]), "crate::flust_debug::pause(\"show\", \"main\", &[\"count\"]);");
+        assert_eq!(record("main", &[]), "");
+    }
+}
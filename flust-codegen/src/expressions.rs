@@ -0,0 +1,283 @@
+use crate::assertions;
+use flust_core::ir::Node;
+use flust_core::stdlib;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+
+pub use flust_core::stdlib::is_expression_node;
+
+/// Rust template of a built-in node
+/// `{name}` placeholders are replaced by the operand of the input port `name`,
+/// or by the node property `name` for settings that are not ports
+fn template(plugin_type: &str) -> Option<&'static str> {
+    let template = match plugin_type {
+        "add" => "{a} + {b}",
+        "subtract" => "{a} - {b}",
+        "multiply" => "{a} * {b}",
+        "divide" => "{a} / {b}",
+        "modulo" => "{a} % {b}",
+        "equal" => "{a} == {b}",
+        "not-equal" => "{a} != {b}",
+        "less-than" => "{a} < {b}",
+        "less-equal" => "{a} <= {b}",
+        "greater-than" => "{a} > {b}",
+        "greater-equal" => "{a} >= {b}",
+        "and" => "{a} && {b}",
+        "or" => "{a} || {b}",
+        "not" => "!{value}",
+        "min" => "{a}.min({b})",
+        "max" => "{a}.max({b})",
+        "clamp" => "{value}.clamp({min}, {max})",
+        "cast" => "{value} as {target_type}",
+        "split" => "{text}.split({separator}).map(String::from)",
+        "join" => "{list}.join({separator})",
+        "trim" => "{text}.trim().to_string()",
+        "parse" => "{text}.trim().parse::<{target_type}>()",
+        "len" => "{collection}.len()",
+        "vec-new" => "Vec::<{element_type}>::new()",
+        "map-new" => "std::collections::HashMap::<{key_type}, {value_type}>::new()",
+        "vec-get" => "{list}.get({index}).cloned()",
+        "map-get" => "{map}.get(&{key}).cloned()",
+        "push" => "{list}.push({value})",
+        "map-insert" => "{map}.insert({key}, {value})",
+        "field-access" => "{value}.{field}",
+        _ => return None,
+    };
+    Some(template)
+}
+
+pub fn is_statement_node(node: &Node) -> bool {
+    stdlib::signature(&node.plugin_type).is_some_and(|s| s.output.is_none())
+}
+
+/// Input port of a statement node whose value is mutated in place, e.g. `list` for `{list}.push({value})`
+pub fn receiver_port(node: &Node) -> Option<&'static str> {
+    if !is_statement_node(node) {
+        return None;
+    }
+    let (name, call) = template(&node.plugin_type)?.strip_prefix('{')?.split_once('}')?;
+    call.starts_with('.').then_some(name)
+}
+
+/// Iterator adapters are fused into one chain when they feed each other
+pub fn is_iterator_adapter(node: &Node) -> bool {
+    matches!(node.plugin_type.as_str(), "vec-map" | "vec-filter")
+}
+
+/// Render a built-in node given the Rust expression feeding each input port
+pub fn render(node: &Node, operands: &HashMap<&str, String>) -> Result<String> {
+    if node.plugin_type == "format-string" {
+        return Ok(format_string(node, operands.get("value")));
+    }
+    if assertions::is_assertion(node) {
+        return Ok(assertions::render(node, operands));
+    }
+
+    let template = template(&node.plugin_type)
+        .ok_or_else(|| anyhow!("'{}' is not an expression node", node.plugin_type))?;
+    let mut expression = fill(template, |name| match operands.get(name) {
+        Some(operand) => Ok(parenthesize(operand)),
+        None => node.properties.get(name)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .ok_or_else(|| anyhow!("'{}' missing {}", node.id, name)),
+    })?;
+
+    if node.plugin_type == "split" {
+        expression.push_str(".collect::<Vec<String>>()");
+    }
+    if node.plugin_type == "parse" {
+        match node.properties.get("on_error").and_then(|v| v.as_str()).unwrap_or("result") {
+            "result" => {}
+            "propagate" => expression.push('?'),
+            "default" => expression.push_str(".unwrap_or_default()"),
+            other => return Err(anyhow!("Parse '{}' has unknown on_error '{}'", node.id, other)),
+        }
+    }
+
+    Ok(expression)
+}
+
+/// Split without collecting, so adapters downstream can continue the iterator
+pub fn split_iterator(node: &Node, operands: &HashMap<&str, String>) -> Result<String> {
+    let split = render(node, operands)?;
+    Ok(split.trim_end_matches(".collect::<Vec<String>>()").to_string())
+}
+
+/// `.map(..)` / `.filter(..)` step appended to an iterator chain
+pub fn iterator_step(node: &Node) -> Result<String> {
+    let item = node.properties.get("item_variable")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("item");
+    let (adapter, property) = match node.plugin_type.as_str() {
+        "vec-map" => ("map", "body"),
+        "vec-filter" => ("filter", "predicate"),
+        other => return Err(anyhow!("'{}' is not an iterator adapter", other)),
+    };
+    let closure_body = node.properties.get(property)
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| anyhow!("'{}' missing {}", node.id, property))?;
+    Ok(format!(".{}(|{}| {})", adapter, item, closure_body))
+}
+
+/// Sorted copy of a vector, optionally by key
+pub fn sorted(node: &Node, source: &str) -> String {
+    let sort = match node.properties.get("sort_key").and_then(|v| v.as_str()).filter(|s| !s.is_empty()) {
+        Some(key) => format!("sorted.sort_by_key(|item| {});", key),
+        None => "sorted.sort();".to_string(),
+    };
+    format!("{{ let mut sorted: Vec<_> = {}; {} sorted }}", source, sort)
+}
+
+fn format_string(node: &Node, value: Option<&String>) -> String {
+    let template = node.properties.get("template")
+        .and_then(|v| v.as_str())
+        .unwrap_or("{}")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    match value {
+        Some(value) => format!("format!(\"{}\", {})", template, value),
+        None => format!("format!(\"{}\")", template),
+    }
+}
+
+/// Replace `{name}` placeholders; braces not wrapping an identifier are kept as-is
+fn fill(template: &str, mut lookup: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        output.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let name_len = after.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(after.len());
+        if name_len > 0 && after[name_len..].starts_with('}') {
+            output.push_str(&lookup(&after[..name_len])?);
+            rest = &after[name_len + 1..];
+        } else {
+            output.push('{');
+            rest = after;
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Wrap compound operands so nesting keeps the flow's evaluation order
+pub fn parenthesize(operand: &str) -> String {
+    let operand = operand.trim();
+    if has_top_level_operator(operand) && !is_wrapped(operand) {
+        format!("({})", operand)
+    } else {
+        operand.to_string()
+    }
+}
+
+/// Whether an operator or a space appears outside brackets, literals and turbofish generics,
+/// i.e. the operand is not a plain identifier, literal, path or call/field chain
+fn has_top_level_operator(operand: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = operand.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '"' | '\'' => {
+                while let Some(inner) = chars.next() {
+                    match inner {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if inner == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            ':' if chars.peek() == Some(&':') => {
+                chars.next();
+                if chars.peek() == Some(&'<') {
+                    let mut generics = 0;
+                    for inner in chars.by_ref() {
+                        match inner {
+                            '<' => generics += 1,
+                            '>' => generics -= 1,
+                            _ => {}
+                        }
+                        if generics == 0 {
+                            break;
+                        }
+                    }
+                }
+            }
+            _ if depth == 0 && (c.is_whitespace() || "+-*/%<>=!&|^".contains(c)) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Whether the whole operand is enclosed by one pair of parentheses
+fn is_wrapped(operand: &str) -> bool {
+    if !operand.starts_with('(') || !operand.ends_with(')') {
+        return false;
+    }
+    let mut depth = 0;
+    for (index, c) in operand.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 && index < operand.len() - 1 {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_operands_are_parenthesized() {
+        let node = Node {
+            id: "mul".to_string(),
+            plugin_type: "multiply".to_string(),
+            label: None,
+            properties: HashMap::new(),
+            parent_id: None,
+        };
+        let mut operands = HashMap::new();
+        operands.insert("a", "x + 1".to_string());
+        operands.insert("b", "factor".to_string());
+
+        assert_eq!(render(&node, &operands).unwrap(), "(x + 1) * factor");
+
+        operands.insert("a", "x+1".to_string());
+        operands.insert("b", "a*b-c".to_string());
+        assert_eq!(render(&node, &operands).unwrap(), "(x+1) * (a*b-c)");
+
+        operands.insert("a", "Vec::<i32>::new().len()".to_string());
+        operands.insert("b", "scale(\"a-b\", 2).value".to_string());
+        assert_eq!(render(&node, &operands).unwrap(), "Vec::<i32>::new().len() * scale(\"a-b\", 2).value");
+    }
+
+    #[test]
+    fn test_format_string_escapes_template() {
+        let mut properties = HashMap::new();
+        properties.insert("template".to_string(), serde_json::json!("Temp \"{}\""));
+        let node = Node {
+            id: "fmt".to_string(),
+            plugin_type: "format-string".to_string(),
+            label: None,
+            properties,
+            parent_id: None,
+        };
+        let mut operands = HashMap::new();
+        operands.insert("value", "reading".to_string());
+
+        assert_eq!(render(&node, &operands).unwrap(), r#"format!("Temp \"{}\"", reading)"#);
+    }
+}
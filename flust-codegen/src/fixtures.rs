@@ -0,0 +1,26 @@
+//! Node and connection factories shared by the unit tests.
+
+use flust_core::ir::{Connection, Node};
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub(crate) fn node(id: &str, plugin_type: &str, parent: Option<&str>, properties: Value) -> Node {
+    Node {
+        id: id.to_string(),
+        plugin_type: plugin_type.to_string(),
+        label: None,
+        properties: serde_json::from_value(properties).unwrap(),
+        parent_id: parent.map(String::from),
+    }
+}
+
+pub(crate) fn connection(from: &str, to: &str, mapping: Option<HashMap<String, String>>) -> Connection {
+    Connection {
+        from: from.to_string(),
+        to: to.to_string(),
+        connection_type: Default::default(),
+        variable_mapping: mapping,
+        from_port: None,
+        to_port: None,
+    }
+}
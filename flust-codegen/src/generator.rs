@@ -1,88 +1,713 @@
-use flust_core::ir::{Flow, Node, Connection};
+use flust_core::ir::{Flow, Node, Connection, ConnectionType};
+use flust_core::modules::ModuleGraph;
 use flust_core::topological_sort::TopologicalSort;
 use crate::template_engine::TemplateEngine;
+use crate::channels::ChannelRegistry;
+use crate::expressions::{self, is_expression_node, is_statement_node};
+use crate::items::ModuleItems;
+use crate::test_cases;
+use crate::{debug, profile, trace};
+use crate::types;
+use flust_core::scope::{self, ScopeAnalysis};
+use flust_core::stdlib;
+use flust_core::types::TypeRegistry;
 use anyhow::{Result, anyhow};
+use flust_core::subflow::{self, SubflowSignature};
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
 const LEGACY_CODE_TEMPLATE: &str = "{{code}}";
+/// Error type of fallible functions that do not declare their own
+const DEFAULT_ERROR_TYPE: &str = "Box<dyn std::error::Error + Send + Sync>";
 const DEBUG_TEMPLATE: &str = r#"{{#if label}}println!("{{label}}: {:?}", {{variable}});
 {{else}}println!("{:?}", {{variable}});
 {{/if}}"#;
 
+/// Shared lookup tables for generating the scopes of a flow
+struct CodegenContext<'a> {
+    flow: &'a Flow,
+    connections: &'a [Connection],
+    node_map: HashMap<String, &'a Node>,
+    nodes_by_parent: HashMap<Option<String>, Vec<&'a Node>>,
+    channels: ChannelRegistry,
+    types: TypeRegistry,
+    /// Module packaged by each subflow `source` path
+    subflow_modules: HashMap<String, String>,
+    /// Other modules of a multi-file program, needed to inline their functions
+    project: Option<&'a Project<'a>>,
+    /// Subflows being inlined, to reject a subflow that inlines itself
+    inlining: RefCell<Vec<String>>,
+    instrumentation: Instrumentation,
+    /// Variables in scope at each node, shown while debugging
+    scope: ScopeAnalysis,
+}
+
+impl<'a> CodegenContext<'a> {
+    fn new(flow: &'a Flow) -> Result<Self> {
+        let mut nodes_by_parent: HashMap<Option<String>, Vec<&Node>> = HashMap::new();
+        let mut node_map: HashMap<String, &Node> = HashMap::new();
+
+        for node in &flow.nodes {
+            node_map.insert(node.id.clone(), node);
+            nodes_by_parent.entry(node.parent_id.clone()).or_default().push(node);
+        }
+
+        let types = TypeRegistry::from_flow(flow);
+        if let Some(diagnostic) = types.diagnostics.first() {
+            return Err(anyhow!("{}", diagnostic));
+        }
+
+        Ok(Self {
+            flow,
+            connections: &flow.connections,
+            node_map,
+            nodes_by_parent,
+            channels: ChannelRegistry::from_flow(flow)?,
+            types,
+            subflow_modules: HashMap::new(),
+            project: None,
+            inlining: RefCell::new(Vec::new()),
+            instrumentation: Instrumentation::None,
+            scope: ScopeAnalysis::default(),
+        })
+    }
+
+    fn instrument_with(&mut self, instrumentation: Instrumentation) -> Result<()> {
+        self.instrumentation = instrumentation;
+        if instrumentation == Instrumentation::Debug {
+            self.scope = ScopeAnalysis::analyze(self.flow)?;
+        }
+        Ok(())
+    }
+
+    fn children(&self, parent_id: &str) -> &[&'a Node] {
+        self.nodes_by_parent.get(&Some(parent_id.to_string()))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Function-definition whose body (directly or through nested containers) holds the node
+    fn enclosing_function(&self, node: &Node) -> Option<&'a Node> {
+        let mut current = node.parent_id.as_ref();
+        while let Some(parent_id) = current {
+            let parent = self.node_map.get(parent_id)?;
+            if parent.plugin_type == "function-definition" {
+                return Some(parent);
+            }
+            current = parent.parent_id.as_ref();
+        }
+        None
+    }
+
+    /// Nodes reached from a call through `error` connections, plus everything downstream of them
+    fn error_branch(&self, call: &Node) -> Vec<&'a Node> {
+        let mut branch: Vec<&Node> = Vec::new();
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut pending: Vec<&str> = self.connections.iter()
+            .filter(|c| c.from == call.id && c.connection_type == ConnectionType::Error)
+            .map(|c| c.to.as_str())
+            .collect();
+
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let Some(node) = self.node_map.get(id) else { continue };
+            if node.parent_id != call.parent_id {
+                continue;
+            }
+            branch.push(node);
+            pending.extend(self.connections.iter().filter(|c| c.from == id).map(|c| c.to.as_str()));
+        }
+
+        branch
+    }
+
+    /// Expression nodes used by exactly one port and not bound to a variable are inlined there
+    /// A value mutated in place is always bound, so the change is not lost on a temporary
+    fn is_inlined(&self, node: &Node) -> bool {
+        if !is_expression_node(node) || str_property(node, "output_variable").is_some() || self.is_mutated(node) {
+            return false;
+        }
+        let mut outgoing = self.connections.iter().filter(|c| c.from == node.id);
+        matches!((outgoing.next(), outgoing.next()), (Some(conn), None) if conn.to_port.is_some())
+    }
+
+    /// Whether the node feeds the receiver port of a statement like `push` or `map-insert`
+    fn is_mutated(&self, node: &Node) -> bool {
+        self.connections.iter()
+            .filter(|c| c.from == node.id)
+            .any(|c| {
+                let receiver = self.node_map.get(&c.to).and_then(|target| expressions::receiver_port(target));
+                receiver.is_some() && c.to_port.as_deref() == receiver
+            })
+    }
+
+    /// Rust expression wired into an input port through a data connection, if any
+    fn port_input(&self, node: &Node, port: &str) -> Result<Option<String>> {
+        let Some(conn) = self.connections.iter().find(|c| c.to == node.id && c.to_port.as_deref() == Some(port)) else {
+            return Ok(None);
+        };
+        let source = self.node_map.get(&conn.from)
+            .ok_or_else(|| anyhow!("Unknown connection source: {}", conn.from))?;
+        self.value_of(source).map(Some)
+    }
+
+    /// Rust expression for the value a node produces
+    fn value_of(&self, node: &Node) -> Result<String> {
+        if is_expression_node(node) {
+            return if self.is_inlined(node) {
+                self.expression(node)
+            } else {
+                Ok(output_variable(node))
+            };
+        }
+        if node.plugin_type == "subflow" {
+            return Ok(output_variable(node));
+        }
+        let variable_property = match node.plugin_type.as_str() {
+            "call-function" => "return_variable",
+            "let-variable" => "variable_name",
+            "channel-receive" => "message_variable",
+            _ => "",
+        };
+        str_property(node, variable_property)
+            .map(String::from)
+            .ok_or_else(|| anyhow!("Node '{}' does not produce a value", node.id))
+    }
+
+    /// Input port operand, falling back to the property of the same name
+    fn operand(&self, node: &Node, port: &str) -> Result<Option<String>> {
+        match self.port_input(node, port)? {
+            Some(operand) => Ok(Some(operand)),
+            None => Ok(property_text(node, port)),
+        }
+    }
+
+    /// Expression of a built-in node with every input port resolved
+    fn expression(&self, node: &Node) -> Result<String> {
+        if expressions::is_iterator_adapter(node) {
+            return Ok(format!("{}.collect::<Vec<_>>()", self.iterator_chain(node)?));
+        }
+        if node.plugin_type == "construct" {
+            let mut wired = Vec::new();
+            for conn in self.connections.iter().filter(|c| c.to == node.id) {
+                if let Some(field) = &conn.to_port
+                    && let Some(value) = self.port_input(node, field)?
+                {
+                    wired.push((field.clone(), value));
+                }
+            }
+            return Ok(types::construct(node, &self.types, wired));
+        }
+        if node.plugin_type == "vec-sort" {
+            let source = match self.inlined_adapter(node)? {
+                Some(adapter) => format!("{}.collect()", self.iterator_chain(adapter)?),
+                None => format!("{}.clone()", self.required_operand(node, "list")?),
+            };
+            return Ok(expressions::sorted(node, &source));
+        }
+
+        let signature = stdlib::signature(&node.plugin_type)
+            .ok_or_else(|| anyhow!("'{}' is not an expression node", node.plugin_type))?;
+        let mut operands = HashMap::new();
+        for input in signature.inputs {
+            match self.operand(node, input.name)? {
+                Some(operand) => {
+                    operands.insert(input.name, operand);
+                }
+                None if input.optional => {}
+                None => return Err(anyhow!("Input '{}' of '{}' is not connected", input.name, node.id)),
+            }
+        }
+        expressions::render(node, &operands)
+    }
+
+    fn required_operand(&self, node: &Node, port: &str) -> Result<String> {
+        self.operand(node, port)?
+            .ok_or_else(|| anyhow!("Input '{}' of '{}' is not connected", port, node.id))
+    }
+
+    /// Call to the function a subflow node packages, or a copy of its body when inlined
+    fn subflow(&self, node: &Node) -> Result<String> {
+        let source = subflow::source_property(node);
+        let module = match source {
+            Some(source) => Some(self.subflow_modules.get(source)
+                .ok_or_else(|| anyhow!("Subflow '{}' source '{}' was not loaded", node.id, source))?),
+            None => None,
+        };
+        let flow = match (module, self.project) {
+            (Some(module), Some(project)) => &project.flows[module],
+            (Some(_), None) => return Err(anyhow!("Subflow '{}' needs its source flow loaded", node.id)),
+            (None, _) => self.flow,
+        };
+        let func_def = subflow::packaged_function(flow, subflow::function_property(node))?;
+        let signature = SubflowSignature::of(func_def);
+
+        let mut arguments = Vec::new();
+        for input in &signature.inputs {
+            arguments.push((input, self.required_operand(node, &input.name)?));
+        }
+        if signature.fallible && !self.enclosing_function(node).map(is_fallible).unwrap_or(false) {
+            return Err(anyhow!("Subflow '{}' is fallible but its function is not", node.id));
+        }
+
+        let value = match str_property(node, "mode").unwrap_or("call") {
+            "call" => {
+                let propagate = if signature.fallible { "?" } else { "" };
+                let mut call_args: Vec<String> = arguments.into_iter().map(|(_, value)| value).collect();
+                if source.is_none() {
+                    call_args.extend(self.channels.call_arguments(&signature.function));
+                }
+                format!("{}({}).await{}", signature.function, call_args.join(", "), propagate)
+            }
+            "inline" => {
+                let key = format!("{}::{}", source.unwrap_or(""), signature.function);
+                if self.inlining.borrow().contains(&key) {
+                    return Err(anyhow!("Subflow '{}' inlines '{}' into itself", node.id, signature.function));
+                }
+                self.inlining.borrow_mut().push(key);
+                // The packaged module's items and imports are brought into the block
+                let mut uses = String::new();
+                let body = match (module, self.project) {
+                    (Some(module), Some(project)) => {
+                        let packaged = project.context(module)?;
+                        packaged.inlining.borrow_mut().clone_from(&self.inlining.borrow());
+                        uses.push_str(&format!("#[allow(unused_imports)]\nuse crate::{}::*;\n", module));
+                        for declaration in ModuleItems::from_flow(flow).render_uses().lines().filter(|l| !l.is_empty()) {
+                            uses.push_str(&format!("#[allow(unused_imports)]\n{}\n", declaration));
+                        }
+                        generate_scope_code(packaged.children(&func_def.id), &packaged)
+                    }
+                    _ => generate_scope_code(self.children(&func_def.id), self),
+                };
+                self.inlining.borrow_mut().pop();
+
+                // An async block keeps a `return` in the body from leaving the caller
+                let mut inner = String::new();
+                for (input, value) in arguments {
+                    inner.push_str(&format!("    let {}: {} = {};\n", input.name, input.type_, value));
+                }
+                inner.push_str(&body?);
+                if signature.fallible && signature.output.is_none() {
+                    inner.push_str("    Ok(())\n");
+                }
+                let inner = format!("async {{\n{}}}.await", inner);
+                let indent = |code: &str| code.lines().map(|line| format!("    {}\n", line)).collect::<String>();
+                if signature.fallible {
+                    // The block's error type cannot be inferred through `?`, so it is spelled out
+                    let result = format!(
+                        "let __flust_result: Result<{}, {}> = {};\n__flust_result?",
+                        signature.output.as_deref().unwrap_or("()"), error_type(func_def), inner
+                    );
+                    format!("{{\n{}{}}}", indent(&uses), indent(&result))
+                } else if uses.is_empty() {
+                    inner
+                } else {
+                    format!("{{\n{}{}}}", indent(&uses), indent(&inner))
+                }
+            }
+            other => return Err(anyhow!("Subflow '{}' has unknown mode '{}'", node.id, other)),
+        };
+
+        Ok(match signature.output {
+            Some(_) => format!("let {} = {};", output_variable(node), value),
+            None => format!("{};", value),
+        })
+    }
+
+    /// Node feeding the `list` port when it is inlined there
+    fn inlined_list_source(&self, node: &Node) -> Result<Option<&'a Node>> {
+        let Some(conn) = self.connections.iter().find(|c| c.to == node.id && c.to_port.as_deref() == Some("list")) else {
+            return Ok(None);
+        };
+        let source = *self.node_map.get(&conn.from)
+            .ok_or_else(|| anyhow!("Unknown connection source: {}", conn.from))?;
+        Ok(self.is_inlined(source).then_some(source))
+    }
+
+    /// Iterator adapter feeding the `list` port, fused into the same chain
+    fn inlined_adapter(&self, node: &Node) -> Result<Option<&'a Node>> {
+        Ok(self.inlined_list_source(node)?.filter(|source| expressions::is_iterator_adapter(source)))
+    }
+
+    /// Uncollected iterator chain ending in an adapter node
+    fn iterator_chain(&self, node: &Node) -> Result<String> {
+        let source = match self.inlined_list_source(node)? {
+            Some(source) if expressions::is_iterator_adapter(source) => self.iterator_chain(source)?,
+            Some(source) if source.plugin_type == "split" => {
+                let mut operands = HashMap::new();
+                for port in ["text", "separator"] {
+                    operands.insert(port, self.required_operand(source, port)?);
+                }
+                expressions::split_iterator(source, &operands)?
+            }
+            _ => format!("{}.iter().cloned()", expressions::parenthesize(&self.required_operand(node, "list")?)),
+        };
+        Ok(format!("{}{}", source, expressions::iterator_step(node)?))
+    }
+}
+
+fn str_property<'n>(node: &'n Node, name: &str) -> Option<&'n str> {
+    node.properties.get(name)
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+}
+
+/// Property rendered as source text (strings verbatim, other JSON values as literals)
+fn property_text(node: &Node, name: &str) -> Option<String> {
+    match node.properties.get(name)? {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) if s.is_empty() => None,
+        serde_json::Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Variable bound to an expression node that is not inlined
+fn output_variable(node: &Node) -> String {
+    match str_property(node, "output_variable") {
+        Some(name) => name.to_string(),
+        None => {
+            let id: String = node.id.chars()
+                .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+                .collect();
+            format!("{}_value", id)
+        }
+    }
+}
+
+/// Whether a function-definition returns `Result` so `?` can be used in its body
+fn is_fallible(func_def: &Node) -> bool {
+    func_def.properties.get("fallible")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn error_type(func_def: &Node) -> &str {
+    func_def.properties.get("error_type")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or(DEFAULT_ERROR_TYPE)
+}
+
 /// Code generator - converts Flow IR to Rust code
 pub fn generate_rust(flow: &Flow) -> Result<String> {
+    generate_instrumented(flow, Instrumentation::None)
+}
+
+/// Binary program whose nodes report to the given instrumentation
+pub fn generate_instrumented(flow: &Flow, instrumentation: Instrumentation) -> Result<String> {
+    check_single_file(flow)?;
+
     // 1. Index nodes by Parent ID
-    let mut nodes_by_parent: HashMap<Option<String>, Vec<&Node>> = HashMap::new();
-    let mut node_map: HashMap<String, &Node> = HashMap::new();
-    
-    for node in &flow.nodes {
-        node_map.insert(node.id.clone(), node);
-        nodes_by_parent.entry(node.parent_id.clone()).or_default().push(node);
+    let mut ctx = CodegenContext::new(flow)?;
+    ctx.instrument_with(instrumentation)?;
+
+    let mut code = generate_items(flow, &ctx, &HashSet::new())?;
+    code.push_str(instrumentation_runtime(instrumentation));
+    code.push_str(&generate_main(flow, &ctx)?);
+    code.push_str(&test_cases::test_module(flow)?);
+    Ok(code)
+}
+
+/// Library target - exported function-definitions become `pub` items of `lib.rs`, with no main
+pub fn generate_library(flow: &Flow) -> Result<String> {
+    check_single_file(flow)?;
+    let ctx = CodegenContext::new(flow)?;
+    let mut code = generate_items(flow, &ctx, &library_exports(flow, &ctx)?)?;
+    code.push_str(&test_cases::test_module(flow)?);
+    Ok(code)
+}
+
+/// Rust generated for one node, e.g. for an editor hover
+/// Functions show their whole definition, other containers the code of their body
+pub fn generate_node(flow: &Flow, node_id: &str) -> Result<String> {
+    let ctx = CodegenContext::new(flow)?;
+    let node = *ctx.node_map.get(node_id).ok_or_else(|| anyhow!("Node '{}' not found", node_id))?;
+    match function_name(node) {
+        Some("main") => generate_main(flow, &ctx),
+        Some(_) => generate_function(node, &ctx, &HashSet::new()),
+        None if !ctx.children(&node.id).is_empty() => generate_scope_code(ctx.children(&node.id), &ctx),
+        None => {
+            let incoming: Vec<&Connection> = flow.connections.iter().filter(|c| c.to == node.id).collect();
+            generate_node_code(node, &incoming, &ctx)
+        }
+    }
+}
+
+fn check_single_file(flow: &Flow) -> Result<()> {
+    let imports_files = !flow.modules.is_empty()
+        || flow.nodes.iter().any(|n| n.plugin_type == "subflow" && subflow::source_property(n).is_some());
+    if imports_files {
+        return Err(anyhow!("Flows importing other flow files must be generated with generate_project"));
+    }
+    Ok(())
+}
+
+/// Kind of crate a flow program is generated into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Target {
+    #[default]
+    Binary,
+    Library,
+}
+
+/// Extra code generated around each node to observe a running program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Instrumentation {
+    #[default]
+    None,
+    /// Entry and exit events per node; `values` adds the variables each node writes
+    Trace { values: bool },
+    /// Timers per node and per function call, reported when main ends
+    Profile,
+    /// Pauses before nodes at the debugger's request, reporting the variables in scope
+    Debug,
+}
+
+/// Support module the instrumented nodes call into, declared in the crate root
+fn instrumentation_runtime(instrumentation: Instrumentation) -> &'static str {
+    match instrumentation {
+        Instrumentation::None => "",
+        Instrumentation::Trace { .. } => trace::runtime(),
+        Instrumentation::Profile => profile::runtime(),
+        Instrumentation::Debug => debug::runtime(),
+    }
+}
+
+/// Statement opening a function body: times the whole call when profiling, records the arguments when debugging
+fn function_prologue(name: &str, arguments: &[String], ctx: &CodegenContext) -> String {
+    let prologue = match ctx.instrumentation {
+        Instrumentation::Profile => profile::enter_function(name),
+        Instrumentation::Debug => debug::record(name, arguments),
+        _ => String::new(),
+    };
+    if prologue.is_empty() { prologue } else { format!("    {}\n", prologue) }
+}
+
+/// Functions marked `export` plus every user-defined type, which exported signatures may use
+fn library_exports<'f>(flow: &'f Flow, ctx: &'f CodegenContext) -> Result<HashSet<&'f str>> {
+    if flow.nodes.iter().any(|n| function_name(n) == Some("main")) {
+        return Err(anyhow!("Library flows cannot define main"));
     }
+    let mut exports: HashSet<&str> = flow.nodes.iter()
+        .filter(|n| n.properties.get("export").and_then(|v| v.as_bool()).unwrap_or(false))
+        .filter_map(function_name)
+        .collect();
+    if exports.is_empty() {
+        return Err(anyhow!("Library target needs at least one function-definition with 'export' enabled"));
+    }
+    exports.extend(ctx.types.types.iter().map(|t| t.name.as_str()));
+    Ok(exports)
+}
+
+/// Source file of a generated multi-file program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFile {
+    /// Path relative to the crate's `src` directory
+    pub path: PathBuf,
+    pub code: String,
+}
+
+/// Modules of a multi-file program, prepared for generation
+struct Project<'a> {
+    graph: &'a ModuleGraph,
+    /// Flow of each module, with its imported items turned into `use crate::<module>::..` declarations
+    flows: HashMap<String, Flow>,
+    /// Modules whose functions are inlined elsewhere, so their items must be visible across the crate
+    inlined: HashSet<String>,
+    instrumentation: Instrumentation,
+}
+
+impl<'a> Project<'a> {
+    fn new(graph: &'a ModuleGraph, instrumentation: Instrumentation) -> Self {
+        let mut flows = HashMap::new();
+        let mut inlined = HashSet::new();
+        for module in &graph.modules {
+            let mut flow = module.flow.clone();
+            for (imported, items) in &module.imports {
+                match items.as_slice() {
+                    [] => {}
+                    [item] => flow.imports.push(format!("crate::{}::{}", imported, item)),
+                    items => flow.imports.push(format!("crate::{}::{{{}}}", imported, items.join(", "))),
+                }
+            }
+            flow.modules.clear();
+            flows.insert(module.name.clone(), flow);
+
+            let inlined_sources = module.flow.nodes.iter()
+                .filter(|n| n.plugin_type == "subflow" && str_property(n, "mode") == Some("inline"))
+                .filter_map(subflow::source_property);
+            for source in inlined_sources {
+                if let Some((_, packaged)) = module.subflows.iter().find(|(s, _)| s == source) {
+                    inlined.insert(packaged.clone());
+                }
+            }
+        }
+        Project { graph, flows, inlined, instrumentation }
+    }
+
+    /// Generation context of one module
+    fn context(&self, name: &str) -> Result<CodegenContext<'_>> {
+        let module = self.graph.get(name).ok_or_else(|| anyhow!("Unknown module '{}'", name))?;
+        let mut ctx = CodegenContext::new(&self.flows[name])?;
+        ctx.types.imported = self.graph.imported_types(name);
+        ctx.instrument_with(self.instrumentation)?;
+        ctx.subflow_modules = module.subflows.iter().cloned().collect();
+        ctx.project = Some(self);
+        Ok(ctx)
+    }
+}
+
+/// Generate one Rust module per flow file of the import graph
+/// The root flow becomes `main.rs` (or `lib.rs`) and declares the other modules
+pub fn generate_project(graph: &ModuleGraph, target: Target) -> Result<Vec<GeneratedFile>> {
+    generate_project_with(graph, target, Instrumentation::None)
+}
 
+/// `generate_project` with every module instrumented
+pub fn generate_project_with(graph: &ModuleGraph, target: Target, instrumentation: Instrumentation) -> Result<Vec<GeneratedFile>> {
+    let root = graph.root();
+    let project = Project::new(graph, instrumentation);
+    let mut files = Vec::new();
+
+    for module in &graph.modules {
+        let ctx = project.context(&module.name)?;
+        let flow = ctx.flow;
+        let mut exports: HashSet<&str> = graph.exports(&module.name).into_iter().collect();
+        if project.inlined.contains(&module.name) {
+            exports.extend(flow.nodes.iter().filter_map(function_name));
+            exports.extend(ctx.types.types.iter().map(|t| t.name.as_str()));
+        }
+
+        let mut file = if module.name == root.name {
+            let mut code: String = graph.modules.iter()
+                .filter(|m| m.name != root.name)
+                .map(|m| format!("mod {};\n", m.name))
+                .collect();
+            if !code.is_empty() {
+                code.push('\n');
+            }
+            code.push_str(instrumentation_runtime(instrumentation));
+            match target {
+                Target::Binary => {
+                    code.push_str(&generate_items(flow, &ctx, &exports)?);
+                    code.push_str(&generate_main(flow, &ctx)?);
+                    GeneratedFile { path: PathBuf::from("main.rs"), code }
+                }
+                Target::Library => {
+                    exports.extend(library_exports(flow, &ctx)?);
+                    code.push_str(&generate_items(flow, &ctx, &exports)?);
+                    GeneratedFile { path: PathBuf::from("lib.rs"), code }
+                }
+            }
+        } else {
+            if flow.nodes.iter().any(|n| function_name(n) == Some("main")) {
+                return Err(anyhow!("{} is imported as a module and cannot define main", module.path.display()));
+            }
+            let code = generate_items(flow, &ctx, &exports)?;
+            GeneratedFile { path: PathBuf::from(format!("{}.rs", module.name)), code }
+        };
+        file.code.push_str(&test_cases::test_module(flow)?);
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+fn function_name(node: &Node) -> Option<&str> {
+    if node.plugin_type != "function-definition" {
+        return None;
+    }
+    node.properties.get("function_name").and_then(|v| v.as_str())
+}
+
+/// Module-level items and every function except main; names in `exports` are made `pub`
+fn generate_items(flow: &Flow, ctx: &CodegenContext, exports: &HashSet<&str>) -> Result<String> {
     // 2. Identify Function Definitions
     let function_defs: Vec<&Node> = flow.nodes.iter()
         .filter(|n| n.plugin_type == "function-definition")
         .collect();
 
-    let mut code = String::new();
+    // 3. Module-level items: use declarations, user-defined types, then module-code blocks
+    let items = ModuleItems::from_flow(flow);
+    let mut code = items.render_uses();
+    code.push_str(&types::type_items(&ctx.types, exports));
+    code.push_str(&items.render_items());
 
-    // 3. Generate Functions
+    // 4. Generate Functions
     for func_def in &function_defs {
-        let func_name = func_def.properties.get("function_name")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown");
-            
-        if func_name == "main" {
+        if function_name(func_def) == Some("main") {
             continue; // Skip main here, handled separately
         }
-        
-        // Generate arguments signature
-        let arguments = func_def.properties.get("arguments")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.to_vec())
-            .unwrap_or_default();
-            
-        let mut args_str = Vec::new();
-        for arg in arguments {
-            let name = arg.get("name").and_then(|v| v.as_str()).unwrap_or("arg");
-            let type_ = arg.get("type").and_then(|v| v.as_str()).unwrap_or("i32");
-            args_str.push(format!("{}: {}", name, type_));
-        }
-        
-        // Get return type if specified
-        let return_type = func_def.properties.get("return_type")
-            .and_then(|v| v.as_str())
-            .filter(|s| !s.is_empty());
-        
-        // Generate body
-        let children = nodes_by_parent.get(&Some(func_def.id.clone()))
-            .map(|v| v.as_slice())
-            .unwrap_or(&[]);
-            
-        let body = generate_scope_code(children, &flow.connections, &node_map)?;
-        
-        // Generate function signature with optional return type
-        if let Some(ret_type) = return_type {
-            code.push_str(&format!("async fn {}({}) -> {} {{\n{}\n}}\n\n", func_name, args_str.join(", "), ret_type, body));
-        } else {
-            code.push_str(&format!("async fn {}({}) {{\n{}\n}}\n\n", func_name, args_str.join(", "), body));
+        code.push_str(&generate_function(func_def, ctx, exports)?);
+    }
+
+    Ok(code)
+}
+
+/// `async fn` of a function-definition other than main; names in `exports` are made `pub`
+fn generate_function(func_def: &Node, ctx: &CodegenContext, exports: &HashSet<&str>) -> Result<String> {
+    let func_name = function_name(func_def).unwrap_or("unknown");
+    let visibility = if exports.contains(func_name) { "pub " } else { "" };
+
+    // Generate arguments signature
+    let arguments = func_def.properties.get("arguments")
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.to_vec())
+        .unwrap_or_default();
+
+    let mut args_str = Vec::new();
+    let mut arg_names = Vec::new();
+    for arg in arguments {
+        let name = arg.get("name").and_then(|v| v.as_str()).unwrap_or("arg");
+        let type_ = arg.get("type").and_then(|v| v.as_str()).unwrap_or("i32");
+        args_str.push(format!("{}: {}", name, type_));
+        arg_names.push(name.to_string());
+    }
+    // Channel ends used inside the function are passed in as parameters
+    args_str.extend(ctx.channels.parameters(func_name));
+
+    // Get return type if specified
+    let return_type = func_def.properties.get("return_type")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty());
+
+    // Generate body
+    let children = ctx.children(&func_def.id);
+    let mut body = function_prologue(func_name, &arg_names, ctx);
+    body.push_str(&generate_scope_code(children, ctx)?);
+
+    // Fallible functions wrap the declared return type in Result
+    let return_type = if is_fallible(func_def) {
+        if return_type.is_none() {
+            body.push_str("    Ok(())\n");
         }
+        Some(format!("Result<{}, {}>", return_type.unwrap_or("()"), error_type(func_def)))
+    } else {
+        return_type.map(String::from)
+    };
+
+    // Generate function signature with optional return type
+    if let Some(ret_type) = return_type {
+        Ok(format!("{}async fn {}({}) -> {} {{\n{}\n}}\n\n", visibility, func_name, args_str.join(", "), ret_type, body))
+    } else {
+        Ok(format!("{}async fn {}({}) {{\n{}\n}}\n\n", visibility, func_name, args_str.join(", "), body))
     }
+}
 
-    // 4. Generate Main
-    let main_def = function_defs.iter().find(|n| 
-        n.properties.get("function_name").and_then(|v| v.as_str()) == Some("main")
-    );
-    
+fn generate_main(flow: &Flow, ctx: &CodegenContext) -> Result<String> {
+    let mut code = String::new();
+
+    // 5. Generate Main
+    let main_def = flow.nodes.iter().find(|n| function_name(n) == Some("main"));
     let main_children = if let Some(main) = main_def {
-        nodes_by_parent.get(&Some(main.id.clone()))
-            .map(|v| v.as_slice())
-            .unwrap_or(&[])
-            .to_vec()
+        ctx.children(&main.id).to_vec()
     } else {
         // Fallback: use root nodes (parent_id == None) that are NOT function definitions
-        nodes_by_parent.get(&None)
+        ctx.nodes_by_parent.get(&None)
             .map(|v| v.as_slice())
             .unwrap_or(&[])
             .iter()
@@ -91,23 +716,60 @@ pub fn generate_rust(flow: &Flow) -> Result<String> {
             .collect()
     };
     
-    let main_body = generate_scope_code(&main_children, &flow.connections, &node_map)?;
-    
-    code.push_str("#[tokio::main]\nasync fn main() {\n");
-    code.push_str(&main_body);
-    code.push_str("}\n");
+    let main_body = function_prologue("main", &[], ctx) + &generate_scope_code(&main_children, ctx)?;
+    // Profiled programs write their samples once main is done
+    let report = match ctx.instrumentation {
+        Instrumentation::Profile => format!("    {}\n", profile::report_guard()),
+        _ => String::new(),
+    };
+
+    match main_def.filter(|main| is_fallible(main)) {
+        Some(main) => {
+            // Fallible main runs in its own function so errors get reported instead of panicking
+            code.push_str(&format!("async fn run_main() -> Result<(), {}> {{\n", error_type(main)));
+            code.push_str(&main_body);
+            code.push_str("    Ok(())\n}\n\n");
+            code.push_str("#[tokio::main]\nasync fn main() {\n");
+            code.push_str(&report);
+            code.push_str("    if let Err(error) = run_main().await {\n");
+            code.push_str("        eprintln!(\"Error: {}\", error);\n");
+            if !report.is_empty() {
+                code.push_str("        drop(__flust_report);\n");
+            }
+            code.push_str("        std::process::exit(1);\n");
+            code.push_str("    }\n");
+            code.push_str("}\n");
+        }
+        None => {
+            code.push_str("#[tokio::main]\nasync fn main() {\n");
+            code.push_str(&report);
+            code.push_str(&main_body);
+            code.push_str("}\n");
+        }
+    }
 
     Ok(code)
 }
 
-fn generate_scope_code(nodes: &[&Node], connections: &[Connection], node_map: &HashMap<String, &Node>) -> Result<String> {
+fn generate_scope_code(nodes: &[&Node], ctx: &CodegenContext) -> Result<String> {
     if nodes.is_empty() {
         return Ok(String::new());
     }
 
+    // Error branches are generated inside their call's `Err` arm, not in sequence
+    let branch_ids: HashSet<String> = nodes.iter()
+        .filter(|n| n.plugin_type == "call-function")
+        .flat_map(|n| ctx.error_branch(n))
+        .map(|n| n.id.clone())
+        .collect();
+    let nodes: Vec<&Node> = nodes.iter()
+        .filter(|n| !branch_ids.contains(&n.id))
+        .copied()
+        .collect();
+
     // Create sub-flow for topological sort
     let child_ids: HashSet<String> = nodes.iter().map(|n| n.id.clone()).collect();
-    let relevant_connections: Vec<Connection> = connections.iter()
+    let relevant_connections: Vec<Connection> = ctx.connections.iter()
         .filter(|c| child_ids.contains(&c.from) && child_ids.contains(&c.to))
         .cloned()
         .collect();
@@ -115,30 +777,45 @@ fn generate_scope_code(nodes: &[&Node], connections: &[Connection], node_map: &H
     let sub_flow = Flow {
         nodes: nodes.iter().map(|&n| n.clone()).collect(),
         connections: relevant_connections.clone(),
+        imports: Vec::new(),
+        modules: Vec::new(),
     };
     
     let sorted_ids = TopologicalSort::sort(&sub_flow)?;
     
     // Map incoming connections for each node (for variable mapping)
-    let incoming_connections: HashMap<String, Vec<&Connection>> = connections.iter()
+    let incoming_connections: HashMap<String, Vec<&Connection>> = ctx.connections.iter()
         .fold(HashMap::new(), |mut acc, conn| {
             acc.entry(conn.to.clone()).or_default().push(conn);
             acc
         });
 
-    let mut scope_code = String::new();
-    
+    let mut generated = Vec::new();
     for node_id in sorted_ids {
-        let node = node_map.get(&node_id).ok_or_else(|| anyhow!("Node not found"))?;
+        let node = ctx.node_map.get(&node_id).ok_or_else(|| anyhow!("Node not found"))?;
         
-        // Skip start-node (it just starts the flow) and function-definition (containers)
-        if node.plugin_type == "start-node" || node.plugin_type == "function-definition" {
+        // Skip start-node (it just starts the flow), function-definition (containers)
+        // type definitions / module code (generated as top-level items) and test cases (test module)
+        if matches!(
+            node.plugin_type.as_str(),
+            "start-node" | "function-definition" | "struct-definition" | "enum-definition" | "module-code" | "test-case"
+        ) {
             continue;
         }
         
-        let incoming = incoming_connections.get(&node_id).map(|v| v.as_slice()).unwrap_or(&[]);
-        let node_code = generate_node_code(node, incoming)?;
+        // Inlined expressions are rendered inside the node consuming them
+        if ctx.is_inlined(node) {
+            continue;
+        }
         
+        let incoming = incoming_connections.get(&node_id).map(|v| v.as_slice()).unwrap_or(&[]);
+        generated.push((*node, generate_node_code(node, incoming, ctx)?));
+    }
+
+    let mut scope_code = String::new();
+    let count = generated.len();
+    for (index, (node, node_code)) in generated.into_iter().enumerate() {
+        let node_code = instrument(node, node_code, index + 1 == count, ctx);
         for line in node_code.lines() {
             if !line.trim().is_empty() {
                 scope_code.push_str("    ");
@@ -151,7 +828,91 @@ fn generate_scope_code(nodes: &[&Node], connections: &[Connection], node_map: &H
     Ok(scope_code)
 }
 
-fn generate_node_code(node: &Node, incoming_connections: &[&Connection]) -> Result<String> {
+/// Node code wrapped in the instrumentation's entry and exit statements
+/// The last node of a scope may be its tail expression, so it is closed when its span is dropped
+fn instrument(node: &Node, code: String, last: bool, ctx: &CodegenContext) -> String {
+    let (enter, exit) = match ctx.instrumentation {
+        Instrumentation::None => return code,
+        Instrumentation::Trace { values } => (trace::enter(&node.id), trace::exit(&traced_variables(node, values))),
+        Instrumentation::Profile => (profile::enter(&node.id), profile::exit()),
+        Instrumentation::Debug => {
+            let function = ctx.enclosing_function(node).and_then(function_name).unwrap_or("main");
+            (debug::pause(&node.id, function, &visible_variables(node, ctx)), debug::record(function, &traced_variables(node, true)))
+        }
+    };
+    let mut wrapped = format!("{}\n{}\n", enter, code);
+    if !last || code.trim_end().ends_with(';') {
+        wrapped.push_str(&exit);
+    }
+    wrapped
+}
+
+/// Names in scope before a node runs, innermost binding of each name only
+/// A `let x;` not assigned yet has no value to show
+fn visible_variables(node: &Node, ctx: &CodegenContext) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for variable in ctx.scope.visible_at(&node.id) {
+        names.retain(|name| *name != variable.name);
+        if variable.initialized {
+            names.push(variable.name.clone());
+        }
+    }
+    names
+}
+
+/// Variables whose values a traced node reports on exit
+fn traced_variables(node: &Node, values: bool) -> Vec<String> {
+    if !values || node.plugin_type == "channel" {
+        return Vec::new();
+    }
+    let mut variables = scope::written_variables(node);
+    // A named output is already one of the written variables
+    if is_expression_node(node) && str_property(node, "output_variable").is_none() {
+        variables.push(output_variable(node));
+    }
+    variables
+}
+
+/// Statement leaving the enclosing function once an error branch has run
+fn error_exit(call: &Node, error_var: &str, ctx: &CodegenContext) -> String {
+    match ctx.enclosing_function(call) {
+        Some(func_def) if is_fallible(func_def) => format!("return Err({}.into());", error_var),
+        Some(func_def) if func_def.properties.get("return_type")
+            .and_then(|v| v.as_str())
+            .is_some_and(|s| !s.is_empty()) => {
+            // A typed, infallible function has no value to return on failure
+            format!("panic!(\"{} failed: {{:?}}\", {});", call.id, error_var)
+        }
+        _ => "return;".to_string(),
+    }
+}
+
+fn generate_node_code(node: &Node, incoming_connections: &[&Connection], ctx: &CodegenContext) -> Result<String> {
+    if is_expression_node(node) {
+        let binding = if ctx.is_mutated(node) { "let mut" } else { "let" };
+        return Ok(format!("{} {} = {};", binding, output_variable(node), ctx.expression(node)?));
+    }
+    if is_statement_node(node) {
+        return Ok(format!("{};", ctx.expression(node)?));
+    }
+
+    // Values wired into a port replace the property of the same name
+    let wired;
+    let node = if incoming_connections.iter().any(|c| c.to_port.is_some()) {
+        let mut clone = node.clone();
+        for conn in incoming_connections {
+            if let Some(port) = &conn.to_port
+                && let Some(value) = ctx.port_input(node, port)?
+            {
+                clone.properties.insert(port.clone(), serde_json::Value::String(value));
+            }
+        }
+        wired = clone;
+        &wired
+    } else {
+        node
+    };
+
     match node.plugin_type.as_str() {
         "call-function" => {
             let func_name = node.properties.get("target_function")
@@ -164,19 +925,26 @@ fn generate_node_code(node: &Node, incoming_connections: &[&Connection]) -> Resu
                 .unwrap_or_default();
                 
             // Find variable mapping from incoming connection
-            // We assume the first incoming connection carries the mapping
+            // We use the first incoming connection that carries a mapping
             // In a more complex flow, we might need to merge mappings or check specific connection
-            let mapping = incoming_connections.first()
-                .and_then(|c| c.variable_mapping.as_ref())
-                .ok_or_else(|| anyhow!("Call function '{}' missing incoming connection with variable mapping", func_name))?;
+            let mapping = incoming_connections.iter()
+                .find_map(|c| c.variable_mapping.as_ref());
                 
             let mut call_args = Vec::new();
             for arg in arguments {
                 let arg_name = arg.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                let var_name = mapping.get(arg_name)
+                // Arguments wired through a data connection take precedence over the mapping
+                if let Some(value) = ctx.port_input(node, arg_name)? {
+                    call_args.push(value);
+                    continue;
+                }
+                let var_name = mapping
+                    .ok_or_else(|| anyhow!("Call function '{}' missing incoming connection with variable mapping", func_name))?
+                    .get(arg_name)
                     .ok_or_else(|| anyhow!("Argument '{}' not mapped", arg_name))?;
-                call_args.push(var_name.as_str());
+                call_args.push(var_name.clone());
             }
+            call_args.extend(ctx.channels.call_arguments(func_name));
             
             // Check if there's a return variable
             let return_var = node.properties.get("return_variable")
@@ -197,26 +965,146 @@ fn generate_node_code(node: &Node, incoming_connections: &[&Connection]) -> Resu
             let is_mutable = node.properties.get("is_mutable")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
+
+            // Spawned calls run concurrently and yield a JoinHandle instead of the return value
+            let spawn = node.properties.get("spawn")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            
+            // How a failed call is handled: "none", "propagate" (`?`) or "branch" (`match`)
+            let error_handling = node.properties.get("error_handling")
+                .and_then(|v| v.as_str())
+                .unwrap_or("none");
+            // The JoinHandle of a spawned call is neither `Try` nor a `Result`
+            if spawn && error_handling != "none" {
+                return Err(anyhow!("Call '{}' is spawned, so its errors cannot be handled with '{}'", node.id, error_handling));
+            }
+            
+            let mut function_call = if spawn {
+                format!("tokio::spawn({}({}))", func_name, call_args.join(", "))
+            } else {
+                format!("{}({}).await", func_name, call_args.join(", "))
+            };
+            
+            match error_handling {
+                "none" => {}
+                "propagate" => {
+                    let enclosing = ctx.enclosing_function(node);
+                    if !enclosing.map(is_fallible).unwrap_or(false) {
+                        return Err(anyhow!("Call '{}' propagates errors with '?' but its function is not fallible", node.id));
+                    }
+                    function_call.push('?');
+                }
+                "branch" => {
+                    let error_var = node.properties.get("error_variable")
+                        .and_then(|v| v.as_str())
+                        .filter(|s| !s.is_empty())
+                        .unwrap_or("error");
+                    let mut arm = generate_scope_code(&ctx.error_branch(node), ctx)?;
+                    for line in error_exit(node, error_var, ctx).lines() {
+                        arm.push_str(&format!("    {}\n", line));
+                    }
+                    let arm: String = arm.lines().map(|line| format!("    {}\n", line)).collect();
+                    let ok_arm = if return_var.is_some() { "Ok(value) => value," } else { "Ok(_) => {}" };
+                    function_call = format!(
+                        "match {} {{\n    {}\n    Err({}) => {{\n{}    }}\n}}",
+                        function_call, ok_arm, error_var, arm
+                    );
+                }
+                other => return Err(anyhow!("Unknown error_handling '{}' on call '{}'", other, node.id)),
+            }
             
             if let Some(var_name) = return_var {
                 let mut_keyword = if is_mutable { "mut " } else { "" };
-                let function_call = format!("{}({}).await", func_name, call_args.join(", "));
                 
                 if declare_variable {
                     // Declare new variable: let [mut] var[: Type] = ...
-                    if let Some(ret_type) = return_type {
-                        Ok(format!("let {}{}: {} = {};", mut_keyword, var_name, ret_type, function_call))
-                    } else {
-                        Ok(format!("let {}{} = {};", mut_keyword, var_name, function_call))
+                    match return_type {
+                        Some(ret_type) if !spawn => Ok(format!("let {}{}: {} = {};", mut_keyword, var_name, ret_type, function_call)),
+                        _ => Ok(format!("let {}{} = {};", mut_keyword, var_name, function_call)),
                     }
                 } else {
                     // Assign to existing variable: var = ...
                     Ok(format!("{} = {};", var_name, function_call))
                 }
             } else {
-                Ok(format!("{}({}).await;", func_name, call_args.join(", ")))
+                Ok(format!("{};", function_call))
+            }
+        },
+        "let-variable" => {
+            let name = node.properties.get("variable_name")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| anyhow!("Let '{}' missing variable_name", node.id))?;
+            let var_type = node.properties.get("variable_type")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty());
+            let value = node.properties.get("value")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty());
+            let is_mutable = node.properties.get("is_mutable")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            
+            let mut statement = format!("let {}{}", if is_mutable { "mut " } else { "" }, name);
+            if let Some(var_type) = var_type {
+                statement.push_str(&format!(": {}", var_type));
+            }
+            if let Some(value) = value {
+                statement.push_str(&format!(" = {}", value));
+            }
+            statement.push(';');
+            Ok(statement)
+        },
+        "assign-variable" => {
+            let name = node.properties.get("variable_name")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| anyhow!("Assignment '{}' missing variable_name", node.id))?;
+            let value = node.properties.get("value")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| anyhow!("Assignment '{}' missing value", node.id))?;
+            let operator = node.properties.get("operator")
+                .and_then(|v| v.as_str())
+                .unwrap_or("=");
+            if !matches!(operator, "=" | "+=" | "-=" | "*=" | "/=" | "%=") {
+                return Err(anyhow!("Assignment '{}' has unknown operator '{}'", node.id, operator));
+            }
+            Ok(format!("{} {} {};", name, operator, value))
+        },
+        "channel" => {
+            let name = node.properties.get("channel_name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let channel = ctx.channels.get(name)
+                .ok_or_else(|| anyhow!("Channel '{}' missing channel_name", node.id))?;
+            Ok(channel.declaration())
+        },
+        "channel-send" => {
+            let channel = ctx.channels.channel_for(node)?;
+            let value = node.properties.get("value")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| anyhow!("Channel send '{}' missing value", node.id))?;
+            Ok(channel.send_statement(value))
+        },
+        "channel-receive" => {
+            // Receive loops are containers: their children form the loop body
+            let channel = ctx.channels.channel_for(node)?;
+            let variable = node.properties.get("message_variable")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+                .unwrap_or("msg");
+            let body = generate_scope_code(ctx.children(&node.id), ctx)?;
+            let receive_loop = channel.receive_loop(variable, &body);
+            if ctx.channels.drops_sender_before(node) {
+                Ok(format!("drop({});\n{}", channel.sender_name(), receive_loop))
+            } else {
+                Ok(receive_loop)
             }
         },
+        "subflow" => ctx.subflow(node),
         "legacy-code" | "legacy_code" => {
             let mut context = HashMap::new();
             for (k, v) in &node.properties {
@@ -250,7 +1138,9 @@ fn generate_node_code(node: &Node, incoming_connections: &[&Connection]) -> Resu
 #[cfg(test)]
 mod tests {
     use super::*;
-    use flust_core::ir::{Node, Connection, ConnectionType};
+    use crate::fixtures::{connection, node};
+    use flust_core::ir::{Node, Connection, ConnectionType, ModuleImport};
+    use std::path::Path;
     use serde_json::json;
     
     #[test]
@@ -295,6 +1185,8 @@ mod tests {
             to: "debug".to_string(),
             connection_type: ConnectionType::Simple,
             variable_mapping: None,
+            from_port: None,
+            to_port: None,
         };
         
         // 4. Define Main Container
@@ -340,11 +1232,14 @@ mod tests {
             to: "call".to_string(),
             connection_type: ConnectionType::Simple,
             variable_mapping: Some(mapping),
+            from_port: None,
+            to_port: None,
         };
         
         let flow = Flow {
             nodes: vec![func_container, start_node, debug_node, main_container, main_start, call_node],
             connections: vec![conn1, conn2],
+            ..Default::default()
         };
         
         let code = generate_rust(&flow).unwrap();
@@ -357,4 +1252,467 @@ mod tests {
         assert!(code.contains("async fn main() {"));
         assert!(code.contains("my_func(42).await;"));
     }
+
+    #[test]
+    fn test_channel_pipeline_generation() {
+        let flow = Flow {
+            nodes: vec![
+                node("producer", "function-definition", None, json!({"function_name": "produce", "arguments": []})),
+                node("p_start", "start-node", Some("producer"), json!({})),
+                node("send", "channel-send", Some("producer"), json!({"channel": "readings", "value": "42"})),
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("m_start", "start-node", Some("main"), json!({})),
+                node("chan", "channel", Some("main"), json!({"channel_name": "readings", "channel_kind": "mpsc", "message_type": "i32", "capacity": 8})),
+                node("spawn", "call-function", Some("main"), json!({"target_function": "produce", "arguments": [], "spawn": true})),
+                node("recv", "channel-receive", Some("main"), json!({"channel": "readings", "message_variable": "reading"})),
+                node("print", "debug", Some("recv"), json!({"variable": "reading"})),
+            ],
+            connections: vec![
+                connection("p_start", "send", None),
+                connection("m_start", "chan", None),
+                connection("chan", "spawn", Some(HashMap::new())),
+                connection("spawn", "recv", None),
+            ],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.contains("async fn produce(readings_tx: tokio::sync::mpsc::Sender<i32>) {"));
+        assert!(code.contains("let _ = readings_tx.send(42).await;"));
+        assert!(code.contains("let (readings_tx, mut readings_rx) = tokio::sync::mpsc::channel::<i32>(8);"));
+        assert!(code.contains("tokio::spawn(produce(readings_tx.clone()));"));
+        assert!(code.contains("    drop(readings_tx);\n    while let Some(reading) = readings_rx.recv().await {\n        println!(\"{:?}\", reading);\n    }"));
+    }
+
+    #[test]
+    fn test_fallible_calls_and_error_branch() {
+        let mut parse_mapping = HashMap::new();
+        parse_mapping.insert("text".to_string(), "\"42\"".to_string());
+        let error_conn = Connection {
+            from: "checked".to_string(),
+            to: "report".to_string(),
+            connection_type: ConnectionType::Error,
+            variable_mapping: None,
+            from_port: None,
+            to_port: None,
+        };
+
+        let flow = Flow {
+            nodes: vec![
+                node("parse", "function-definition", None, json!({
+                    "function_name": "parse_number",
+                    "arguments": [{"name": "text", "type": "&str"}],
+                    "return_type": "i32",
+                    "fallible": true
+                })),
+                node("p_code", "legacy-code", Some("parse"), json!({"code": "Ok(text.parse::<i32>()?)"})),
+                node("main", "function-definition", None, json!({"function_name": "main", "fallible": true})),
+                node("m_start", "start-node", Some("main"), json!({})),
+                node("propagated", "call-function", Some("main"), json!({
+                    "target_function": "parse_number",
+                    "arguments": [{"name": "text", "type": "&str"}],
+                    "return_variable": "n",
+                    "error_handling": "propagate"
+                })),
+                node("checked", "call-function", Some("main"), json!({
+                    "target_function": "parse_number",
+                    "arguments": [{"name": "text", "type": "&str"}],
+                    "return_variable": "m",
+                    "error_handling": "branch"
+                })),
+                node("report", "debug", Some("main"), json!({"variable": "error"})),
+            ],
+            connections: vec![
+                connection("m_start", "propagated", Some(parse_mapping.clone())),
+                connection("propagated", "checked", Some(parse_mapping)),
+                error_conn,
+            ],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.contains("async fn parse_number(text: &str) -> Result<i32, Box<dyn std::error::Error + Send + Sync>> {"));
+        assert!(code.contains("let n = parse_number(\"42\").await?;"));
+        assert!(code.contains("    let m = match parse_number(\"42\").await {\n        Ok(value) => value,\n        Err(error) => {\n            println!(\"{:?}\", error);\n            return Err(error.into());\n        }\n    };"));
+        assert!(code.contains("async fn run_main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {"));
+        assert!(code.contains("    if let Err(error) = run_main().await {\n        eprintln!(\"Error: {}\", error);\n        std::process::exit(1);\n    }"));
+    }
+
+    #[test]
+    fn test_propagate_requires_fallible_function() {
+        let flow = Flow {
+            nodes: vec![
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("m_start", "start-node", Some("main"), json!({})),
+                node("call", "call-function", Some("main"), json!({"target_function": "work", "error_handling": "propagate"})),
+            ],
+            connections: vec![connection("m_start", "call", Some(HashMap::new()))],
+            ..Default::default()
+        };
+
+        assert!(generate_rust(&flow).is_err());
+
+        let spawned = Flow {
+            nodes: vec![
+                node("main", "function-definition", None, json!({"function_name": "main", "fallible": true})),
+                node("m_start", "start-node", Some("main"), json!({})),
+                node("call", "call-function", Some("main"), json!({"target_function": "work", "spawn": true, "error_handling": "branch"})),
+            ],
+            connections: vec![connection("m_start", "call", Some(HashMap::new()))],
+            ..Default::default()
+        };
+        let error = generate_rust(&spawned).unwrap_err().to_string();
+        assert!(error.contains("is spawned"), "{}", error);
+    }
+
+    #[test]
+    fn test_let_and_assign_generation() {
+        let flow = Flow {
+            nodes: vec![
+                node("decl", "let-variable", None, json!({"variable_name": "count", "variable_type": "u32", "value": "0", "is_mutable": true})),
+                node("inc", "assign-variable", None, json!({"variable_name": "count", "operator": "+=", "value": "1"})),
+            ],
+            connections: vec![connection("decl", "inc", None)],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.contains("    let mut count: u32 = 0;\n    count += 1;\n"));
+    }
+
+    fn data(from: &str, to: &str, to_port: &str) -> Connection {
+        Connection {
+            from: from.to_string(),
+            to: to.to_string(),
+            connection_type: ConnectionType::Simple,
+            variable_mapping: None,
+            from_port: Some("result".to_string()),
+            to_port: Some(to_port.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_expression_nodes_are_inlined() {
+        let flow = Flow {
+            nodes: vec![
+                node("sensor", "let-variable", None, json!({"variable_name": "temperature", "variable_type": "f64", "value": "31.5"})),
+                node("offset", "add", None, json!({"b": "0.5"})),
+                node("scaled", "multiply", None, json!({"b": "2.0", "output_variable": "scaled"})),
+                node("threshold", "greater-than", None, json!({"b": "60.0"})),
+                node("alert", "debug", None, json!({"label": "alert"})),
+            ],
+            connections: vec![
+                data("sensor", "offset", "a"),
+                data("offset", "scaled", "a"),
+                data("scaled", "threshold", "a"),
+                data("threshold", "alert", "variable"),
+            ],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.contains("    let temperature: f64 = 31.5;\n    let scaled = (temperature + 0.5) * 2.0;\n    println!(\"alert: {:?}\", scaled > 60.0);\n"));
+    }
+
+    #[test]
+    fn test_collection_nodes_fuse_into_iterator_chain() {
+        let flow = Flow {
+            nodes: vec![
+                node("input", "let-variable", None, json!({"variable_name": "csv", "value": "\"ana, ,luis\""})),
+                node("split", "split", None, json!({"separator": "','"})),
+                node("trim", "vec-map", None, json!({"body": "item.trim().to_string()"})),
+                node("non_empty", "vec-filter", None, json!({"predicate": "!item.is_empty()"})),
+                node("names", "let-variable", None, json!({"variable_name": "names", "is_mutable": true})),
+                node("push", "push", None, json!({"value": "\"eva\".to_string()"})),
+            ],
+            connections: vec![
+                data("input", "split", "text"),
+                data("split", "trim", "list"),
+                data("trim", "non_empty", "list"),
+                data("non_empty", "names", "value"),
+                data("names", "push", "list"),
+            ],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.contains("    let mut names = csv.split(',').map(String::from).map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect::<Vec<_>>();\n    names.push(\"eva\".to_string());\n"));
+    }
+
+    #[test]
+    fn test_mutated_collections_are_bound_mutably() {
+        let flow = Flow {
+            nodes: vec![
+                node("nums", "vec-new", None, json!({"element_type": "i32", "output_variable": "nums"})),
+                node("add", "push", None, json!({"value": "4"})),
+                node("scores", "map-new", None, json!({"key_type": "String", "value_type": "i32"})),
+                node("insert", "map-insert", None, json!({"key": "\"ana\".to_string()", "value": "7"})),
+                node("show", "debug", None, json!({"variable": "nums"})),
+            ],
+            connections: vec![
+                data("nums", "add", "list"),
+                data("scores", "insert", "map"),
+                connection("add", "show", None),
+                connection("insert", "show", None),
+            ],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.contains("    let mut nums = Vec::<i32>::new();\n"));
+        assert!(code.contains("    nums.push(4);\n"));
+        assert!(code.contains("    let mut scores_value = std::collections::HashMap::<String, i32>::new();\n"));
+        assert!(code.contains("    scores_value.insert(\"ana\".to_string(), 7);\n"));
+    }
+
+    #[test]
+    fn test_type_definitions_are_top_level_items() {
+        let flow = Flow {
+            nodes: vec![
+                node("point", "struct-definition", None, json!({"type_name": "Point", "derives": "Debug", "fields": "x: f64\ny: f64"})),
+                node("shape", "enum-definition", None, json!({"type_name": "Shape", "variants": "Empty\nCircle(f64)"})),
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("origin", "construct", Some("main"), json!({"type_name": "Point", "values": "x: 0.0", "output_variable": "origin"})),
+                node("radius", "let-variable", Some("main"), json!({"variable_name": "radius", "value": "2.5"})),
+                node("x", "field-access", Some("main"), json!({"field": "x", "output_variable": "x"})),
+                node("circle", "construct", Some("main"), json!({"type_name": "Shape", "variant": "Circle", "output_variable": "circle"})),
+            ],
+            connections: vec![
+                data("radius", "origin", "y"),
+                data("origin", "x", "value"),
+                data("radius", "circle", "0"),
+            ],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.starts_with("#[derive(Debug)]\nstruct Point {\n    x: f64,\n    y: f64,\n}\n\nenum Shape {\n    Empty,\n    Circle(f64),\n}\n\n"));
+        assert!(code.contains("let origin = Point { x: 0.0, y: radius };"));
+        assert!(code.contains("let x = origin.x;"));
+        assert!(code.contains("let circle = Shape::Circle(radius);"));
+    }
+
+    #[test]
+    fn test_project_has_one_module_per_flow_file() {
+        let util = Flow {
+            nodes: vec![
+                node("reading", "struct-definition", None, json!({"type_name": "Reading", "fields": "value: f64"})),
+                node("scale", "function-definition", None, json!({"function_name": "scale", "arguments": [{"name": "x", "type": "f64"}], "return_type": "f64"})),
+                node("helper", "function-definition", None, json!({"function_name": "helper"})),
+            ],
+            ..Default::default()
+        };
+        let app = Flow {
+            nodes: vec![
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("r", "construct", Some("main"), json!({"type_name": "Reading", "values": "value: 1.0", "output_variable": "r"})),
+            ],
+            modules: vec![ModuleImport { path: "util.flow.json".to_string(), items: vec!["scale".to_string(), "Reading".to_string()] }],
+            ..Default::default()
+        };
+        let graph = ModuleGraph::load_with(Path::new("app.flow.json"), |path| {
+            Ok(if path == Path::new("util.flow.json") { util.clone() } else { app.clone() })
+        }).unwrap();
+
+        let files = generate_project(&graph, Target::Binary).unwrap();
+
+        assert_eq!(files[0].path, PathBuf::from("util.rs"));
+        assert!(files[0].code.starts_with("pub struct Reading {\n    pub value: f64,\n}\n"));
+        assert!(files[0].code.contains("pub async fn scale(x: f64) -> f64 {"));
+        assert!(files[0].code.contains("\nasync fn helper() {"));
+        assert_eq!(files[1].path, PathBuf::from("main.rs"));
+        assert!(files[1].code.starts_with("mod util;\n\nuse crate::util::{scale, Reading};\n\n"));
+        assert!(files[1].code.contains("let r = Reading { value: 1.0 };"));
+        assert!(generate_rust(&app).is_err());
+    }
+
+    #[test]
+    fn test_subflow_call_and_inline() {
+        let flow = Flow {
+            nodes: vec![
+                node("half", "function-definition", None, json!({"function_name": "half", "arguments": [{"name": "x", "type": "f64"}], "return_type": "f64"})),
+                node("body", "legacy-code", Some("half"), json!({"code": "x / 2.0"})),
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("called", "subflow", Some("main"), json!({"function": "half", "x": "10.0", "output_variable": "called"})),
+                node("inlined", "subflow", Some("main"), json!({"function": "half", "mode": "inline", "output_variable": "inlined"})),
+            ],
+            connections: vec![data("called", "inlined", "x")],
+            ..Default::default()
+        };
+
+        let code = generate_rust(&flow).unwrap();
+
+        assert!(code.contains("    let called = half(10.0).await;\n    let inlined = async {\n        let x: f64 = called;\n        x / 2.0\n    }.await;\n"));
+    }
+
+    #[test]
+    fn test_subflow_inlined_from_another_file() {
+        let checks = Flow {
+            nodes: vec![
+                node("limit", "struct-definition", None, json!({"type_name": "Limit", "fields": "max: i32"})),
+                node("bound", "function-definition", None, json!({"function_name": "bound", "return_type": "i32"})),
+                node("b_body", "legacy-code", Some("bound"), json!({"code": "100"})),
+                node("validate", "function-definition", None, json!({"function_name": "validate", "arguments": [{"name": "x", "type": "i32"}], "fallible": true})),
+                node("v_body", "legacy-code", Some("validate"), json!({"code": "if x < 0 {\n    return Err(\"negative\".into());\n}\nlet limit = Limit { max: bound().await };\nprintln!(\"{} <= {}\", x, limit.max);"})),
+            ],
+            ..Default::default()
+        };
+        let app = Flow {
+            nodes: vec![
+                node("main", "function-definition", None, json!({"function_name": "main", "fallible": true})),
+                node("check", "subflow", Some("main"), json!({"source": "checks.flow.json", "function": "validate", "mode": "inline", "x": "5"})),
+            ],
+            ..Default::default()
+        };
+        let graph = ModuleGraph::load_with(Path::new("app.flow.json"), |path| {
+            Ok(if path == Path::new("checks.flow.json") { checks.clone() } else { app.clone() })
+        }).unwrap();
+
+        let files = generate_project(&graph, Target::Binary).unwrap();
+
+        assert!(files[0].code.contains("pub struct Limit {"));
+        assert!(files[0].code.contains("pub async fn bound() -> i32 {"));
+        assert!(files[1].code.contains(concat!(
+            "    {\n",
+            "        #[allow(unused_imports)]\n",
+            "        use crate::checks::*;\n",
+            "        let __flust_result: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {\n",
+            "            let x: i32 = 5;\n",
+        )), "{}", files[1].code);
+        assert!(files[1].code.contains("            Ok(())\n        }.await;\n        __flust_result?\n    };\n"), "{}", files[1].code);
+    }
+
+    #[test]
+    fn test_generate_single_node() {
+        let flow = Flow {
+            nodes: vec![
+                node("half", "function-definition", None, json!({"function_name": "half", "arguments": [{"name": "x", "type": "f64"}], "return_type": "f64"})),
+                node("body", "legacy-code", Some("half"), json!({"code": "x / 2.0"})),
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("show", "debug", Some("main"), json!({"variable": "total", "label": "Total"})),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(generate_node(&flow, "show").unwrap(), "println!(\"Total: {:?}\", total);");
+        assert_eq!(generate_node(&flow, "half").unwrap(), "async fn half(x: f64) -> f64 {\n    x / 2.0\n\n}\n\n");
+        assert_eq!(generate_node(&flow, "missing").unwrap_err().to_string(), "Node 'missing' not found");
+    }
+
+    #[test]
+    fn test_trace_instrumentation_wraps_nodes() {
+        let flow = Flow {
+            nodes: vec![
+                node("half", "function-definition", None, json!({"function_name": "half", "arguments": [{"name": "x", "type": "f64"}], "return_type": "f64"})),
+                node("body", "legacy-code", Some("half"), json!({"code": "x / 2.0"})),
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("decl", "let-variable", Some("main"), json!({"variable_name": "count", "value": "1"})),
+                node("show", "debug", Some("main"), json!({"variable": "count"})),
+            ],
+            connections: vec![connection("decl", "show", None)],
+            ..Default::default()
+        };
+
+        let code = generate_instrumented(&flow, Instrumentation::Trace { values: false }).unwrap();
+        assert!(code.contains("mod flust_trace {"));
+        // The tail expression keeps its value; its span closes when dropped
+        assert!(code.contains("async fn half(x: f64) -> f64 {\n    let __flust_span = crate::flust_trace::enter(\"body\");\n    x / 2.0\n\n}"));
+        assert!(code.contains("    let count = 1;\n    __flust_span.exit(&[]);\n    let __flust_span = crate::flust_trace::enter(\"show\");\n"));
+
+        let code = generate_instrumented(&flow, Instrumentation::Trace { values: true }).unwrap();
+        assert!(code.contains("__flust_span.exit(&[(\"count\", (&crate::flust_trace::Value(&count)).show())]);"));
+        assert!(!generate_rust(&flow).unwrap().contains("flust_trace"));
+    }
+
+    #[test]
+    fn test_profile_instrumentation_times_nodes_and_functions() {
+        let flow = Flow {
+            nodes: vec![
+                node("half", "function-definition", None, json!({"function_name": "half", "arguments": [{"name": "x", "type": "f64"}], "return_type": "f64"})),
+                node("body", "legacy-code", Some("half"), json!({"code": "x / 2.0"})),
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("decl", "let-variable", Some("main"), json!({"variable_name": "count", "value": "1"})),
+                node("show", "debug", Some("main"), json!({"variable": "count"})),
+            ],
+            connections: vec![connection("decl", "show", None)],
+            ..Default::default()
+        };
+
+        let code = generate_instrumented(&flow, Instrumentation::Profile).unwrap();
+        assert!(code.contains("mod flust_profile {"));
+        assert!(code.contains("async fn half(x: f64) -> f64 {\n    let __flust_function = crate::flust_profile::enter_function(\"half\");\n    let __flust_span = crate::flust_profile::enter(\"body\");\n    x / 2.0\n"));
+        assert!(code.contains("async fn main() {\n    let __flust_report = crate::flust_profile::Report;\n    let __flust_function = crate::flust_profile::enter_function(\"main\");\n"));
+        assert!(code.contains("    let count = 1;\n    drop(__flust_span);\n"));
+    }
+
+    #[test]
+    fn test_debug_instrumentation_pauses_with_scope() {
+        let flow = Flow {
+            nodes: vec![
+                node("half", "function-definition", None, json!({"function_name": "half", "arguments": [{"name": "x", "type": "f64"}], "return_type": "f64"})),
+                node("body", "legacy-code", Some("half"), json!({"code": "x / 2.0"})),
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("decl", "let-variable", Some("main"), json!({"variable_name": "count", "value": "1"})),
+                node("show", "debug", Some("main"), json!({"variable": "count"})),
+            ],
+            connections: vec![connection("decl", "show", None)],
+            ..Default::default()
+        };
+
+        let code = generate_instrumented(&flow, Instrumentation::Debug).unwrap();
+        assert!(code.contains("mod flust_debug {"));
+        assert!(code.contains("crate::flust_debug::record(\"half\", &[(\"x\", (&crate::flust_debug::Value(&x)).show())]); }\n    crate::flust_debug::pause(\"body\", \"half\", &[\"x\"]);\n    x / 2.0\n"));
+        assert!(code.contains("    crate::flust_debug::pause(\"decl\", \"main\", &[]);\n    let count = 1;\n"));
+        assert!(code.contains("crate::flust_debug::record(\"main\", &[(\"count\", (&crate::flust_debug::Value(&count)).show())]); }\n    crate::flust_debug::pause(\"show\", \"main\", &[\"count\"]);\n"));
+
+        // A deferred `let` is neither recorded nor shown until it is assigned
+        let deferred = Flow {
+            nodes: vec![
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("decl", "let-variable", Some("main"), json!({"variable_name": "total", "variable_type": "i32"})),
+                node("init", "assign-variable", Some("main"), json!({"variable_name": "total", "value": "2"})),
+                node("show", "debug", Some("main"), json!({"variable": "total"})),
+            ],
+            connections: vec![connection("decl", "init", None), connection("init", "show", None)],
+            ..Default::default()
+        };
+        let code = generate_instrumented(&deferred, Instrumentation::Debug).unwrap();
+        assert!(code.contains("    let total: i32;\n    crate::flust_debug::pause(\"init\", \"main\", &[]);\n    total = 2;\n"));
+        assert!(code.contains("crate::flust_debug::pause(\"show\", \"main\", &[\"total\"]);"));
+    }
+
+    #[test]
+    fn test_library_exports_marked_functions() {
+        let mut flow = Flow {
+            nodes: vec![
+                node("reading", "struct-definition", None, json!({"type_name": "Reading", "fields": "value: f64"})),
+                node("scale", "function-definition", None, json!({
+                    "function_name": "scale",
+                    "arguments": [{"name": "r", "type": "Reading"}],
+                    "return_type": "f64",
+                    "export": true
+                })),
+                node("body", "legacy-code", Some("scale"), json!({"code": "factor().await * r.value"})),
+                node("factor", "function-definition", None, json!({"function_name": "factor", "return_type": "f64"})),
+            ],
+            ..Default::default()
+        };
+
+        let code = generate_library(&flow).unwrap();
+
+        assert!(code.starts_with("pub struct Reading {\n    pub value: f64,\n}\n"));
+        assert!(code.contains("pub async fn scale(r: Reading) -> f64 {"));
+        assert!(code.contains("\nasync fn factor() -> f64 {"));
+        assert!(!code.contains("fn main"));
+
+        flow.nodes.push(node("main", "function-definition", None, json!({"function_name": "main"})));
+        assert_eq!(generate_library(&flow).unwrap_err().to_string(), "Library flows cannot define main");
+    }
 }
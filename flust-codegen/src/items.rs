@@ -0,0 +1,181 @@
+use flust_core::ir::Flow;
+use std::collections::BTreeSet;
+
+/// Module-level items hoisted above the functions of the generated file
+/// `use` declarations come from the flow's imports and from `module-code` blocks
+#[derive(Debug, Default)]
+pub struct ModuleItems {
+    uses: BTreeSet<String>,
+    items: Vec<String>,
+}
+
+impl ModuleItems {
+    pub fn from_flow(flow: &Flow) -> Self {
+        let mut module = ModuleItems::default();
+        for import in &flow.imports {
+            module.add_use(import);
+        }
+        for node in flow.nodes.iter().filter(|n| n.plugin_type == "module-code") {
+            let code = node.properties.get("code").and_then(|v| v.as_str()).unwrap_or("");
+            for item in split_items(code) {
+                if is_use(&item) {
+                    module.add_use(&item);
+                } else if !module.items.iter().any(|existing| same_item(existing, &item)) {
+                    module.items.push(item);
+                }
+            }
+        }
+        module
+    }
+
+    /// Accepts `std::fmt`, `use std::fmt;` or `pub use std::fmt::Display;`
+    fn add_use(&mut self, path: &str) {
+        let path = path.trim().trim_end_matches(';').trim();
+        if path.is_empty() {
+            return;
+        }
+        let declaration = if is_use(path) { format!("{};", path) } else { format!("use {};", path) };
+        self.uses.insert(declaration);
+    }
+
+    /// Sorted `use` declarations
+    pub fn render_uses(&self) -> String {
+        let mut code = String::new();
+        for declaration in &self.uses {
+            code.push_str(declaration);
+            code.push('\n');
+        }
+        if !self.uses.is_empty() {
+            code.push('\n');
+        }
+        code
+    }
+
+    /// Remaining items (consts, statics, impls, traits...) in flow order
+    pub fn render_items(&self) -> String {
+        let mut code = String::new();
+        for item in &self.items {
+            code.push_str(item);
+            code.push_str("\n\n");
+        }
+        code
+    }
+}
+
+fn is_use(item: &str) -> bool {
+    let item = item.trim_start();
+    item.starts_with("use ") || item.starts_with("pub use ") || item.starts_with("pub(crate) use ")
+}
+
+/// Items differing only in whitespace are the same item
+fn same_item(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
+
+/// Split source text into top-level items
+/// An item ends at a `;` or closing `}` outside of any bracket; attributes and comments stay with the next item
+fn split_items(code: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let mut chars = code.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        match c {
+            '"' => {
+                // Skip string literals so braces inside them do not count
+                while let Some(s) = chars.next() {
+                    current.push(s);
+                    if s == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            current.push(escaped);
+                        }
+                    } else if s == '"' {
+                        break;
+                    }
+                }
+            }
+            // Char literals like '{' (lifetimes have no closing quote)
+            '\'' => {
+                let mut lookahead = chars.clone();
+                let literal_len = match (lookahead.next(), lookahead.next()) {
+                    (Some('\\'), _) => lookahead.position(|s| s == '\'').map(|n| n + 3),
+                    (Some(_), Some('\'')) => Some(2),
+                    _ => None,
+                };
+                for _ in 0..literal_len.unwrap_or(0) {
+                    current.extend(chars.next());
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for s in chars.by_ref() {
+                    current.push(s);
+                    if s == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' => depth -= 1,
+            '}' => {
+                depth -= 1;
+                // `struct S { .. };` style trailing semicolons belong to the same item
+                if depth == 0 && chars.peek() != Some(&';') {
+                    push_item(&mut items, &mut current);
+                }
+            }
+            ';' if depth == 0 => push_item(&mut items, &mut current),
+            _ => {}
+        }
+    }
+    push_item(&mut items, &mut current);
+    items
+}
+
+fn push_item(items: &mut Vec<String>, current: &mut String) {
+    let item = current.trim();
+    // Leftover comments without an item are dropped
+    let is_code = item.lines().any(|line| !line.trim().is_empty() && !line.trim().starts_with("//"));
+    if is_code {
+        items.push(item.to_string());
+    }
+    current.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flust_core::ir::Node;
+    use std::collections::HashMap;
+
+    fn module_code(id: &str, code: &str) -> Node {
+        let mut properties = HashMap::new();
+        properties.insert("code".to_string(), serde_json::json!(code));
+        Node {
+            id: id.to_string(),
+            plugin_type: "module-code".to_string(),
+            label: None,
+            properties,
+            parent_id: None,
+        }
+    }
+
+    #[test]
+    fn test_items_are_hoisted_and_deduplicated() {
+        let flow = Flow {
+            nodes: vec![
+                module_code("a", "use std::fmt;\nconst LIMIT: usize = 3;\n\n// Greeting shown at startup\nimpl fmt::Display for Unit {\n    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, \"{{}}\") }\n}"),
+                module_code("b", "use std::collections::HashMap;\nconst  LIMIT: usize = 3;"),
+            ],
+            connections: vec![],
+            imports: vec!["std::collections::HashMap".to_string(), "use std::fmt;".to_string()],
+            ..Default::default()
+        };
+
+        let items = ModuleItems::from_flow(&flow);
+        let code = items.render_uses() + &items.render_items();
+
+        assert_eq!(code, "use std::collections::HashMap;\nuse std::fmt;\n\nconst LIMIT: usize = 3;\n\n// Greeting shown at startup\nimpl fmt::Display for Unit {\n    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, \"{{}}\") }\n}\n\n");
+    }
+}
@@ -1,2 +1,13 @@
+pub mod assertions;
+pub mod channels;
+pub mod debug;
+pub mod expressions;
+#[cfg(test)]
+mod fixtures;
 pub mod generator;
+pub mod items;
+pub mod profile;
 pub mod template_engine;
+pub mod test_cases;
+pub mod trace;
+pub mod types;
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Environment variable naming the file a profiled program writes its samples to
+pub const OUTPUT_VAR: &str = "FLUST_PROFILE_OUT";
+
+/// Runtime support of profiled programs: nodes and functions hold timer spans on a per-thread call stack
+/// The samples are written when main ends, to `FLUST_PROFILE_OUT` or as a JSON line on stderr
+/// Tasks resumed on another worker thread may attribute their stacks to the wrong caller
+const RUNTIME: &str = r#"#[allow(dead_code)]
+mod flust_profile {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Instant;
+
+    #[derive(Default)]
+    struct Samples {
+        nodes: HashMap<&'static str, Vec<u64>>,
+        functions: HashMap<&'static str, Vec<u64>>,
+        stacks: HashMap<String, u64>,
+    }
+
+    fn samples() -> &'static Mutex<Samples> {
+        static SAMPLES: OnceLock<Mutex<Samples>> = OnceLock::new();
+        SAMPLES.get_or_init(Default::default)
+    }
+
+    thread_local! {
+        static STACK: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Timer of one node or function call, recorded when dropped
+    pub struct Span {
+        name: &'static str,
+        function: bool,
+        stack: String,
+        start: Instant,
+    }
+
+    fn open(name: &'static str, function: bool) -> Span {
+        let stack = STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            stack.push(name);
+            stack.join(";")
+        });
+        Span { name, function, stack, start: Instant::now() }
+    }
+
+    pub fn enter(node: &'static str) -> Span {
+        open(node, false)
+    }
+
+    pub fn enter_function(name: &'static str) -> Span {
+        open(name, true)
+    }
+
+    impl Drop for Span {
+        fn drop(&mut self) {
+            let elapsed = self.start.elapsed().as_nanos() as u64;
+            STACK.with(|stack| {
+                let mut stack = stack.borrow_mut();
+                if let Some(index) = stack.iter().rposition(|name| *name == self.name) {
+                    stack.remove(index);
+                }
+            });
+            let mut samples = samples().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let durations = if self.function { &mut samples.functions } else { &mut samples.nodes };
+            durations.entry(self.name).or_default().push(elapsed);
+            *samples.stacks.entry(std::mem::take(&mut self.stack)).or_default() += elapsed;
+        }
+    }
+
+    fn quote(text: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in text.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    fn durations(by_name: &HashMap<&'static str, Vec<u64>>) -> String {
+        let entries: Vec<String> = by_name.iter()
+            .map(|(name, durations)| {
+                let durations: Vec<String> = durations.iter().map(u64::to_string).collect();
+                format!("{}:[{}]", quote(name), durations.join(","))
+            })
+            .collect();
+        format!("{{{}}}", entries.join(","))
+    }
+
+    /// Held by main; writes the samples once it is dropped
+    pub struct Report;
+
+    impl Drop for Report {
+        fn drop(&mut self) {
+            let samples = samples().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let stacks: Vec<String> = samples.stacks.iter().map(|(stack, total)| format!("{}:{}", quote(stack), total)).collect();
+            let json = format!(
+                "{{\"flust_profile\":{{\"nodes\":{},\"functions\":{},\"stacks\":{{{}}}}}}}\n",
+                durations(&samples.nodes),
+                durations(&samples.functions),
+                stacks.join(","),
+            );
+            let _ = match std::env::var("FLUST_PROFILE_OUT") {
+                Ok(path) => std::fs::write(path, json),
+                Err(_) => std::io::stderr().write_all(json.as_bytes()),
+            };
+        }
+    }
+}
+
+"#;
+
+pub fn runtime() -> &'static str {
+    RUNTIME
+}
+
+/// Statement starting the timer of a node
+pub fn enter(node_id: &str) -> String {
+    format!("let __flust_span = crate::flust_profile::enter({:?});", node_id)
+}
+
+/// Statement stopping the timer of the last node entered
+pub fn exit() -> String {
+    "drop(__flust_span);".to_string()
+}
+
+/// Statement starting the timer of a whole function call, stopped when the function returns
+pub fn enter_function(name: &str) -> String {
+    format!("let __flust_function = crate::flust_profile::enter_function({:?});", name)
+}
+
+/// Statement in main that writes the samples when main ends
+pub fn report_guard() -> String {
+    "let __flust_report = crate::flust_profile::Report;".to_string()
+}
+
+/// Durations in nanoseconds recorded by a profiled program
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Samples {
+    pub nodes: BTreeMap<String, Vec<u64>>,
+    pub functions: BTreeMap<String, Vec<u64>>,
+    /// Inclusive time per call stack, frames joined with `;`
+    pub stacks: BTreeMap<String, u64>,
+}
+
+#[derive(Deserialize)]
+struct Line {
+    flust_profile: Samples,
+}
+
+/// Samples in a profile file, or in the stderr of a program run without `FLUST_PROFILE_OUT`
+pub fn parse_samples(output: &str) -> Option<Samples> {
+    output.lines()
+        .filter(|line| line.starts_with("{\"flust_profile\""))
+        .find_map(|line| serde_json::from_str::<Line>(line).ok())
+        .map(|line| line.flust_profile)
+}
+
+/// Aggregated durations of one node or function
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub name: String,
+    pub calls: usize,
+    pub total_ns: u64,
+    pub mean_ns: u64,
+    pub p99_ns: u64,
+}
+
+impl Stats {
+    fn of(name: &str, durations: &[u64]) -> Self {
+        let mut sorted = durations.to_vec();
+        sorted.sort_unstable();
+        let total_ns: u64 = sorted.iter().sum();
+        let calls = sorted.len();
+        // Nearest-rank percentile
+        let p99_ns = sorted.get((calls * 99).div_ceil(100).saturating_sub(1)).copied().unwrap_or_default();
+        Stats { name: name.to_string(), calls, total_ns, mean_ns: total_ns / calls.max(1) as u64, p99_ns }
+    }
+}
+
+/// Per-node and per-function statistics, slowest first, with the folded stacks of the run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub nodes: Vec<Stats>,
+    pub functions: Vec<Stats>,
+    /// Flamegraph input: one `frame;frame;frame self_ns` line per stack
+    pub folded: String,
+}
+
+pub fn report(samples: &Samples) -> Report {
+    let stats = |by_name: &BTreeMap<String, Vec<u64>>| {
+        let mut stats: Vec<Stats> = by_name.iter().map(|(name, durations)| Stats::of(name, durations)).collect();
+        stats.sort_by(|a, b| b.total_ns.cmp(&a.total_ns).then_with(|| a.name.cmp(&b.name)));
+        stats
+    };
+    Report { nodes: stats(&samples.nodes), functions: stats(&samples.functions), folded: folded(&samples.stacks) }
+}
+
+/// Self time of each stack: its inclusive time minus that of the stacks directly below it
+fn folded(stacks: &BTreeMap<String, u64>) -> String {
+    let mut folded = String::new();
+    for (stack, total) in stacks {
+        let children: u64 = stacks.iter()
+            .filter(|(other, _)| other.strip_prefix(stack.as_str()).and_then(|rest| rest.strip_prefix(';')).is_some_and(|rest| !rest.contains(';')))
+            .map(|(_, child)| child)
+            .sum();
+        folded.push_str(&format!("{} {}\n", stack, total.saturating_sub(children)));
+    }
+    folded
+}
+
+fn duration(ns: u64) -> String {
+    match ns {
+        0..1_000 => format!("{}ns", ns),
+        1_000..1_000_000 => format!("{:.1}µs", ns as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.2}ms", ns as f64 / 1e6),
+        _ => format!("{:.2}s", ns as f64 / 1e9),
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (title, stats) in [("node", &self.nodes), ("function", &self.functions)] {
+            if stats.is_empty() {
+                continue;
+            }
+            let width = stats.iter().map(|s| s.name.chars().count()).max().unwrap_or_default().max(title.len());
+            writeln!(f, "{:<width$}  {:>8}  {:>10}  {:>10}  {:>10}", title, "calls", "total", "mean", "p99")?;
+            for s in stats {
+                writeln!(f, "{:<width$}  {:>8}  {:>10}  {:>10}  {:>10}", s.name, s.calls, duration(s.total_ns), duration(s.mean_ns), duration(s.p99_ns))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_aggregates_samples() {
+        let stderr = "hello\n{\"flust_profile\":{\"nodes\":{\"call\":[3000,5000],\"body\":[1000,1500]},\
+            \"functions\":{\"main\":[9000],\"double\":[1200,1800]},\
+            \"stacks\":{\"main\":9000,\"main;call\":8000,\"main;call;double\":3000,\"main;call;double;body\":2500}}}\n";
+        let samples = parse_samples(stderr).unwrap();
+        let report = report(&samples);
+
+        assert_eq!(report.nodes[0], Stats { name: "call".to_string(), calls: 2, total_ns: 8000, mean_ns: 4000, p99_ns: 5000 });
+        assert_eq!(report.functions.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["main", "double"]);
+        assert_eq!(report.folded, "main 1000\nmain;call 5000\nmain;call;double 500\nmain;call;double;body 2500\n");
+        assert_eq!(
+            report.to_string().lines().take(2).collect::<Vec<_>>(),
+            vec!["node     calls       total        mean         p99", "call         2       8.0µs       4.0µs       5.0µs"]
+        );
+        let many: Vec<u64> = (1..=200).collect();
+        assert_eq!(Stats::of("loop", &many).p99_ns, 198);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::channels::ChannelRegistry;
+use anyhow::{Result, anyhow};
+use flust_core::ir::{Flow, Node};
+use flust_core::types::split_top_level;
+
+/// `test-case` node attached to the function-definition it tests
+#[derive(Debug, Clone)]
+pub struct TestCase<'f> {
+    /// Name of the generated test function
+    pub name: String,
+    pub node: &'f Node,
+    /// Owning function-definition
+    pub function: &'f Node,
+}
+
+/// Expected result of calling the function under test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Returns,
+    Panics,
+    Errors,
+}
+
+/// Test cases of a flow, in node order
+/// Each `test-case` node must sit inside a function-definition other than main
+pub fn test_cases(flow: &Flow) -> Result<Vec<TestCase<'_>>> {
+    let mut cases: Vec<TestCase> = Vec::new();
+    for node in flow.nodes.iter().filter(|n| n.plugin_type == "test-case") {
+        let function = node.parent_id.as_ref()
+            .and_then(|parent| flow.nodes.iter().find(|n| &n.id == parent))
+            .filter(|parent| parent.plugin_type == "function-definition")
+            .ok_or_else(|| anyhow!("Test case '{}' must be placed inside a function-definition", node.id))?;
+        let function_name = str_property(function, "function_name");
+        if function_name == "main" {
+            return Err(anyhow!("Test case '{}' cannot test main", node.id));
+        }
+
+        let name = match str_property(node, "test_name") {
+            "" => identifier(&format!("{}_{}", function_name, node.id)),
+            name => identifier(name),
+        };
+        if cases.iter().any(|case| case.name == name) {
+            return Err(anyhow!("Test case '{}' reuses the test name '{}'", node.id, name));
+        }
+        cases.push(TestCase { name, node, function });
+    }
+    Ok(cases)
+}
+
+/// `#[cfg(test)]` module with one test per test case, empty when the flow has none
+pub fn test_module(flow: &Flow) -> Result<String> {
+    let cases = test_cases(flow)?;
+    if cases.is_empty() {
+        return Ok(String::new());
+    }
+
+    let channels = ChannelRegistry::from_flow(flow)?;
+    let mut code = String::from("\n#[cfg(test)]\nmod tests {\n    use super::*;\n");
+    for case in &cases {
+        code.push('\n');
+        code.push_str(&test_function(case, &channels)?);
+    }
+    code.push_str("}\n");
+    Ok(code)
+}
+
+/// Flow functions are async, so their tests run on the tokio test runtime
+/// Channel ends the function takes as parameters come from channels declared in the test
+fn test_function(case: &TestCase, channels: &ChannelRegistry) -> Result<String> {
+    let fallible = case.function.properties.get("fallible").and_then(|v| v.as_bool()).unwrap_or(false);
+    let outcome = match str_property(case.node, "outcome") {
+        "" | "returns" => Outcome::Returns,
+        "panics" => Outcome::Panics,
+        "errors" if fallible => Outcome::Errors,
+        "errors" => return Err(anyhow!("Test case '{}' expects an error from a function that is not fallible", case.node.id)),
+        other => return Err(anyhow!("Test case '{}' has unknown outcome '{}'", case.node.id, other)),
+    };
+
+    let function = str_property(case.function, "function_name");
+    let mut arguments: Vec<String> = split_top_level(str_property(case.node, "arguments")).into_iter().map(String::from).collect();
+    arguments.extend(channels.call_arguments(function));
+    let call = format!("{}({}).await", function, arguments.join(", "));
+    let expected = str_property(case.node, "expected");
+    let assertion = match outcome {
+        Outcome::Returns if fallible && expected.is_empty() => format!("{}.unwrap();", call),
+        Outcome::Returns if fallible => format!("assert_eq!({}.unwrap(), {});", call, expected),
+        Outcome::Returns if expected.is_empty() => format!("{};", call),
+        Outcome::Returns => format!("assert_eq!({}, {});", call, expected),
+        Outcome::Panics => format!("let _ = {};", call),
+        Outcome::Errors => format!("assert!({}.is_err());", call),
+    };
+
+    let mut code = String::from("    #[tokio::test]\n");
+    if outcome == Outcome::Panics {
+        match str_property(case.node, "panic_message") {
+            "" => code.push_str("    #[should_panic]\n"),
+            message => code.push_str(&format!("    #[should_panic(expected = {:?})]\n", message)),
+        }
+    }
+    code.push_str(&format!("    async fn {}() {{\n", case.name));
+    for channel in channels.channels_of(function) {
+        code.push_str(&format!("        {}\n", channel.declaration()));
+    }
+    code.push_str(&format!("        {}\n    }}\n", assertion));
+    Ok(code)
+}
+
+fn str_property<'n>(node: &'n Node, name: &str) -> &'n str {
+    node.properties.get(name).and_then(|v| v.as_str()).unwrap_or("").trim()
+}
+
+/// `Doubles input!` -> `doubles_input_`
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::node;
+    use serde_json::json;
+
+    #[test]
+    fn test_cases_become_tokio_tests() {
+        let mut flow = Flow {
+            nodes: vec![
+                node("half", "function-definition", None, json!({"function_name": "half", "arguments": [{"name": "x", "type": "f64"}], "return_type": "f64"})),
+                node("t1", "test-case", Some("half"), json!({"test_name": "Halves ten", "arguments": "10.0", "expected": "5.0"})),
+                node("t2", "test-case", Some("half"), json!({"arguments": "f64::NAN", "outcome": "panics", "panic_message": "not a number"})),
+            ],
+            ..Default::default()
+        };
+
+        let code = test_module(&flow).unwrap();
+
+        assert!(code.starts_with("\n#[cfg(test)]\nmod tests {\n    use super::*;\n"));
+        assert!(code.contains("    #[tokio::test]\n    async fn halves_ten() {\n        assert_eq!(half(10.0).await, 5.0);\n    }\n"));
+        assert!(code.contains("    #[tokio::test]\n    #[should_panic(expected = \"not a number\")]\n    async fn half_t2() {\n        let _ = half(f64::NAN).await;\n    }\n"));
+
+        flow.nodes.push(node("log", "channel", None, json!({"channel_name": "log", "message_type": "f64"})));
+        flow.nodes.push(node("send", "channel-send", Some("half"), json!({"channel": "log", "value": "x"})));
+        let code = test_module(&flow).unwrap();
+        assert!(code.contains("    async fn halves_ten() {\n        let (log_tx, mut log_rx) = tokio::sync::mpsc::channel::<f64>(32);\n        assert_eq!(half(10.0, log_tx.clone()).await, 5.0);\n    }\n"), "{}", code);
+
+        flow.nodes.push(node("t3", "test-case", Some("half"), json!({"outcome": "errors"})));
+        assert!(test_module(&flow).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Environment variable naming the local `host:port` a traced program sends its events to
+pub const ADDRESS_VAR: &str = "FLUST_TRACE_ADDR";
+
+/// Runtime support of traced programs: each node holds a span from entry to exit
+/// Events are JSON lines on the socket in `FLUST_TRACE_ADDR`, or on stderr when it is not set
+const RUNTIME: &str = r#"#[allow(dead_code)]
+mod flust_trace {
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn sink() -> &'static Mutex<Option<TcpStream>> {
+        static SINK: OnceLock<Mutex<Option<TcpStream>>> = OnceLock::new();
+        SINK.get_or_init(|| Mutex::new(std::env::var("FLUST_TRACE_ADDR").ok().and_then(|addr| TcpStream::connect(addr).ok())))
+    }
+
+    fn quote(text: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in text.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    fn emit(event: &str, node: &str, values: &[(&str, String)]) {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros()).unwrap_or_default();
+        let mut line = format!("{{\"flust_trace\":\"{}\",\"node\":{},\"ts_us\":{}", event, quote(node), ts);
+        if !values.is_empty() {
+            let values: Vec<String> = values.iter().map(|(name, value)| format!("{}:{}", quote(name), quote(value))).collect();
+            line.push_str(&format!(",\"values\":{{{}}}", values.join(",")));
+        }
+        line.push_str("}\n");
+        let mut sink = sink().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = match sink.as_mut() {
+            Some(stream) => stream.write_all(line.as_bytes()),
+            None => std::io::stderr().write_all(line.as_bytes()),
+        };
+    }
+
+    /// Variable reported on exit: its `Debug` text when it has one, otherwise its type name
+    pub struct Value<'v, T>(pub &'v T);
+
+    pub trait Debugged {
+        fn show(&self) -> String;
+    }
+
+    impl<T: std::fmt::Debug> Debugged for Value<'_, T> {
+        fn show(&self) -> String {
+            format!("{:?}", self.0)
+        }
+    }
+
+    pub trait Opaque {
+        fn show(&self) -> String;
+    }
+
+    // Only picked when `Debugged` does not apply, as it needs one more autoref
+    impl<T> Opaque for &Value<'_, T> {
+        fn show(&self) -> String {
+            format!("<{}>", std::any::type_name::<T>())
+        }
+    }
+
+    /// Open from a node's entry until `exit`, or until dropped when the node returns early
+    pub struct Span {
+        node: &'static str,
+        open: bool,
+    }
+
+    pub fn enter(node: &'static str) -> Span {
+        emit("enter", node, &[]);
+        Span { node, open: true }
+    }
+
+    impl Span {
+        pub fn exit(mut self, values: &[(&str, String)]) {
+            self.open = false;
+            emit("exit", self.node, values);
+        }
+    }
+
+    impl Drop for Span {
+        fn drop(&mut self) {
+            if self.open {
+                emit("exit", self.node, &[]);
+            }
+        }
+    }
+}
+
+"#;
+
+pub fn runtime() -> &'static str {
+    RUNTIME
+}
+
+/// Statement opening the span of a node
+pub fn enter(node_id: &str) -> String {
+    format!("let __flust_span = crate::flust_trace::enter({:?});", node_id)
+}
+
+/// Statement closing the span of a node with the values of `variables`
+pub fn exit(variables: &[String]) -> String {
+    if variables.is_empty() {
+        return "__flust_span.exit(&[]);".to_string();
+    }
+    format!(
+        "{{ #[allow(unused_imports)] use crate::flust_trace::{{Debugged as _, Opaque as _}}; __flust_span.exit(&[{}]); }}",
+        shown_values("flust_trace", variables)
+    )
+}
+
+/// `(name, text)` pairs of `variables`, shown through the `Value` wrapper of a runtime module
+pub(crate) fn shown_values(module: &str, variables: &[String]) -> String {
+    let values: Vec<String> = variables.iter()
+        .map(|v| format!("({:?}, (&crate::{}::Value(&{})).show())", v, module, v))
+        .collect();
+    values.join(", ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Enter,
+    Exit,
+}
+
+/// Entry or exit of a node reported by a traced program
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TraceEvent {
+    #[serde(rename = "flust_trace")]
+    pub kind: EventKind,
+    pub node: String,
+    pub ts_us: u64,
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+/// Trace events among the lines of `output`; other lines are ignored
+pub fn parse_events(output: &str) -> Vec<TraceEvent> {
+    output.lines()
+        .filter(|line| line.starts_with("{\"flust_trace\""))
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// `output` without its trace event lines
+pub fn strip_events(output: &str) -> String {
+    output.split_inclusive('\n')
+        .filter(|line| !line.starts_with("{\"flust_trace\""))
+        .collect()
+}
+
+/// One execution of a node; times are relative to the first event of the run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeSpan {
+    pub node_id: String,
+    pub start_us: u64,
+    /// `None` when the program ended inside the node
+    pub duration_us: Option<u64>,
+    pub values: BTreeMap<String, String>,
+}
+
+/// Executions of every node in order of entry, pairing each exit with the latest open entry of its node
+pub fn timeline(events: &[TraceEvent]) -> Vec<NodeSpan> {
+    let origin = events.iter().map(|e| e.ts_us).min().unwrap_or_default();
+    let mut spans: Vec<NodeSpan> = Vec::new();
+    let mut open: HashMap<&str, Vec<usize>> = HashMap::new();
+    for event in events {
+        match event.kind {
+            EventKind::Enter => {
+                open.entry(&event.node).or_default().push(spans.len());
+                spans.push(NodeSpan {
+                    node_id: event.node.clone(),
+                    start_us: event.ts_us.saturating_sub(origin),
+                    duration_us: None,
+                    values: BTreeMap::new(),
+                });
+            }
+            EventKind::Exit => {
+                let Some(index) = open.get_mut(event.node.as_str()).and_then(Vec::pop) else { continue };
+                let span = &mut spans[index];
+                // Clocks of different threads may disagree by a few microseconds
+                span.duration_us = Some(event.ts_us.saturating_sub(origin).saturating_sub(span.start_us));
+                span.values = event.values.clone();
+            }
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeline_pairs_nested_events() {
+        let stderr = "{\"flust_trace\":\"enter\",\"node\":\"call\",\"ts_us\":1000}\n\
+            {\"flust_trace\":\"enter\",\"node\":\"body\",\"ts_us\":1010}\n\
+            warning: not a trace line\n\
+            {\"flust_trace\":\"exit\",\"node\":\"body\",\"ts_us\":1030}\n\
+            {\"flust_trace\":\"exit\",\"node\":\"call\",\"ts_us\":1050,\"values\":{\"d\":\"42\"}}\n\
+            {\"flust_trace\":\"enter\",\"node\":\"fail\",\"ts_us\":1060}\n";
+
+        let events = parse_events(stderr);
+        assert_eq!(events.len(), 5);
+        assert_eq!(strip_events(stderr), "warning: not a trace line\n");
+        let spans = timeline(&events);
+        let summary: Vec<(&str, u64, Option<u64>)> = spans.iter().map(|s| (s.node_id.as_str(), s.start_us, s.duration_us)).collect();
+        assert_eq!(summary, vec![("call", 0, Some(50)), ("body", 10, Some(20)), ("fail", 60, None)]);
+        assert_eq!(spans[0].values["d"], "42");
+        let skewed = parse_events("{\"flust_trace\":\"enter\",\"node\":\"a\",\"ts_us\":20}\n{\"flust_trace\":\"exit\",\"node\":\"a\",\"ts_us\":10}\n");
+        assert_eq!(timeline(&skewed)[0].duration_us, Some(0));
+        assert_eq!(
+            exit(&["d".to_string()]),
+            "{ #[allow(unused_imports)] use crate::flust_trace::{Debugged as _, Opaque as _}; __flust_span.exit(&[(\"d\", (&crate::flust_trace::Value(&d)).show())]); }"
+        );
+    }
+}
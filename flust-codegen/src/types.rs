@@ -0,0 +1,83 @@
+use flust_core::ir::Node;
+use flust_core::types::{Field, TypeDefinition, TypeKind, TypeRegistry, VariantShape, split_top_level};
+use std::collections::HashSet;
+
+/// Top-level `struct` / `enum` item of a user-defined type
+/// Public structs also expose their fields so other modules can construct them
+pub fn type_item(definition: &TypeDefinition, public: bool) -> String {
+    let visibility = if public { "pub " } else { "" };
+    let mut item = String::new();
+    if !definition.derives.is_empty() {
+        item.push_str(&format!("#[derive({})]\n", definition.derives.join(", ")));
+    }
+    match &definition.kind {
+        TypeKind::Struct(fields) if fields.is_empty() => {
+            item.push_str(&format!("{}struct {};\n", visibility, definition.name));
+        }
+        TypeKind::Struct(fields) => {
+            item.push_str(&format!("{}struct {} {{\n", visibility, definition.name));
+            for field in fields {
+                item.push_str(&format!("    {}{}: {},\n", visibility, field.name, field.type_));
+            }
+            item.push_str("}\n");
+        }
+        TypeKind::Enum(variants) => {
+            item.push_str(&format!("{}enum {} {{\n", visibility, definition.name));
+            for variant in variants {
+                let payload = match &variant.shape {
+                    VariantShape::Unit => String::new(),
+                    VariantShape::Tuple(types) => format!("({})", types.join(", ")),
+                    VariantShape::Struct(fields) => format!(" {{ {} }}", field_list(fields)),
+                };
+                item.push_str(&format!("    {}{},\n", variant.name, payload));
+            }
+            item.push_str("}\n");
+        }
+    }
+    item
+}
+
+/// Every user-defined type of the flow, in node order
+pub fn type_items(registry: &TypeRegistry, exports: &HashSet<&str>) -> String {
+    registry.types.iter()
+        .map(|definition| format!("{}\n", type_item(definition, exports.contains(definition.name.as_str()))))
+        .collect()
+}
+
+/// Constructor expression of a `construct` node
+/// `wired` holds the `(port, expr)` pairs of connected ports: field names, or positions for tuple variants
+pub fn construct(node: &Node, registry: &TypeRegistry, mut wired: Vec<(String, String)>) -> String {
+    let property = |name: &str| node.properties.get(name).and_then(|v| v.as_str()).unwrap_or("").trim();
+    let type_name = property("type_name");
+    let variant = property("variant");
+    let mut values: Vec<String> = split_top_level(property("values")).into_iter().map(String::from).collect();
+
+    let shape = registry.get(type_name).and_then(|definition| definition.variant(variant)).map(|v| &v.shape);
+    let path = if variant.is_empty() { type_name.to_string() } else { format!("{}::{}", type_name, variant) };
+    let positional = match shape {
+        Some(VariantShape::Tuple(_)) => true,
+        Some(_) => false,
+        // Types defined outside the flow are guessed from the initialisers
+        None => !variant.is_empty() && values.iter().all(|v| !v.contains(':'))
+            && wired.iter().all(|(port, _)| port.parse::<usize>().is_ok()),
+    };
+
+    if positional {
+        wired.sort_by_key(|(port, _)| port.parse::<usize>().unwrap_or(usize::MAX));
+        values.extend(wired.into_iter().map(|(_, value)| value));
+        return if values.is_empty() { path } else { format!("{}({})", path, values.join(", ")) };
+    }
+    values.extend(wired.into_iter().map(|(field, value)| format!("{}: {}", field, value)));
+    match shape {
+        Some(VariantShape::Unit) => path,
+        _ if values.is_empty() => format!("{} {{}}", path),
+        _ => format!("{} {{ {} }}", path, values.join(", ")),
+    }
+}
+
+fn field_list(fields: &[Field]) -> String {
+    fields.iter()
+        .map(|f| format!("{}: {}", f.name, f.type_))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
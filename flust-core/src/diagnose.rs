@@ -0,0 +1,47 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::modules::{FlowModule, ModuleGraph};
+use crate::scope::ScopeAnalysis;
+use crate::type_checker::TypeChecker;
+
+/// Diagnostic with the analysis that reported it (`scope`, `types`, ...)
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub diagnostic: Diagnostic,
+}
+
+/// Diagnostics of one flow file of the import graph
+pub struct ModuleDiagnostics<'g> {
+    pub module: &'g FlowModule,
+    pub findings: Vec<Finding>,
+}
+
+impl ModuleDiagnostics<'_> {
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|f| f.diagnostic.is_error())
+    }
+}
+
+/// Scope analysis and type checking of every flow file, dependencies first
+/// A flow the analysis cannot walk (e.g. a cycle) gets a file-level error instead of failing the rest
+pub fn diagnose(graph: &ModuleGraph) -> Vec<ModuleDiagnostics<'_>> {
+    let mut results = Vec::new();
+    for module in &graph.modules {
+        let mut findings: Vec<Finding> = match ScopeAnalysis::analyze(&module.flow) {
+            Ok(analysis) => analysis.diagnostics.into_iter()
+                .map(|diagnostic| Finding { rule: "scope", diagnostic })
+                .collect(),
+            Err(error) => vec![Finding {
+                rule: "scope",
+                diagnostic: Diagnostic { severity: Severity::Error, node_id: None, message: error.to_string() },
+            }],
+        };
+        for diagnostic in TypeChecker::check_with_imports(&module.flow, graph.imported_types(&module.name)) {
+            if !findings.iter().any(|f| f.diagnostic == diagnostic) {
+                findings.push(Finding { rule: "types", diagnostic });
+            }
+        }
+        results.push(ModuleDiagnostics { module, findings });
+    }
+    results
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Diagnostic severity
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Problem found while analysing a flow, attributed to the node that caused it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub node_id: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(node_id: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            node_id: Some(node_id.into()),
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(node_id: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            node_id: Some(node_id.into()),
+            message: message.into(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match &self.node_id {
+            Some(node_id) => write!(f, "{} [{}]: {}", severity, node_id, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::formatter;
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Editor bookkeeping (node placement, document metadata): never a change, and merged as ours
+const PRESENTATION: &[&str] = &["position", "positionAbsolute", "width", "height", "selected", "dragging", "metadata"];
+
+/// Part of a node compared on its own
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Field {
+    Label,
+    ParentId,
+    Property(String),
+    /// Any other key of the node object, carried through as is
+    Other(String),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Label => write!(f, "label"),
+            Field::ParentId => write!(f, "parent_id"),
+            Field::Property(name) => write!(f, "properties.{}", name),
+            Field::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Semantic unit of a flow; node and key order, formatting and editor layout are not part of it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Item {
+    /// The node itself (valued by its plugin type) when `field` is `None`
+    Node { id: String, field: Option<Field> },
+    /// Connection identified by its ends and the input port it feeds
+    Connection { from: String, to: String, to_port: Option<String> },
+    Import { path: String },
+    Module { path: String },
+    /// Other top-level key of the document (`version`, `metadata`, ...)
+    Document { key: String },
+}
+
+impl Item {
+    fn node(id: &str, field: Option<Field>) -> Self {
+        Item::Node { id: id.to_string(), field }
+    }
+
+    /// Whether the item belongs to node `id`, connects to it, or places a node inside it
+    pub fn touches(&self, value: &Value, id: &str) -> bool {
+        match self {
+            Item::Node { id: node, field } => {
+                node == id || (*field == Some(Field::ParentId) && value.as_str() == Some(id))
+            }
+            Item::Connection { from, to, .. } => from == id || to == id,
+            Item::Import { .. } | Item::Module { .. } | Item::Document { .. } => false,
+        }
+    }
+
+    /// Whether the item only records how the editor shows the flow
+    pub fn is_presentation(&self) -> bool {
+        match self {
+            Item::Node { field: Some(Field::Other(key)), .. } | Item::Document { key } => PRESENTATION.contains(&key.as_str()),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Node { id, field: None } => write!(f, "node '{}'", id),
+            Item::Node { id, field: Some(field) } => write!(f, "node '{}' {}", id, field),
+            Item::Connection { from, to, to_port: None } => write!(f, "connection {} → {}", from, to),
+            Item::Connection { from, to, to_port: Some(port) } => write!(f, "connection {} → {}:{}", from, to, port),
+            Item::Import { path } => write!(f, "import {}", path),
+            Item::Module { path } => write!(f, "module {}", path),
+            Item::Document { key } => write!(f, "{}", key),
+        }
+    }
+}
+
+/// Every semantic item of a flow document (IR or editor layout) with its value
+/// Null fields count as absent; keys the format does not know are items of their own
+pub fn entries(document: &Value) -> Result<BTreeMap<Item, Value>> {
+    let root = document.as_object().ok_or_else(|| anyhow!("A flow file must be an object"))?;
+    let layout = formatter::layout_of(root);
+    let mut entries = BTreeMap::new();
+    for node in objects(root, "nodes")? {
+        let id = text(node, "id").ok_or_else(|| anyhow!("Every node needs an 'id'"))?;
+        for (key, value) in node.iter().filter(|(key, value)| key.as_str() != "id" && !value.is_null()) {
+            let field = match key.as_str() {
+                key if key == layout.plugin => None,
+                key if key == layout.parent => Some(Field::ParentId),
+                key if key == layout.properties => {
+                    for (name, value) in value.as_object().into_iter().flatten() {
+                        entries.insert(Item::node(id, Some(Field::Property(name.clone()))), value.clone());
+                    }
+                    continue;
+                }
+                "label" => Some(Field::Label),
+                key => Some(Field::Other(key.to_string())),
+            };
+            entries.insert(Item::node(id, field), value.clone());
+        }
+    }
+    for edge in objects(root, layout.edges)? {
+        // The identifying fields are part of the key, the rest (type, mapping, source port) is the value
+        let (Some(from), Some(to)) = (text(edge, layout.from), text(edge, layout.to)) else {
+            return Err(anyhow!("Every entry of '{}' needs '{}' and '{}'", layout.edges, layout.from, layout.to));
+        };
+        let item = Item::Connection { from: from.to_string(), to: to.to_string(), to_port: text(edge, layout.to_port).map(String::from) };
+        let value: Map<String, Value> = edge.iter()
+            .filter(|(key, value)| ![layout.from, layout.to, layout.to_port].contains(&key.as_str()) && !value.is_null())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        entries.insert(item, Value::Object(value));
+    }
+    for (key, value) in root.iter().filter(|(_, value)| !value.is_null()) {
+        match key.as_str() {
+            "nodes" => {}
+            key if key == layout.edges => {}
+            "imports" => {
+                for path in value.as_array().into_iter().flatten().filter_map(Value::as_str) {
+                    entries.insert(Item::Import { path: path.to_string() }, Value::Bool(true));
+                }
+            }
+            "modules" => {
+                for module in objects(root, "modules")? {
+                    let path = text(module, "path").ok_or_else(|| anyhow!("Every module needs a 'path'"))?;
+                    let mut rest = module.clone();
+                    rest.remove("path");
+                    entries.insert(Item::Module { path: path.to_string() }, Value::Object(rest));
+                }
+            }
+            key => {
+                entries.insert(Item::Document { key: key.to_string() }, value.clone());
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Objects listed under `key`, none when it is missing
+pub(crate) fn objects<'a>(root: &'a Map<String, Value>, key: &str) -> Result<Vec<&'a Map<String, Value>>> {
+    match root.get(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => items.iter()
+            .map(|item| item.as_object().ok_or_else(|| anyhow!("Every entry of '{}' must be an object", key)))
+            .collect(),
+        Some(_) => Err(anyhow!("'{}' must be a list", key)),
+    }
+}
+
+pub(crate) fn text<'a>(object: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    object.get(key).and_then(Value::as_str)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    /// An input port now fed from another node; `before` and `after` are the source nodes
+    Rewired,
+}
+
+/// One semantic difference between two flows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub item: Item,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |value: &Option<Value>| value.as_ref().map(compact).unwrap_or_default();
+        match (self.kind, &self.item) {
+            (ChangeKind::Added, Item::Node { field: None, .. }) => write!(f, "+ {} ({})", self.item, value(&self.after)),
+            (ChangeKind::Removed, Item::Node { field: None, .. }) => write!(f, "- {} ({})", self.item, value(&self.before)),
+            (ChangeKind::Added, Item::Node { .. } | Item::Module { .. }) => write!(f, "+ {} = {}", self.item, value(&self.after)),
+            (ChangeKind::Added, _) => write!(f, "+ {}", self.item),
+            (ChangeKind::Removed, _) => write!(f, "- {}", self.item),
+            (ChangeKind::Changed, _) => write!(f, "~ {}: {} → {}", self.item, value(&self.before), value(&self.after)),
+            (ChangeKind::Rewired, _) => write!(f, "↷ {} (was from {})", self.item, value(&self.before)),
+        }
+    }
+}
+
+/// Strings without quotes, everything else as compact JSON
+fn compact(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Semantic differences from `old` to `new`, ordered by node and item
+/// Fields of added or removed nodes are reported with the node, not one by one
+pub fn diff(old: &Value, new: &Value) -> Result<Vec<Change>> {
+    let (mut before, mut after) = (entries(old)?, entries(new)?);
+    before.retain(|item, _| !item.is_presentation());
+    after.retain(|item, _| !item.is_presentation());
+    let change = |kind, item: &Item| Change {
+        kind,
+        item: item.clone(),
+        before: before.get(item).cloned(),
+        after: after.get(item).cloned(),
+    };
+
+    let mut changes: Vec<Change> = Vec::new();
+    for (item, value) in &before {
+        match after.get(item) {
+            None => changes.push(change(ChangeKind::Removed, item)),
+            Some(new_value) if new_value != value => changes.push(change(ChangeKind::Changed, item)),
+            _ => {}
+        }
+    }
+    changes.extend(after.keys().filter(|item| !before.contains_key(item)).map(|item| change(ChangeKind::Added, item)));
+
+    let whole_nodes: BTreeSet<String> = changes.iter()
+        .filter(|c| c.kind != ChangeKind::Changed)
+        .filter_map(|c| match &c.item {
+            Item::Node { id, field: None } => Some(id.clone()),
+            _ => None,
+        })
+        .collect();
+    changes.retain(|c| !matches!(&c.item, Item::Node { id, field: Some(_) } if whole_nodes.contains(id)));
+
+    rewire(&mut changes);
+    changes.sort_by(|a, b| a.item.cmp(&b.item));
+    Ok(changes)
+}
+
+/// A removed and an added connection feeding the same input port become one rewiring
+fn rewire(changes: &mut Vec<Change>) {
+    let mut index = 0;
+    while index < changes.len() {
+        let Change { kind: ChangeKind::Added, item: Item::Connection { from, to, to_port: Some(port) }, .. } = &changes[index] else {
+            index += 1;
+            continue;
+        };
+        let removed = changes.iter().position(|c| {
+            c.kind == ChangeKind::Removed
+                && matches!(&c.item, Item::Connection { to: t, to_port: Some(p), .. } if t == to && p == port)
+        });
+        let Some(removed) = removed else {
+            index += 1;
+            continue;
+        };
+        let Item::Connection { from: old_from, .. } = &changes[removed].item else { unreachable!() };
+        let (old_from, new_from) = (Value::String(old_from.clone()), Value::String(from.clone()));
+        let added = &mut changes[index];
+        added.kind = ChangeKind::Rewired;
+        added.before = Some(old_from);
+        added.after = Some(new_from);
+        changes.remove(removed);
+        if removed < index {
+            index -= 1;
+        }
+        index += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_ignores_order_and_layout() {
+        let old: Value = serde_json::from_str(r#"{
+            "nodes": [
+                {"id": "a", "plugin_type": "let-variable", "label": "A", "properties": {"name": "x", "value": "1"}, "parent_id": null, "position": {"x": 1, "y": 2}},
+                {"id": "b", "plugin_type": "debug", "label": null, "properties": {}, "parent_id": null},
+                {"id": "c", "plugin_type": "debug", "label": null, "properties": {}, "parent_id": null}
+            ],
+            "connections": [
+                {"from": "a", "to": "b", "variable_mapping": null},
+                {"from": "a", "to": "c", "variable_mapping": null, "to_port": "value"}
+            ]
+        }"#).unwrap();
+        let new: Value = serde_json::from_str(r#"{
+            "connections": [
+                {"from": "b", "to": "c", "variable_mapping": null, "to_port": "value"},
+                {"from": "a", "to": "b", "variable_mapping": null}
+            ],
+            "nodes": [
+                {"id": "d", "plugin_type": "sleep", "label": null, "properties": {"ms": 5}, "parent_id": null},
+                {"id": "c", "plugin_type": "debug", "label": null, "properties": {}, "parent_id": null},
+                {"id": "b", "plugin_type": "debug", "label": null, "properties": {}, "parent_id": null},
+                {"properties": {"value": "2", "name": "x"}, "parent_id": null, "label": "A", "plugin_type": "let-variable", "id": "a", "position": {"x": 9, "y": 9}}
+            ]
+        }"#).unwrap();
+
+        assert!(diff(&old, &old).unwrap().is_empty());
+        let lines: Vec<String> = diff(&old, &new).unwrap().iter().map(ToString::to_string).collect();
+        assert_eq!(lines, vec![
+            "~ node 'a' properties.value: 1 → 2",
+            "+ node 'd' (sleep)",
+            "↷ connection b → c:value (was from a)",
+        ]);
+
+        // Editor documents: moving a node is not a change, its `data` properties are
+        let editor = serde_json::json!({
+            "version": "1.0",
+            "metadata": {"name": "app", "modified": "2025-01-01"},
+            "nodes": [{"id": "a", "pluginId": "debug", "position": {"x": 1, "y": 2}, "data": {"label": "A"}}],
+            "edges": [],
+        });
+        let mut moved = editor.clone();
+        moved["metadata"]["modified"] = "2025-01-02".into();
+        moved["nodes"][0]["position"]["x"] = 5.into();
+        moved["nodes"][0]["data"]["label"] = "B".into();
+        let lines: Vec<String> = diff(&editor, &moved).unwrap().iter().map(ToString::to_string).collect();
+        assert_eq!(lines, vec!["~ node 'a' properties.label: A → B"]);
+    }
+}
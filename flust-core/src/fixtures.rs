@@ -0,0 +1,31 @@
+//! Node and connection factories shared by the unit tests.
+
+use crate::ir::{Connection, Node};
+use serde_json::{Value, json};
+
+pub(crate) fn node(id: &str, plugin_type: &str, parent: Option<&str>, properties: Value) -> Node {
+    Node {
+        id: id.to_string(),
+        plugin_type: plugin_type.to_string(),
+        label: None,
+        properties: serde_json::from_value(properties).unwrap(),
+        parent_id: parent.map(String::from),
+    }
+}
+
+/// A `function-definition` node whose id is its name.
+pub(crate) fn function(name: &str, mut properties: Value) -> Node {
+    properties["function_name"] = json!(name);
+    node(name, "function-definition", None, properties)
+}
+
+pub(crate) fn connection(from: &str, to: &str) -> Connection {
+    Connection {
+        from: from.to_string(),
+        to: to.to_string(),
+        connection_type: Default::default(),
+        variable_mapping: None,
+        from_port: None,
+        to_port: None,
+    }
+}
@@ -0,0 +1,245 @@
+use crate::ir::Flow;
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+
+/// Field names of the two flow file formats
+pub(crate) struct Layout {
+    pub(crate) edges: &'static str,
+    pub(crate) plugin: &'static str,
+    pub(crate) properties: &'static str,
+    pub(crate) parent: &'static str,
+    pub(crate) from: &'static str,
+    pub(crate) to: &'static str,
+    pub(crate) from_port: &'static str,
+    pub(crate) to_port: &'static str,
+}
+
+/// `ir::Flow` as read by the CLI and the server
+const IR: Layout = Layout {
+    edges: "connections", plugin: "plugin_type", properties: "properties", parent: "parent_id",
+    from: "from", to: "to", from_port: "from_port", to_port: "to_port",
+};
+
+/// Document saved by the editor (`version`, `metadata`, positioned `nodes` and `edges`)
+const EDITOR: Layout = Layout {
+    edges: "edges", plugin: "pluginId", properties: "data", parent: "parentNode",
+    from: "source", to: "target", from_port: "sourceHandle", to_port: "targetHandle",
+};
+
+/// Layout of a flow document, told apart by its `edges`
+pub(crate) fn layout_of(root: &Map<String, Value>) -> &'static Layout {
+    if root.contains_key(EDITOR.edges) { &EDITOR } else { &IR }
+}
+
+/// Flow file content as JSON, or as YAML when it is not JSON
+pub fn parse_value(content: &str) -> Result<Value> {
+    match serde_json::from_str(content) {
+        Ok(value) => Ok(value),
+        Err(_) => Ok(serde_yaml::from_str(content)?),
+    }
+}
+
+/// Canonical text of a flow file; YAML input stays YAML
+pub fn format_str(content: &str, yaml: bool) -> Result<String> {
+    format_document(parse_value(content)?, yaml)
+}
+
+/// Canonical text of a flow document, as JSON or YAML
+pub fn format_document(value: Value, yaml: bool) -> Result<String> {
+    let value = format_value(value)?;
+    if yaml {
+        Ok(serde_yaml::to_string(&value)?)
+    } else {
+        Ok(serde_json::to_string_pretty(&value)? + "\n")
+    }
+}
+
+/// Canonical JSON of an in-memory flow
+pub fn format_flow(flow: &Flow) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&format_value(serde_json::to_value(flow)?)?)? + "\n")
+}
+
+/// Nodes in topological order within each container (containers before their contents),
+/// connections by their ends, sorted imports, and no null fields on nodes and connections
+/// Object keys come out sorted since `serde_json::Map` is ordered
+pub fn format_value(mut value: Value) -> Result<Value> {
+    let root = value.as_object_mut().ok_or_else(|| anyhow!("A flow file must be an object"))?;
+    let layout = layout_of(root);
+    if layout.edges == IR.edges {
+        serde_json::from_value::<Flow>(Value::Object(root.clone()))?;
+    }
+
+    let mut nodes = take_objects(root, "nodes")?;
+    let mut edges = take_objects(root, layout.edges)?;
+    for object in nodes.iter_mut().chain(edges.iter_mut()) {
+        object.retain(|_, v| !v.is_null());
+    }
+
+    let order = node_order(&nodes, &edges, layout);
+    let position: HashMap<String, usize> = order.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+    let index_of = |object: &Map<String, Value>, key: &str| {
+        object.get(key).and_then(Value::as_str).and_then(|id| position.get(id)).copied().unwrap_or(usize::MAX)
+    };
+    nodes.sort_by_key(|node| index_of(node, "id"));
+    edges.sort_by_cached_key(|edge| {
+        let text = |key: &str| edge.get(key).map(Value::to_string).unwrap_or_default();
+        (index_of(edge, layout.from), index_of(edge, layout.to), text(layout.to_port), text(layout.from_port), Value::Object(edge.clone()).to_string())
+    });
+    root.insert("nodes".to_string(), Value::Array(nodes.into_iter().map(Value::Object).collect()));
+    root.insert(layout.edges.to_string(), Value::Array(edges.into_iter().map(Value::Object).collect()));
+
+    if let Some(Value::Array(imports)) = root.get_mut("imports") {
+        imports.sort_by_key(|import| import.to_string());
+    }
+    if let Some(Value::Array(modules)) = root.get_mut("modules") {
+        modules.sort_by_key(|module| module.get("path").map(Value::to_string).unwrap_or_default());
+    }
+    Ok(value)
+}
+
+fn take_objects(root: &mut Map<String, Value>, key: &str) -> Result<Vec<Map<String, Value>>> {
+    match root.remove(key) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(items)) => items.into_iter()
+            .map(|item| match item {
+                Value::Object(object) => Ok(object),
+                _ => Err(anyhow!("Every entry of '{}' must be an object", key)),
+            })
+            .collect(),
+        Some(_) => Err(anyhow!("'{}' must be a list", key)),
+    }
+}
+
+/// Pre-order walk of the containers; siblings in topological order of the connections between them
+/// (or their descendants), ties and cycles broken by id so the result does not depend on the input order
+fn node_order(nodes: &[Map<String, Value>], edges: &[Map<String, Value>], layout: &Layout) -> Vec<String> {
+    let str_of = |object: &Map<String, Value>, key: &str| object.get(key).and_then(Value::as_str).map(String::from);
+    let ids: Vec<String> = nodes.iter().filter_map(|n| str_of(n, "id")).collect();
+    let parents: HashMap<String, String> = nodes.iter()
+        .filter_map(|n| Some((str_of(n, "id")?, str_of(n, layout.parent)?)))
+        .filter(|(_, parent)| ids.contains(parent))
+        .collect();
+    let connections: Vec<(String, String)> = edges.iter()
+        .filter_map(|e| Some((str_of(e, layout.from)?, str_of(e, layout.to)?)))
+        .collect();
+
+    let mut order = Vec::new();
+    visit(None, &ids, &parents, &connections, &mut order);
+    // Nodes caught in a parent cycle are not reachable from the top level
+    let mut rest: Vec<&String> = ids.iter().filter(|id| !order.contains(id)).collect();
+    rest.sort_by_key(|id| natural_key(id));
+    order.extend(rest.into_iter().cloned());
+    order
+}
+
+/// Append the children of `scope` and, after each, its own contents
+fn visit(scope: Option<&String>, ids: &[String], parents: &HashMap<String, String>, connections: &[(String, String)], order: &mut Vec<String>) {
+    let children: Vec<&String> = ids.iter().filter(|id| parents.get(*id) == scope).collect();
+    for id in sort_siblings(&children, connections, parents) {
+        order.push(id.clone());
+        visit(Some(id), ids, parents, connections, order);
+    }
+}
+
+fn sort_siblings<'a>(siblings: &[&'a String], connections: &[(String, String)], parents: &HashMap<String, String>) -> Vec<&'a String> {
+    let index: HashMap<&str, usize> = siblings.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+    // Sibling containing `id`, walking up the parent chain
+    let sibling_of = |start: &String| -> Option<usize> {
+        let mut id = start.as_str();
+        for _ in 0..=parents.len() {
+            if let Some(&i) = index.get(id) {
+                return Some(i);
+            }
+            id = parents.get(id)?;
+        }
+        None
+    };
+    let mut incoming = vec![0usize; siblings.len()];
+    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); siblings.len()];
+    for (from, to) in connections {
+        if let (Some(from), Some(to)) = (sibling_of(from), sibling_of(to))
+            && from != to
+        {
+            outgoing[from].push(to);
+            incoming[to] += 1;
+        }
+    }
+
+    let key = |i: usize| (natural_key(siblings[i]), i);
+    let mut ready: BTreeSet<_> = (0..siblings.len()).filter(|&i| incoming[i] == 0).map(key).collect();
+    let mut done = vec![false; siblings.len()];
+    let mut sorted = Vec::new();
+    while sorted.len() < siblings.len() {
+        let next = match ready.pop_first() {
+            Some((_, i)) => i,
+            // A cycle: continue with its smallest id
+            None => (0..siblings.len()).filter(|&i| !done[i]).min_by_key(|&i| key(i)).unwrap_or_default(),
+        };
+        if done[next] {
+            continue;
+        }
+        done[next] = true;
+        sorted.push(siblings[next]);
+        for &to in &outgoing[next] {
+            incoming[to] = incoming[to].saturating_sub(1);
+            if incoming[to] == 0 && !done[to] {
+                ready.insert(key(to));
+            }
+        }
+    }
+    sorted
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Part {
+    Number(u64),
+    Text(String),
+}
+
+/// `node_2` sorts before `node_10`
+fn natural_key(id: &str) -> Vec<Part> {
+    let mut parts = Vec::new();
+    let mut rest = id;
+    while let Some(first) = rest.chars().next() {
+        let digits = first.is_ascii_digit();
+        let end = rest.find(|c: char| c.is_ascii_digit() != digits).unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        parts.push(match chunk.parse() {
+            Ok(number) if digits => Part::Number(number),
+            _ => Part::Text(chunk.to_string()),
+        });
+        rest = tail;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_order_is_independent_of_input() {
+        let first = r#"{"connections": [{"to": "node_10", "from": "node_2", "variable_mapping": null}],
+            "nodes": [
+                {"id": "node_10", "plugin_type": "debug", "parent_id": "main", "properties": {"b": 1, "a": 2}, "label": null},
+                {"id": "helper", "plugin_type": "function-definition", "parent_id": null, "properties": {}},
+                {"id": "node_2", "plugin_type": "let-variable", "parent_id": "main", "properties": {}},
+                {"id": "main", "plugin_type": "function-definition", "parent_id": null, "properties": {}}
+            ], "imports": ["std::fmt", "std::collections::HashMap"]}"#;
+        let second = r#"{"imports": ["std::collections::HashMap", "std::fmt"], "nodes": [
+                {"id": "main", "plugin_type": "function-definition", "properties": {}},
+                {"id": "node_2", "plugin_type": "let-variable", "parent_id": "main", "properties": {}},
+                {"id": "helper", "plugin_type": "function-definition", "properties": {}},
+                {"properties": {"a": 2, "b": 1}, "plugin_type": "debug", "parent_id": "main", "id": "node_10"}
+            ], "connections": [{"from": "node_2", "to": "node_10"}]}"#;
+
+        let formatted = format_str(first, false).unwrap();
+        assert_eq!(formatted, format_str(second, false).unwrap());
+        assert_eq!(format_str(&formatted, false).unwrap(), formatted);
+        let ids: Vec<&str> = formatted.lines().filter_map(|l| l.trim().strip_prefix("\"id\": ")).collect();
+        assert_eq!(ids, vec!["\"helper\",", "\"main\",", "\"node_2\",", "\"node_10\","]);
+        assert!(formatted.contains("      \"properties\": {\n        \"a\": 2,\n        \"b\": 1\n      }"));
+        assert!(!formatted.contains("null"));
+    }
+}
@@ -0,0 +1,422 @@
+use crate::ir::{Connection, ConnectionType, Flow, Node};
+use std::collections::HashMap;
+
+/// Parent/child view of a flow shared by the renderers
+/// Nodes with children (and every function-definition) are drawn as clusters
+struct Tree<'f> {
+    flow: &'f Flow,
+    children: HashMap<Option<&'f str>, Vec<&'f Node>>,
+}
+
+impl<'f> Tree<'f> {
+    fn new(flow: &'f Flow) -> Self {
+        let mut children: HashMap<Option<&str>, Vec<&Node>> = HashMap::new();
+        for node in &flow.nodes {
+            children.entry(node.parent_id.as_deref()).or_default().push(node);
+        }
+        Tree { flow, children }
+    }
+
+    fn children<'t>(&'t self, parent: Option<&'t str>) -> &'t [&'f Node] {
+        let children: &'t HashMap<Option<&'t str>, Vec<&'f Node>> = &self.children;
+        children.get(&parent).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn is_cluster(&self, node: &Node) -> bool {
+        node.plugin_type == "function-definition" || self.children.contains_key(&Some(node.id.as_str()))
+    }
+}
+
+fn title(node: &Node) -> &str {
+    node.label.as_deref()
+        .filter(|l| !l.is_empty())
+        .or_else(|| node.properties.get("function_name").and_then(|v| v.as_str()))
+        .unwrap_or(&node.id)
+}
+
+/// Edge label lines: connection type, ports and variable mapping
+fn edge_label(conn: &Connection) -> Vec<String> {
+    let mut lines = Vec::new();
+    match conn.connection_type {
+        ConnectionType::Simple => {}
+        ConnectionType::FunctionCall => lines.push("call".to_string()),
+        ConnectionType::Error => lines.push("error".to_string()),
+    }
+    if let Some(to_port) = &conn.to_port {
+        match conn.from_port.as_deref() {
+            Some(from_port) if from_port != crate::stdlib::OUTPUT_PORT => lines.push(format!("{} → {}", from_port, to_port)),
+            _ => lines.push(to_port.clone()),
+        }
+    }
+    if let Some(mapping) = &conn.variable_mapping {
+        let mut pairs: Vec<String> = mapping.iter().map(|(arg, var)| format!("{} = {}", arg, var)).collect();
+        pairs.sort();
+        lines.extend(pairs);
+    }
+    lines
+}
+
+/// Graphviz DOT; function-definitions become `cluster_` subgraphs nested along `parent_id`
+pub fn to_dot(flow: &Flow) -> String {
+    let tree = Tree::new(flow);
+    let mut dot = String::from("digraph flow {\n");
+    dot.push_str("    compound=true;\n");
+    dot.push_str("    node [shape=box, style=\"rounded,filled\", fillcolor=\"#ffffff\", fontname=\"Helvetica\"];\n");
+    dot.push_str("    edge [fontname=\"Helvetica\", fontsize=10];\n");
+    let mut clusters = 0;
+    dot_scope(&tree, None, 1, &mut clusters, &mut dot);
+
+    for conn in &flow.connections {
+        let mut attributes = Vec::new();
+        let label = edge_label(conn);
+        if !label.is_empty() {
+            attributes.push(format!("label=\"{}\"", dot_escape(&label.join("\n"))));
+        }
+        match conn.connection_type {
+            ConnectionType::Simple => {}
+            ConnectionType::FunctionCall => attributes.push("style=bold".to_string()),
+            ConnectionType::Error => attributes.push("style=dashed, color=\"#c0392b\"".to_string()),
+        }
+        let attributes = if attributes.is_empty() { String::new() } else { format!(" [{}]", attributes.join(", ")) };
+        dot.push_str(&format!("    \"{}\" -> \"{}\"{};\n", dot_escape(&conn.from), dot_escape(&conn.to), attributes));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn dot_scope(tree: &Tree, parent: Option<&str>, depth: usize, clusters: &mut usize, dot: &mut String) {
+    let indent = "    ".repeat(depth);
+    for node in tree.children(parent) {
+        let node_label = dot_escape(&format!("{}\n{}", title(node), node.plugin_type));
+        if tree.is_cluster(node) {
+            *clusters += 1;
+            dot.push_str(&format!("{}subgraph cluster_{} {{\n", indent, clusters));
+            dot.push_str(&format!("{}    label=\"{}\";\n", indent, dot_escape(title(node))));
+            dot.push_str(&format!("{}    style=dashed;\n", indent));
+            // The container itself stays addressable by the connections that reach it
+            dot.push_str(&format!("{}    \"{}\" [label=\"{}\", shape=folder];\n", indent, dot_escape(&node.id), node_label));
+            dot_scope(tree, Some(&node.id), depth + 1, clusters, dot);
+            dot.push_str(&format!("{}}}\n", indent));
+        } else {
+            dot.push_str(&format!("{}\"{}\" [label=\"{}\"];\n", indent, dot_escape(&node.id), node_label));
+        }
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Mermaid flowchart; clusters become nested `subgraph` blocks
+pub fn to_mermaid(flow: &Flow) -> String {
+    let tree = Tree::new(flow);
+    let ids = mermaid_ids(flow);
+    let mut mermaid = String::from("flowchart TD\n");
+    mermaid_scope(&tree, &ids, None, 1, &mut mermaid);
+
+    for conn in &flow.connections {
+        let (Some(from), Some(to)) = (ids.get(conn.from.as_str()), ids.get(conn.to.as_str())) else { continue };
+        let arrow = match conn.connection_type {
+            ConnectionType::Simple => "-->",
+            ConnectionType::FunctionCall => "==>",
+            ConnectionType::Error => "-.->",
+        };
+        let label = edge_label(conn);
+        if label.is_empty() {
+            mermaid.push_str(&format!("    {} {} {}\n", from, arrow, to));
+        } else {
+            mermaid.push_str(&format!("    {} {}|\"{}\"| {}\n", from, arrow, mermaid_escape(&label.join("<br/>")), to));
+        }
+    }
+    mermaid
+}
+
+fn mermaid_scope(tree: &Tree, ids: &HashMap<&str, String>, parent: Option<&str>, depth: usize, mermaid: &mut String) {
+    let indent = "    ".repeat(depth);
+    for node in tree.children(parent) {
+        let id = &ids[node.id.as_str()];
+        if tree.is_cluster(node) {
+            mermaid.push_str(&format!("{}subgraph {}[\"{}\"]\n", indent, id, mermaid_escape(title(node))));
+            mermaid_scope(tree, ids, Some(&node.id), depth + 1, mermaid);
+            mermaid.push_str(&format!("{}end\n", indent));
+        } else {
+            let label = format!("{}<br/><small>{}</small>", mermaid_escape(title(node)), node.plugin_type);
+            mermaid.push_str(&format!("{}{}[\"{}\"]\n", indent, id, label));
+        }
+    }
+}
+
+/// Words of the flowchart syntax that cannot be used as node ids
+const MERMAID_KEYWORDS: &[&str] = &[
+    "end", "graph", "flowchart", "subgraph", "direction", "style", "linkstyle", "classdef", "class", "click", "default",
+];
+
+/// Mermaid ids allow only identifier characters and no keywords; clashes after sanitizing get a numeric suffix
+fn mermaid_ids(flow: &Flow) -> HashMap<&str, String> {
+    let mut ids: HashMap<&str, String> = HashMap::new();
+    for node in &flow.nodes {
+        let base: String = node.id.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let is_keyword = MERMAID_KEYWORDS.contains(&base.to_ascii_lowercase().as_str());
+        let base = if base.starts_with(|c: char| c.is_ascii_alphabetic()) && !is_keyword { base } else { format!("n_{}", base) };
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while ids.values().any(|existing| *existing == id) {
+            suffix += 1;
+            id = format!("{}_{}", base, suffix);
+        }
+        ids.insert(&node.id, id);
+    }
+    ids
+}
+
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+const NODE_WIDTH: f64 = 170.0;
+const NODE_HEIGHT: f64 = 52.0;
+const GAP_X: f64 = 40.0;
+const GAP_Y: f64 = 60.0;
+const PADDING: f64 = 20.0;
+const HEADER: f64 = 28.0;
+
+/// Box placed by the SVG layout, relative to its block until flattened
+struct Placed<'f> {
+    node: &'f Node,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    cluster: bool,
+}
+
+/// Laid out children of one scope
+struct Block<'f> {
+    width: f64,
+    height: f64,
+    boxes: Vec<Placed<'f>>,
+}
+
+/// Self-contained SVG drawing, no Graphviz needed
+/// Siblings are ranked top to bottom by the connections between them; clusters are laid out recursively
+pub fn to_svg(flow: &Flow) -> String {
+    let tree = Tree::new(flow);
+    let block = layout(&tree, None);
+    let (width, height) = (block.width + 2.0 * PADDING, block.height + 2.0 * PADDING);
+    let boxes: HashMap<&str, &Placed> = block.boxes.iter().map(|b| (b.node.id.as_str(), b)).collect();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"Helvetica, Arial, sans-serif\">\n",
+        w = width, h = height
+    );
+    svg.push_str("  <defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"7\" markerHeight=\"7\" orient=\"auto-start-reverse\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"context-stroke\"/></marker></defs>\n");
+    svg.push_str(&format!("  <g transform=\"translate({p} {p})\">\n", p = PADDING));
+
+    // Clusters first so their contents and the edges are drawn on top
+    for placed in block.boxes.iter().filter(|b| b.cluster) {
+        svg.push_str(&format!(
+            "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"8\" fill=\"#f0f0f0\" fill-opacity=\"0.6\" stroke=\"#888\" stroke-dasharray=\"6 4\"/>\n",
+            placed.x, placed.y, placed.width, placed.height
+        ));
+        svg.push_str(&format!(
+            "    <text x=\"{}\" y=\"{}\" font-size=\"13\" font-weight=\"bold\" fill=\"#333\">{}</text>\n",
+            placed.x + 10.0, placed.y + 19.0, xml_escape(title(placed.node))
+        ));
+    }
+    for placed in block.boxes.iter().filter(|b| !b.cluster) {
+        svg.push_str(&format!(
+            "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"6\" fill=\"#ffffff\" stroke=\"#333\"/>\n",
+            placed.x, placed.y, placed.width, placed.height
+        ));
+        let center = placed.x + placed.width / 2.0;
+        svg.push_str(&format!(
+            "    <text x=\"{}\" y=\"{}\" font-size=\"13\" text-anchor=\"middle\" fill=\"#111\">{}</text>\n",
+            center, placed.y + 22.0, xml_escape(title(placed.node))
+        ));
+        svg.push_str(&format!(
+            "    <text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\" fill=\"#777\">{}</text>\n",
+            center, placed.y + 38.0, xml_escape(&placed.node.plugin_type)
+        ));
+    }
+
+    for conn in &tree.flow.connections {
+        let (Some(from), Some(to)) = (boxes.get(conn.from.as_str()), boxes.get(conn.to.as_str())) else { continue };
+        let (x1, y1) = (from.x + from.width / 2.0, from.y + from.height);
+        let (x2, y2) = (to.x + to.width / 2.0, to.y);
+        let style = match conn.connection_type {
+            ConnectionType::Simple => "stroke=\"#555\"",
+            ConnectionType::FunctionCall => "stroke=\"#2c3e50\" stroke-width=\"2.5\"",
+            ConnectionType::Error => "stroke=\"#c0392b\" stroke-dasharray=\"5 4\"",
+        };
+        svg.push_str(&format!(
+            "    <path d=\"M {} {} C {} {} {} {} {} {}\" fill=\"none\" {} marker-end=\"url(#arrow)\"/>\n",
+            x1, y1, x1, y1 + GAP_Y / 2.0, x2, y2 - GAP_Y / 2.0, x2, y2, style
+        ));
+        for (index, line) in edge_label(conn).iter().enumerate() {
+            svg.push_str(&format!(
+                "    <text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\" fill=\"#333\" stroke=\"#fff\" stroke-width=\"3\" paint-order=\"stroke\">{}</text>\n",
+                (x1 + x2) / 2.0, (y1 + y2) / 2.0 + 12.0 * index as f64, xml_escape(line)
+            ));
+        }
+    }
+    svg.push_str("  </g>\n</svg>\n");
+    svg
+}
+
+/// Rows of siblings ordered by rank; clusters are sized by their own layout
+fn layout<'f>(tree: &Tree<'f>, parent: Option<&str>) -> Block<'f> {
+    let siblings = tree.children(parent);
+    let ranks = ranks(tree, parent, siblings);
+
+    // Size every sibling, laying out cluster contents first
+    let mut sized: Vec<(Placed<'f>, Vec<Placed<'f>>)> = Vec::new();
+    for node in siblings {
+        if tree.is_cluster(node) {
+            let inner = layout(tree, Some(&node.id));
+            let width = (inner.width + 2.0 * PADDING).max(NODE_WIDTH);
+            let height = HEADER + inner.height + PADDING;
+            sized.push((Placed { node, x: 0.0, y: 0.0, width, height, cluster: true }, inner.boxes));
+        } else {
+            sized.push((Placed { node, x: 0.0, y: 0.0, width: NODE_WIDTH, height: NODE_HEIGHT, cluster: false }, Vec::new()));
+        }
+    }
+
+    let row_count = ranks.iter().copied().max().map_or(0, |r| r + 1);
+    let rows: Vec<Vec<usize>> = (0..row_count)
+        .map(|rank| (0..siblings.len()).filter(|&i| ranks[i] == rank).collect())
+        .collect();
+    let row_width = |row: &[usize]| row.iter().map(|&i| sized[i].0.width).sum::<f64>() + GAP_X * row.len().saturating_sub(1) as f64;
+    let width = rows.iter().map(|row| row_width(row)).fold(0.0, f64::max);
+
+    let mut boxes = Vec::new();
+    let mut y = 0.0;
+    for row in &rows {
+        let mut x = (width - row_width(row)) / 2.0;
+        let mut row_height: f64 = 0.0;
+        for &index in row {
+            let (placed, inner) = &sized[index];
+            boxes.push(Placed { x, y, ..*placed });
+            // Cluster contents sit below the header, inside the padding
+            let (offset_x, offset_y) = (x + (placed.width - inner_width(inner)) / 2.0, y + HEADER);
+            boxes.extend(inner.iter().map(|b| Placed { x: b.x + offset_x, y: b.y + offset_y, ..*b }));
+            x += placed.width + GAP_X;
+            row_height = row_height.max(placed.height);
+        }
+        y += row_height + GAP_Y;
+    }
+    let height = if rows.is_empty() { 0.0 } else { y - GAP_Y };
+    Block { width, height, boxes }
+}
+
+/// Width spanned by already laid out boxes
+fn inner_width(boxes: &[Placed]) -> f64 {
+    boxes.iter().map(|b| b.x + b.width).fold(0.0, f64::max)
+}
+
+/// Longest-path rank of each sibling; connections between descendants count for their ancestors
+fn ranks<'f>(tree: &Tree<'f>, parent: Option<&str>, siblings: &[&Node]) -> Vec<usize> {
+    let index: HashMap<&str, usize> = siblings.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+    let parents: HashMap<&'f str, Option<&'f str>> = tree.flow.nodes.iter().map(|n| (n.id.as_str(), n.parent_id.as_deref())).collect();
+    // Sibling containing `id`, walking up the parent chain
+    let sibling_of = |mut id: &'f str| -> Option<usize> {
+        for _ in 0..parents.len() {
+            if let Some(&i) = index.get(id)
+                && parents.get(id).copied().flatten() == parent
+            {
+                return Some(i);
+            }
+            id = parents.get(id).copied().flatten()?;
+        }
+        None
+    };
+    let edges: Vec<(usize, usize)> = tree.flow.connections.iter()
+        .filter_map(|c| Some((sibling_of(&c.from)?, sibling_of(&c.to)?)))
+        .filter(|(from, to)| from != to)
+        .collect();
+
+    let mut ranks = vec![0; siblings.len()];
+    // Bounded relaxation so cycles cannot loop forever
+    for _ in 0..siblings.len() {
+        let mut changed = false;
+        for &(from, to) in &edges {
+            if ranks[to] < ranks[from] + 1 && ranks[from] + 1 < siblings.len() {
+                ranks[to] = ranks[from] + 1;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    ranks
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{connection, node};
+    use serde_json::json;
+
+    fn link(from: &str, to: &str, connection_type: ConnectionType, mapping: Option<(&str, &str)>) -> Connection {
+        Connection {
+            connection_type,
+            variable_mapping: mapping.map(|(arg, var)| HashMap::from([(arg.to_string(), var.to_string())])),
+            ..connection(from, to)
+        }
+    }
+
+    #[test]
+    fn test_functions_render_as_clusters_with_labelled_edges() {
+        let flow = Flow {
+            nodes: vec![
+                node("main", "function-definition", None, json!({"function_name": "main"})),
+                node("start", "start-node", Some("main"), json!({})),
+                node("call", "call-function", Some("main"), json!({"target_function": "work"})),
+                node("report", "debug", Some("main"), json!({})),
+            ],
+            connections: vec![
+                link("start", "call", ConnectionType::Simple, Some(("input", "\"data\""))),
+                link("call", "report", ConnectionType::Error, None),
+            ],
+            ..Default::default()
+        };
+
+        let dot = to_dot(&flow);
+        assert!(dot.contains("    subgraph cluster_1 {\n        label=\"main\";\n        style=dashed;\n        \"main\" [label=\"main\\nfunction-definition\", shape=folder];\n        \"start\" [label=\"start\\nstart-node\"];\n"));
+        assert!(dot.contains("    \"start\" -> \"call\" [label=\"input = \\\"data\\\"\"];\n"));
+        assert!(dot.contains("    \"call\" -> \"report\" [label=\"error\", style=dashed, color=\"#c0392b\"];\n"));
+
+        let mermaid = to_mermaid(&flow);
+        assert!(mermaid.starts_with("flowchart TD\n    subgraph main[\"main\"]\n        start[\"start<br/><small>start-node</small>\"]\n"));
+        assert!(mermaid.contains("    start -->|\"input = #quot;data#quot;\"| call\n"));
+        assert!(mermaid.contains("    call -.->|\"error\"| report\n"));
+
+        let svg = to_svg(&flow);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert_eq!(svg.matches("stroke-dasharray=\"6 4\"").count(), 1);
+        assert!(svg.contains(">input = &quot;data&quot;</text>"));
+    }
+
+    #[test]
+    fn test_mermaid_ids_avoid_keywords() {
+        let flow = Flow {
+            nodes: vec![
+                node("end", "debug", None, json!({})),
+                node("n_end", "debug", None, json!({})),
+                node("Graph", "debug", None, json!({})),
+                node("1st", "debug", None, json!({})),
+            ],
+            connections: vec![connection("end", "1st"), connection("Graph", "n_end")],
+            ..Default::default()
+        };
+
+        let mermaid = to_mermaid(&flow);
+        assert!(mermaid.contains("    n_end[\"end<br/><small>debug</small>\"]\n"));
+        assert!(mermaid.contains("    n_end --> n_1st\n"));
+        assert!(mermaid.contains("    n_Graph --> n_end_2\n"));
+    }
+}
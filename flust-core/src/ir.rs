@@ -2,10 +2,26 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Flow definition - represents the complete visual flow
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Flow {
     pub nodes: Vec<Node>,
     pub connections: Vec<Connection>,
+    /// `use` paths hoisted to the top of the generated file (e.g. "std::collections::HashMap")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imports: Vec<String>,
+    /// Other flow files whose functions and types this flow uses
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<ModuleImport>,
+}
+
+/// Import of function-definitions and type definitions from another flow file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModuleImport {
+    /// Path of the flow file, relative to the importing flow
+    pub path: String,
+    /// Imported function and type names
+    #[serde(default)]
+    pub items: Vec<String>,
 }
 
 /// Node - represents a single block in the flow
@@ -23,17 +39,14 @@ pub struct Node {
 }
 
 /// Connection type - defines how the connection behaves
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ConnectionType {
+    #[default]
     Simple,
     FunctionCall,
-}
-
-impl Default for ConnectionType {
-    fn default() -> Self {
-        ConnectionType::Simple
-    }
+    /// Leads from a fallible call to the nodes handling its error
+    Error,
 }
 
 /// Connection - represents data flow between nodes
@@ -45,4 +58,10 @@ pub struct Connection {
     pub connection_type: ConnectionType,
     /// Maps argument name to variable name for function calls
     pub variable_mapping: Option<HashMap<String, String>>,
+    /// Output port of the source node carrying a value (data connections only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_port: Option<String>,
+    /// Input port (or property) of the target node fed by the value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_port: Option<String>,
 }
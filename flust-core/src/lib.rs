@@ -1,3 +1,17 @@
+pub mod diagnose;
+pub mod diagnostics;
+pub mod diff;
+#[cfg(test)]
+mod fixtures;
+pub mod formatter;
+pub mod graph;
 pub mod ir;
+pub mod merge;
+pub mod modules;
 pub mod parser;
+pub mod scope;
+pub mod stdlib;
+pub mod subflow;
 pub mod topological_sort;
+pub mod type_checker;
+pub mod types;
@@ -0,0 +1,270 @@
+use crate::diff::{self, Field, Item};
+use crate::formatter;
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Item changed differently on both sides; the merged flow keeps our version
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub item: Item,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |value: &Option<Value>| value.as_ref().map_or("removed".to_string(), Value::to_string);
+        write!(f, "conflict in {}: ours {}, theirs {} (base {})", self.item, value(&self.ours), value(&self.theirs), value(&self.base))
+    }
+}
+
+pub struct Merge {
+    /// Merged document, in the layout of ours
+    pub document: Value,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Three-way merge item by item: a side that left an item as in `base` takes the other side's version
+/// A node deleted on one side while the other edited it (or connected to it) is a single node conflict
+/// Fields the flow format does not know are merged like any other; editor placement always takes ours
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Result<Merge> {
+    let layouts: Vec<&str> = [base, ours, theirs].into_iter()
+        .map(|document| document.as_object().map_or("", |root| formatter::layout_of(root).edges))
+        .collect();
+    if layouts.iter().any(|edges| *edges != layouts[1]) {
+        return Err(anyhow!("Cannot merge editor documents with flow files; convert them to the same layout first"));
+    }
+    let (b, o, t) = (diff::entries(base)?, diff::entries(ours)?, diff::entries(theirs)?);
+    let mut conflicts = Vec::new();
+
+    // Nodes deleted on one side and modified on the other keep everything of ours touching them
+    let edited = |side: &BTreeMap<Item, Value>, id: &str| {
+        let keys: BTreeSet<&Item> = side.keys().chain(b.keys()).collect();
+        keys.into_iter().any(|item| {
+            let (before, after) = (b.get(item), side.get(item));
+            before != after && [before, after].into_iter().flatten().any(|value| item.touches(value, id))
+        })
+    };
+    let mut kept_nodes = BTreeSet::new();
+    for item in b.keys() {
+        let Item::Node { id, field: None } = item else { continue };
+        let (in_ours, in_theirs) = (o.contains_key(item), t.contains_key(item));
+        if (!in_ours && in_theirs && edited(&t, id)) || (in_ours && !in_theirs && edited(&o, id)) {
+            conflicts.push(Conflict { item: item.clone(), base: b.get(item).cloned(), ours: o.get(item).cloned(), theirs: t.get(item).cloned() });
+            kept_nodes.insert(id.as_str());
+        }
+    }
+    let kept = |item: &Item, value: Option<&Value>| {
+        kept_nodes.iter().any(|id| value.is_some_and(|value| item.touches(value, id)))
+    };
+
+    let mut merged = BTreeMap::new();
+    let items: BTreeSet<&Item> = b.keys().chain(o.keys()).chain(t.keys()).collect();
+    for item in items {
+        let (base_value, ours_value, theirs_value) = (b.get(item), o.get(item), t.get(item));
+        let conflicted_node = kept(item, ours_value) || kept(item, theirs_value) || kept(item, base_value);
+        let value = if conflicted_node || ours_value == theirs_value || theirs_value == base_value {
+            ours_value
+        } else if ours_value == base_value {
+            theirs_value
+        } else if item.is_presentation() {
+            ours_value
+        } else {
+            conflicts.push(Conflict {
+                item: item.clone(),
+                base: base_value.cloned(),
+                ours: ours_value.cloned(),
+                theirs: theirs_value.cloned(),
+            });
+            ours_value
+        };
+        if let Some(value) = value {
+            merged.insert(item.clone(), value.clone());
+        }
+    }
+
+    conflicts.sort_by(|a, b| a.item.cmp(&b.item));
+    Ok(Merge { document: rebuild(merged, &[ours, theirs, base])?, conflicts })
+}
+
+/// Document from merged items, keeping the node and connection order of the first document that has them
+fn rebuild(mut entries: BTreeMap<Item, Value>, order: &[&Value]) -> Result<Value> {
+    let roots: Vec<&Map<String, Value>> = order.iter().filter_map(|document| document.as_object()).collect();
+    let layout = formatter::layout_of(roots[0]);
+
+    let mut nodes: Vec<Map<String, Value>> = Vec::new();
+    for root in &roots {
+        for node in diff::objects(root, "nodes")? {
+            let Some(id) = diff::text(node, "id") else { continue };
+            // Removing the entry also skips the node's copies in the later documents
+            let Some(plugin) = entries.remove(&Item::Node { id: id.to_string(), field: None }) else { continue };
+            let mut object = Map::new();
+            object.insert("id".to_string(), Value::String(id.to_string()));
+            object.insert(layout.plugin.to_string(), plugin);
+            object.insert(layout.properties.to_string(), Value::Object(Map::new()));
+            nodes.push(object);
+        }
+    }
+    let positions: BTreeMap<String, usize> = nodes.iter().enumerate()
+        .filter_map(|(i, n)| Some((diff::text(n, "id")?.to_string(), i)))
+        .collect();
+
+    let mut root = Map::new();
+    let (mut edges, mut imports, mut modules) = (Vec::new(), Vec::new(), Vec::new());
+    for (item, value) in entries {
+        match item {
+            Item::Node { id, field: Some(field) } => {
+                let Some(&index) = positions.get(&id) else { continue };
+                let node = &mut nodes[index];
+                let key = match field {
+                    Field::Label => "label".to_string(),
+                    Field::ParentId => layout.parent.to_string(),
+                    Field::Other(key) => key,
+                    Field::Property(name) => {
+                        if let Some(Value::Object(properties)) = node.get_mut(layout.properties) {
+                            properties.insert(name, value);
+                        }
+                        continue;
+                    }
+                };
+                node.insert(key, value);
+            }
+            Item::Connection { from, to, to_port } => {
+                let mut edge = match value {
+                    Value::Object(fields) => fields,
+                    _ => Map::new(),
+                };
+                edge.insert(layout.from.to_string(), Value::String(from));
+                edge.insert(layout.to.to_string(), Value::String(to));
+                if let Some(port) = to_port {
+                    edge.insert(layout.to_port.to_string(), Value::String(port));
+                }
+                edges.push(edge);
+            }
+            Item::Import { path } => imports.push(path),
+            Item::Module { path } => {
+                let mut module = match value {
+                    Value::Object(fields) => fields,
+                    _ => Map::new(),
+                };
+                module.insert("path".to_string(), Value::String(path));
+                modules.push(module);
+            }
+            Item::Document { key } => {
+                root.insert(key, value);
+            }
+            Item::Node { field: None, .. } => {}
+        }
+    }
+
+    // Entries come out sorted; restore the order the documents had them in
+    let key_of = |edge: &Map<String, Value>| {
+        (diff::text(edge, layout.from).map(String::from), diff::text(edge, layout.to).map(String::from), diff::text(edge, layout.to_port).map(String::from))
+    };
+    let mut edge_order = Vec::new();
+    let mut module_order = Vec::new();
+    for root in &roots {
+        edge_order.extend(diff::objects(root, layout.edges)?.into_iter().map(&key_of));
+        module_order.extend(diff::objects(root, "modules")?.into_iter().filter_map(|module| diff::text(module, "path")));
+    }
+    let import_order: Vec<&str> = roots.iter()
+        .filter_map(|root| root.get("imports").and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    edges.sort_by_key(|edge| edge_order.iter().position(|key| *key == key_of(edge)).unwrap_or(usize::MAX));
+    imports.sort_by_key(|path| import_order.iter().position(|p| p == path));
+    modules.sort_by_key(|module| module_order.iter().position(|p| Some(*p) == diff::text(module, "path")));
+
+    root.insert("nodes".to_string(), Value::Array(nodes.into_iter().map(Value::Object).collect()));
+    root.insert(layout.edges.to_string(), Value::Array(edges.into_iter().map(Value::Object).collect()));
+    if !imports.is_empty() {
+        root.insert("imports".to_string(), serde_json::json!(imports));
+    }
+    if !modules.is_empty() {
+        root.insert("modules".to_string(), Value::Array(modules.into_iter().map(Value::Object).collect()));
+    }
+    Ok(Value::Object(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn flow(nodes: &str, connections: &str) -> Value {
+        serde_json::from_str(&format!("{{\"nodes\": [{}], \"connections\": [{}]}}", nodes, connections)).unwrap()
+    }
+
+    const A: &str = r#"{"id": "a", "plugin_type": "let-variable", "label": null, "properties": {"name": "x", "value": "1"}, "parent_id": null}"#;
+    const B: &str = r#"{"id": "b", "plugin_type": "debug", "label": null, "properties": {}, "parent_id": null}"#;
+    const AB: &str = r#"{"from": "a", "to": "b", "variable_mapping": null}"#;
+
+    #[test]
+    fn test_merge_combines_edits_and_reports_conflicts() {
+        let base = flow(&[A, B].join(","), AB);
+        let ours = flow(&[A.replace("\"x\"", "\"total\""), B.to_string()].join(","), AB);
+        let theirs = flow(
+            &[A.replace("\"1\"", "\"2\""), B.replace("null, \"properties\"", "\"Show\", \"properties\"")].join(","),
+            AB,
+        );
+
+        let clean = merge(&base, &ours, &theirs).unwrap();
+        assert!(clean.conflicts.is_empty());
+        assert!(diff::diff(&clean.document, &flow(
+            &[A.replace("\"x\"", "\"total\"").replace("\"1\"", "\"2\""), B.replace("null, \"properties\"", "\"Show\", \"properties\"")].join(","),
+            AB,
+        )).unwrap().is_empty());
+
+        let changed = flow(&[A.replace("\"1\"", "\"3\""), B.to_string()].join(","), AB);
+        let conflicting = merge(&base, &changed, &theirs).unwrap();
+        assert_eq!(conflicting.conflicts.len(), 1);
+        assert_eq!(conflicting.conflicts[0].to_string(), "conflict in node 'a' properties.value: ours \"3\", theirs \"2\" (base \"1\")");
+        assert_eq!(conflicting.document["nodes"][0]["properties"]["value"], "3");
+
+        // Deleting b while the other side labels it keeps our version of the node
+        let deleted = merge(&base, &flow(A, ""), &theirs).unwrap();
+        assert_eq!(deleted.conflicts.len(), 1);
+        assert_eq!(deleted.conflicts[0].item, Item::Node { id: "b".to_string(), field: None });
+        assert_eq!(deleted.document["nodes"].as_array().unwrap().len(), 1);
+        assert_eq!(deleted.document["connections"], json!([]));
+    }
+
+    #[test]
+    fn test_merge_round_trips_editor_documents() {
+        let base = json!({
+            "version": "1.0",
+            "metadata": {"name": "app", "modified": "2025-01-01"},
+            "nodes": [
+                {"id": "main", "pluginId": "function-definition", "position": {"x": 0, "y": 0},
+                 "data": {"label": "Main", "function_name": "main"}},
+                {"id": "a", "pluginId": "print", "position": {"x": 10, "y": 20}, "parentNode": "main",
+                 "data": {"label": "Print", "message": "hi"}},
+                {"id": "b", "pluginId": "print", "position": {"x": 10, "y": 80}, "parentNode": "main",
+                 "data": {"label": "Print", "message": "bye"}}
+            ],
+            "edges": [{"id": "e1", "source": "a", "target": "b", "data": {"connectionType": "simple"}}],
+        });
+        assert_eq!(merge(&base, &base, &base).unwrap().document, base);
+
+        // Both sides move a; ours edits a's message, theirs b's and the metadata
+        let mut ours = base.clone();
+        ours["nodes"][1]["position"] = json!({"x": 40, "y": 20});
+        ours["nodes"][1]["data"]["message"] = "hello".into();
+        let mut theirs = base.clone();
+        theirs["nodes"][1]["position"] = json!({"x": 99, "y": 99});
+        theirs["nodes"][2]["data"]["message"] = "goodbye".into();
+        theirs["metadata"]["modified"] = "2025-01-02".into();
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert!(merged.conflicts.is_empty());
+        let mut expected = ours.clone();
+        expected["nodes"][2]["data"]["message"] = "goodbye".into();
+        expected["metadata"]["modified"] = "2025-01-02".into();
+        assert_eq!(merged.document, expected);
+        assert!(merge(&base, &flow(A, ""), &theirs).is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::ir::Flow;
+use crate::parser;
+use crate::subflow;
+use crate::types::{TypeDefinition, TypeRegistry};
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Flow file loaded as one module of a multi-file program
+#[derive(Debug, Clone)]
+pub struct FlowModule {
+    /// Rust module name, derived from the file name (`sensors.flow.json` -> `sensors`)
+    pub name: String,
+    pub path: PathBuf,
+    pub flow: Flow,
+    /// Imported items per module name
+    pub imports: Vec<(String, Vec<String>)>,
+    /// Module loaded for each `source` path of the flow's subflow nodes
+    pub subflows: Vec<(String, String)>,
+}
+
+/// Import graph of a root flow and every flow file it reaches
+/// Modules are ordered dependencies first; the root flow is last
+#[derive(Debug, Clone)]
+pub struct ModuleGraph {
+    pub modules: Vec<FlowModule>,
+}
+
+impl ModuleGraph {
+    pub fn load(root: &Path) -> Result<Self> {
+        Self::load_with(root, |path| parser::parse_file(path))
+    }
+
+    /// Resolve the import graph reading each flow through `read`
+    pub fn load_with(root: &Path, mut read: impl FnMut(&Path) -> Result<Flow>) -> Result<Self> {
+        let mut loader = Loader {
+            read: &mut read,
+            stack: Vec::new(),
+            loaded: HashMap::new(),
+            modules: Vec::new(),
+        };
+        loader.visit(&normalize(root))?;
+        Ok(ModuleGraph { modules: loader.modules })
+    }
+
+    pub fn root(&self) -> &FlowModule {
+        self.modules.last().expect("module graph always contains the root flow")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FlowModule> {
+        self.modules.iter().find(|m| m.name == name)
+    }
+
+    /// Items other modules import from `name`; these must be public
+    pub fn exports(&self, name: &str) -> Vec<&str> {
+        let mut exports: Vec<&str> = self.modules.iter()
+            .flat_map(|m| m.imports.iter())
+            .filter(|(module, _)| module == name)
+            .flat_map(|(_, items)| items.iter().map(String::as_str))
+            .collect();
+        exports.sort_unstable();
+        exports.dedup();
+        exports
+    }
+
+    /// Type definitions module `name` imports from the other flow files
+    pub fn imported_types(&self, name: &str) -> Vec<TypeDefinition> {
+        let Some(module) = self.get(name) else { return Vec::new() };
+        module.imports.iter()
+            .filter_map(|(imported, items)| Some((self.get(imported)?, items)))
+            .flat_map(|(imported, items)| {
+                TypeRegistry::from_flow(&imported.flow).types.into_iter().filter(move |t| items.contains(&t.name))
+            })
+            .collect()
+    }
+}
+
+struct Loader<'r> {
+    read: &'r mut dyn FnMut(&Path) -> Result<Flow>,
+    /// Files being loaded, to report cycles
+    stack: Vec<PathBuf>,
+    /// Module name of every file already loaded
+    loaded: HashMap<PathBuf, String>,
+    modules: Vec<FlowModule>,
+}
+
+impl Loader<'_> {
+    /// Load a flow file and its imports, returning its module name
+    fn visit(&mut self, path: &Path) -> Result<String> {
+        if let Some(start) = self.stack.iter().position(|p| p == path) {
+            let cycle: Vec<String> = self.stack[start..].iter()
+                .chain(std::iter::once(&path.to_path_buf()))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(anyhow!("Import cycle: {}", cycle.join(" -> ")));
+        }
+        if let Some(name) = self.loaded.get(path) {
+            return Ok(name.clone());
+        }
+
+        let flow = (self.read)(path).map_err(|e| anyhow!("Failed to load {}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        self.stack.push(path.to_path_buf());
+        let mut imports = Vec::new();
+        for import in &flow.modules {
+            let module = self.visit(&normalize(&dir.join(&import.path)))?;
+            self.check_items(path, &module, &import.items)?;
+            imports.push((module, import.items.clone()));
+        }
+        // Subflow nodes packaging another flow file import it implicitly
+        let mut subflows: Vec<(String, String)> = Vec::new();
+        for node in flow.nodes.iter().filter(|n| n.plugin_type == "subflow") {
+            let Some(source) = subflow::source_property(node) else { continue };
+            let module = self.visit(&normalize(&dir.join(source)))?;
+            let packaged = self.modules.iter().find(|m| m.name == module).map(|m| &m.flow)
+                .ok_or_else(|| anyhow!("Import cycle through subflow '{}'", node.id))?;
+            let function = subflow::SubflowSignature::of(subflow::packaged_function(packaged, subflow::function_property(node))?).function;
+            if function == "main" {
+                return Err(anyhow!("Subflow '{}' cannot package main", node.id));
+            }
+            // Inlined subflows copy the body instead of calling the function
+            if node.properties.get("mode").and_then(|v| v.as_str()) != Some("inline") {
+                match imports.iter_mut().find(|(name, _)| *name == module) {
+                    Some((_, items)) if items.contains(&function) => {}
+                    Some((_, items)) => items.push(function),
+                    None => imports.push((module.clone(), vec![function])),
+                }
+            }
+            subflows.push((source.to_string(), module));
+        }
+        self.stack.pop();
+
+        let name = module_name(path);
+        if let Some(other) = self.modules.iter().find(|m| m.name == name) {
+            return Err(anyhow!(
+                "{} and {} would both become module '{}'",
+                other.path.display(), path.display(), name
+            ));
+        }
+        self.loaded.insert(path.to_path_buf(), name.clone());
+        self.modules.push(FlowModule { name: name.clone(), path: path.to_path_buf(), flow, imports, subflows });
+        Ok(name)
+    }
+
+    /// Imported names must be function-definitions or types of the imported flow
+    fn check_items(&self, importer: &Path, module: &str, items: &[String]) -> Result<()> {
+        let Some(imported) = self.modules.iter().find(|m| m.name == module) else { return Ok(()) };
+        let types = TypeRegistry::from_flow(&imported.flow);
+        for item in items {
+            let is_function = imported.flow.nodes.iter().any(|n| {
+                n.plugin_type == "function-definition"
+                    && n.properties.get("function_name").and_then(|v| v.as_str()) == Some(item.as_str())
+            });
+            if item == "main" || (!is_function && types.get(item).is_none()) {
+                return Err(anyhow!(
+                    "{} imports '{}', which {} does not define",
+                    importer.display(), item, imported.path.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `sensors.flow.json` -> `sensors`, sanitized into a Rust identifier
+fn module_name(path: &Path) -> String {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("module");
+    let stem = file_name.split('.').next().unwrap_or(file_name);
+    let mut name: String = stem.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    // `main` and `lib` are the crate roots, and keywords are not valid module names
+    if name == "main" || name == "lib" || name == "_" || RUST_KEYWORDS.contains(&name.as_str()) {
+        name.push_str("_flow");
+    }
+    name
+}
+
+/// Strict and reserved keywords of the 2024 edition; `r#` cannot escape `crate`, `self`, `super` or `Self`
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct",
+    "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Resolve `.` and `..` without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::function;
+    use crate::ir::ModuleImport;
+    use serde_json::json;
+
+    fn flow(functions: &[&str], imports: &[(&str, &[&str])]) -> Flow {
+        Flow {
+            nodes: functions.iter().map(|f| function(f, json!({}))).collect(),
+            modules: imports.iter()
+                .map(|(path, items)| ModuleImport {
+                    path: path.to_string(),
+                    items: items.iter().map(|i| i.to_string()).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn load(files: &HashMap<&str, Flow>) -> Result<ModuleGraph> {
+        ModuleGraph::load_with(Path::new("app/main.flow.json"), |path| {
+            files.get(path.to_str().unwrap()).cloned().ok_or_else(|| anyhow!("not found"))
+        })
+    }
+
+    #[test]
+    fn test_import_graph_and_cycles() {
+        let mut files = HashMap::new();
+        files.insert("app/main.flow.json", flow(&["main"], &[("lib/sensors.flow.json", &["read"]), ("./util.flow.json", &["clamp"])]));
+        files.insert("app/lib/sensors.flow.json", flow(&["read"], &[("../util.flow.json", &["clamp"])]));
+        files.insert("app/util.flow.json", flow(&["clamp"], &[]));
+
+        let graph = load(&files).unwrap();
+        let names: Vec<&str> = graph.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["util", "sensors", "main_flow"]);
+        assert_eq!(graph.exports("util"), vec!["clamp"]);
+
+        files.insert("app/util.flow.json", flow(&["clamp"], &[("main.flow.json", &["main"])]));
+        let error = load(&files).unwrap_err().to_string();
+        assert_eq!(error, "Import cycle: app/main.flow.json -> app/lib/sensors.flow.json -> app/util.flow.json -> app/main.flow.json");
+        assert_eq!(module_name(Path::new("lib/mod.flow.json")), "mod_flow");
+        assert_eq!(module_name(Path::new("type.flow.json")), "type_flow");
+    }
+}
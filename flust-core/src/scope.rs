@@ -0,0 +1,443 @@
+use crate::diagnostics::Diagnostic;
+use crate::ir::{Connection, Flow, Node};
+use crate::stdlib::is_expression_node;
+use crate::topological_sort::TopologicalSort;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Variable binding visible at some point of a function body
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    pub mutable: bool,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    /// Declaring node, `None` for function arguments
+    pub declared_by: Option<String>,
+    /// False for a `let x;` not assigned yet on this path
+    pub initialized: bool,
+}
+
+/// Scope analysis - knows which variables each node can see inside its function
+/// Flags use-before-declare, use-before-initialize, shadowing and reassignment of immutable bindings
+#[derive(Debug, Default, Serialize)]
+pub struct ScopeAnalysis {
+    visible: HashMap<String, Vec<Variable>>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ScopeAnalysis {
+    pub fn analyze(flow: &Flow) -> Result<Self> {
+        let mut nodes_by_parent: HashMap<Option<&str>, Vec<&Node>> = HashMap::new();
+        for node in &flow.nodes {
+            nodes_by_parent.entry(node.parent_id.as_deref()).or_default().push(node);
+        }
+
+        let mut walker = ScopeWalker {
+            connections: &flow.connections,
+            nodes_by_parent: &nodes_by_parent,
+            declared_in_function: HashSet::new(),
+            analysis: ScopeAnalysis::default(),
+        };
+
+        let function_defs = flow.nodes.iter().filter(|n| n.plugin_type == "function-definition");
+        let mut has_main = false;
+        for func_def in function_defs {
+            has_main |= func_def.properties.get("function_name").and_then(|v| v.as_str()) == Some("main");
+            walker.declared_in_function = walker.declarations_below(&func_def.id);
+            let children = walker.children(Some(&func_def.id));
+            walker.walk_scope(&children, function_arguments(func_def))?;
+        }
+
+        // Without a main container the root nodes form the body of main
+        if !has_main {
+            let roots: Vec<&Node> = walker.children(None)
+                .into_iter()
+                .filter(|n| n.plugin_type != "function-definition")
+                .collect();
+            walker.declared_in_function = roots.iter()
+                .flat_map(|n| declarations(n))
+                .map(|v| v.name)
+                .collect();
+            walker.walk_scope(&roots, Vec::new())?;
+        }
+
+        Ok(walker.analysis)
+    }
+
+    /// Variables in scope when the node runs (empty for unknown nodes)
+    pub fn visible_at(&self, node_id: &str) -> &[Variable] {
+        self.visible.get(node_id).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+}
+
+struct ScopeWalker<'a> {
+    connections: &'a [Connection],
+    nodes_by_parent: &'a HashMap<Option<&'a str>, Vec<&'a Node>>,
+    /// Every name declared somewhere in the function being walked
+    declared_in_function: HashSet<String>,
+    analysis: ScopeAnalysis,
+}
+
+impl<'a> ScopeWalker<'a> {
+    fn children(&self, parent_id: Option<&str>) -> Vec<&'a Node> {
+        self.nodes_by_parent.get(&parent_id).cloned().unwrap_or_default()
+    }
+
+    fn declarations_below(&self, parent_id: &str) -> HashSet<String> {
+        let mut names = HashSet::new();
+        for child in self.children(Some(parent_id)) {
+            names.extend(declarations(child).into_iter().map(|v| v.name));
+            names.extend(self.declarations_below(&child.id));
+        }
+        names
+    }
+
+    fn walk_scope(&mut self, nodes: &[&'a Node], mut env: Vec<Variable>) -> Result<()> {
+        let ids: HashSet<&str> = nodes.iter().map(|n| n.id.as_str()).collect();
+        let sub_flow = Flow {
+            nodes: nodes.iter().map(|&n| n.clone()).collect(),
+            connections: self.connections.iter()
+                .filter(|c| ids.contains(c.from.as_str()) && ids.contains(c.to.as_str()))
+                .cloned()
+                .collect(),
+            imports: Vec::new(),
+            modules: Vec::new(),
+        };
+        let by_id: HashMap<&str, &Node> = nodes.iter().map(|n| (n.id.as_str(), *n)).collect();
+
+        for node_id in TopologicalSort::sort(&sub_flow)? {
+            let node = by_id[node_id.as_str()];
+            self.analysis.visible.insert(node.id.clone(), env.clone());
+            self.check_uses(node, &mut env);
+
+            if node.plugin_type == "channel-receive" {
+                let mut inner = env.clone();
+                inner.push(Variable {
+                    name: str_property(node, "message_variable").unwrap_or("msg").to_string(),
+                    mutable: false,
+                    type_: None,
+                    declared_by: Some(node.id.clone()),
+                    initialized: true,
+                });
+                let children = self.children(Some(&node.id));
+                self.walk_scope(&children, inner)?;
+            }
+
+            for variable in declarations(node) {
+                if env.iter().any(|v| v.name == variable.name) {
+                    self.analysis.diagnostics.push(Diagnostic::warning(
+                        &node.id,
+                        format!("'{}' shadows an existing binding", variable.name),
+                    ));
+                }
+                env.push(variable);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_uses(&mut self, node: &Node, env: &mut [Variable]) {
+        // Arguments mapped on incoming connections are read by the call
+        let mapped = self.connections.iter()
+            .filter(|c| c.to == node.id)
+            .filter_map(|c| c.variable_mapping.as_ref())
+            .flat_map(|mapping| mapping.values())
+            .map(|value| value.trim())
+            .filter(|value| is_identifier(value))
+            .map(String::from);
+
+        for name in uses(node).into_iter().chain(mapped) {
+            match env.iter().rev().find(|v| v.name == name) {
+                None if self.declared_in_function.contains(&name) => {
+                    self.analysis.diagnostics.push(Diagnostic::error(
+                        &node.id,
+                        format!("'{}' is used before it is declared", name),
+                    ));
+                }
+                Some(variable) if !variable.initialized => {
+                    self.analysis.diagnostics.push(Diagnostic::error(
+                        &node.id,
+                        format!("'{}' is used before it is initialized", name),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(target) = assignment_target(node) {
+            match env.iter_mut().rev().find(|v| v.name == target) {
+                // Deferred initialization: the first plain assignment to `let x;` is allowed,
+                // a compound one (`x += 1`) reads the unset value
+                Some(variable) if !variable.initialized => {
+                    if str_property(node, "operator").unwrap_or("=") == "=" {
+                        variable.initialized = true;
+                    } else {
+                        self.analysis.diagnostics.push(Diagnostic::error(
+                            &node.id,
+                            format!("'{}' is used before it is initialized", target),
+                        ));
+                    }
+                }
+                Some(variable) if !variable.mutable => {
+                    self.analysis.diagnostics.push(Diagnostic::error(
+                        &node.id,
+                        format!("cannot assign twice to immutable variable '{}'", target),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    self.analysis.diagnostics.push(Diagnostic::error(
+                        &node.id,
+                        format!("cannot assign to undeclared variable '{}'", target),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn str_property<'n>(node: &'n Node, name: &str) -> Option<&'n str> {
+    node.properties.get(name)
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+}
+
+fn bool_property(node: &Node, name: &str, default: bool) -> bool {
+    node.properties.get(name)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(default)
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && !matches!(text, "true" | "false" | "self" | "None")
+}
+
+fn function_arguments(func_def: &Node) -> Vec<Variable> {
+    func_def.properties.get("arguments")
+        .and_then(|v| v.as_array())
+        .map(|args| {
+            args.iter()
+                .filter_map(|arg| {
+                    let name = arg.get("name").and_then(|v| v.as_str())?;
+                    let (mutable, name) = match name.strip_prefix("mut ") {
+                        Some(rest) => (true, rest.trim()),
+                        None => (false, name),
+                    };
+                    Some(Variable {
+                        name: name.to_string(),
+                        mutable,
+                        type_: arg.get("type").and_then(|v| v.as_str()).map(String::from),
+                        declared_by: None,
+                        initialized: true,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Bindings a node introduces into the rest of its scope
+fn declarations(node: &Node) -> Vec<Variable> {
+    let declared = |name: &str, mutable: bool, type_: Option<&str>| Variable {
+        name: name.to_string(),
+        mutable,
+        type_: type_.map(String::from),
+        declared_by: Some(node.id.clone()),
+        initialized: true,
+    };
+
+    match node.plugin_type.as_str() {
+        "let-variable" => str_property(node, "variable_name")
+            .map(|name| vec![Variable {
+                initialized: str_property(node, "value").is_some(),
+                ..declared(name, bool_property(node, "is_mutable", false), str_property(node, "variable_type"))
+            }])
+            .unwrap_or_default(),
+        "call-function" if bool_property(node, "declare_variable", true) => str_property(node, "return_variable")
+            .map(|name| vec![declared(name, bool_property(node, "is_mutable", false), str_property(node, "return_type"))])
+            .unwrap_or_default(),
+        "channel" => str_property(node, "channel_name")
+            .map(|name| vec![
+                declared(&format!("{}_tx", name), false, None),
+                declared(&format!("{}_rx", name), true, None),
+            ])
+            .unwrap_or_default(),
+        "subflow" => str_property(node, "output_variable")
+            .map(|name| vec![declared(name, false, None)])
+            .unwrap_or_default(),
+        // Without an output_variable the value is inlined or bound to a generated name
+        _ if is_expression_node(node) => str_property(node, "output_variable")
+            .map(|name| vec![declared(name, false, None)])
+            .unwrap_or_default(),
+        "legacy-code" | "legacy_code" => str_property(node, "code")
+            .map(|code| legacy_declarations(code).into_iter().map(|(name, mutable)| declared(&name, mutable, None)).collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Names a node declares or assigns, holding their new values once it has run
+/// A `let x;` without a value is left out: its binding cannot be read yet
+pub fn written_variables(node: &Node) -> Vec<String> {
+    let mut names: Vec<String> = declarations(node).into_iter().filter(|v| v.initialized).map(|v| v.name).collect();
+    names.extend(assignment_target(node).map(String::from));
+    names
+}
+
+/// Best-effort scan for `let [mut] name` at the top level of a legacy code block
+fn legacy_declarations(code: &str) -> Vec<(String, bool)> {
+    let mut found = Vec::new();
+    let mut depth = 0i32;
+    for line in code.lines() {
+        let trimmed = line.trim_start();
+        if depth == 0
+            && let Some(rest) = trimmed.strip_prefix("let ")
+        {
+            let (mutable, rest) = match rest.trim_start().strip_prefix("mut ") {
+                Some(rest) => (true, rest),
+                None => (false, rest),
+            };
+            let name: String = rest.trim_start()
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            if is_identifier(&name) {
+                found.push((name, mutable));
+            }
+        }
+        depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+    }
+    found
+}
+
+/// Plain variable names a node reads
+fn uses(node: &Node) -> Vec<String> {
+    let candidates: Vec<&str> = match node.plugin_type.as_str() {
+        "debug" => str_property(node, "variable").into_iter().collect(),
+        "let-variable" | "assign-variable" | "channel-send" => str_property(node, "value").into_iter().collect(),
+        _ => Vec::new(),
+    };
+    candidates.into_iter()
+        .map(str::trim)
+        .filter(|s| is_identifier(s))
+        .map(String::from)
+        .collect()
+}
+
+/// Existing variable a node writes to
+fn assignment_target(node: &Node) -> Option<&str> {
+    match node.plugin_type.as_str() {
+        "assign-variable" => str_property(node, "variable_name"),
+        "call-function" if !bool_property(node, "declare_variable", true) => str_property(node, "return_variable"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{connection, node};
+    use serde_json::json;
+
+    #[test]
+    fn test_visible_variables_and_diagnostics() {
+        let flow = Flow {
+            nodes: vec![
+                node("f", "function-definition", None, json!({
+                    "function_name": "main",
+                    "arguments": [{"name": "limit", "type": "i32"}]
+                })),
+                node("print_early", "debug", Some("f"), json!({"variable": "total"})),
+                node("decl", "let-variable", Some("f"), json!({"variable_name": "total", "value": "0"})),
+                node("assign", "assign-variable", Some("f"), json!({"variable_name": "total", "value": "limit"})),
+                node("shadow", "let-variable", Some("f"), json!({"variable_name": "limit", "value": "1"})),
+                node("pending", "let-variable", Some("f"), json!({"variable_name": "count", "variable_type": "i32", "is_mutable": true})),
+                node("bump", "assign-variable", Some("f"), json!({"variable_name": "count", "operator": "+=", "value": "1"})),
+            ],
+            connections: vec![
+                connection("print_early", "decl"),
+                connection("decl", "assign"),
+                connection("assign", "shadow"),
+                connection("shadow", "pending"),
+                connection("pending", "bump"),
+            ],
+            ..Default::default()
+        };
+
+        let analysis = ScopeAnalysis::analyze(&flow).unwrap();
+
+        let names: Vec<&str> = analysis.visible_at("assign").iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["limit", "total"]);
+
+        let messages: Vec<(&str, &str)> = analysis.diagnostics.iter()
+            .map(|d| (d.node_id.as_deref().unwrap(), d.message.as_str()))
+            .collect();
+        assert_eq!(messages, vec![
+            ("print_early", "'total' is used before it is declared"),
+            ("assign", "cannot assign twice to immutable variable 'total'"),
+            ("shadow", "'limit' shadows an existing binding"),
+            ("bump", "'count' is used before it is initialized"),
+        ]);
+    }
+
+    #[test]
+    fn test_deferred_initialization() {
+        let flow = Flow {
+            nodes: vec![
+                node("decl", "let-variable", None, json!({"variable_name": "x", "variable_type": "i32"})),
+                node("early", "debug", None, json!({"variable": "x"})),
+                node("init", "assign-variable", None, json!({"variable_name": "x", "value": "1"})),
+                node("again", "assign-variable", None, json!({"variable_name": "x", "value": "2"})),
+            ],
+            connections: vec![connection("decl", "early"), connection("early", "init"), connection("init", "again")],
+            ..Default::default()
+        };
+
+        let analysis = ScopeAnalysis::analyze(&flow).unwrap();
+
+        assert!(!analysis.visible_at("init")[0].initialized);
+        assert!(analysis.visible_at("again")[0].initialized);
+        assert!(written_variables(&flow.nodes[0]).is_empty());
+        assert_eq!(written_variables(&flow.nodes[2]), vec!["x"]);
+        let messages: Vec<(&str, &str)> = analysis.diagnostics.iter()
+            .map(|d| (d.node_id.as_deref().unwrap(), d.message.as_str()))
+            .collect();
+        assert_eq!(messages, vec![
+            ("early", "'x' is used before it is initialized"),
+            ("again", "cannot assign twice to immutable variable 'x'"),
+        ]);
+    }
+
+    #[test]
+    fn test_expression_nodes_declare_their_output() {
+        let flow = Flow {
+            nodes: vec![
+                node("sum", "add", None, json!({"a": "1", "b": "2", "output_variable": "total"})),
+                node("inlined", "multiply", None, json!({"a": "total", "b": "2"})),
+                node("show", "debug", None, json!({"variable": "total"})),
+            ],
+            connections: vec![connection("sum", "inlined"), connection("inlined", "show")],
+            ..Default::default()
+        };
+
+        let analysis = ScopeAnalysis::analyze(&flow).unwrap();
+
+        let names: Vec<&str> = analysis.visible_at("show").iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["total"]);
+        assert!(analysis.diagnostics.is_empty());
+        assert_eq!(written_variables(&flow.nodes[0]), vec!["total"]);
+        assert!(written_variables(&flow.nodes[1]).is_empty());
+    }
+
+    #[test]
+    fn test_legacy_code_declarations() {
+        let code = "let a = 1;\nlet mut b = 2;\nif a > 0 {\n    let inner = 3;\n}";
+        assert_eq!(legacy_declarations(code), vec![("a".to_string(), false), ("b".to_string(), true)]);
+    }
+}
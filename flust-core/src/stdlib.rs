@@ -0,0 +1,133 @@
+use crate::ir::Node;
+use serde::{Deserialize, Serialize};
+
+/// Type carried by a node port
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PortType {
+    Number,
+    Bool,
+    String,
+    Vec,
+    Map,
+    Any,
+}
+
+impl PortType {
+    /// Whether a value of type `self` can feed a port of type `target`
+    pub fn accepts(&self, target: PortType) -> bool {
+        *self == PortType::Any || target == PortType::Any || *self == target
+    }
+}
+
+/// Input port of a built-in node; unconnected inputs fall back to the property of the same name
+#[derive(Debug, Clone, Copy)]
+pub struct PortSpec {
+    pub name: &'static str,
+    pub port_type: PortType,
+    /// Optional inputs may be left unconnected and unset
+    pub optional: bool,
+}
+
+/// Ports of a built-in node
+#[derive(Debug, Clone, Copy)]
+pub struct NodeSignature {
+    pub inputs: &'static [PortSpec],
+    /// `None` for statement nodes, which act on their inputs instead of producing a value
+    pub output: Option<PortType>,
+}
+
+/// Name of the single output port of expression nodes
+pub const OUTPUT_PORT: &str = "result";
+
+const fn port(name: &'static str, port_type: PortType) -> PortSpec {
+    PortSpec { name, port_type, optional: false }
+}
+
+const fn optional(name: &'static str, port_type: PortType) -> PortSpec {
+    PortSpec { name, port_type, optional: true }
+}
+
+const NONE: &[PortSpec] = &[];
+const NUMBER_PAIR: &[PortSpec] = &[port("a", PortType::Number), port("b", PortType::Number)];
+const BOOL_PAIR: &[PortSpec] = &[port("a", PortType::Bool), port("b", PortType::Bool)];
+const ANY_PAIR: &[PortSpec] = &[port("a", PortType::Any), port("b", PortType::Any)];
+const BOOL_VALUE: &[PortSpec] = &[port("value", PortType::Bool)];
+const ANY_VALUE: &[PortSpec] = &[port("value", PortType::Any)];
+const CLAMP_INPUTS: &[PortSpec] = &[
+    port("value", PortType::Number),
+    port("min", PortType::Number),
+    port("max", PortType::Number),
+];
+const FORMAT_INPUTS: &[PortSpec] = &[optional("value", PortType::Any)];
+const TEXT: &[PortSpec] = &[port("text", PortType::String)];
+const SPLIT_INPUTS: &[PortSpec] = &[port("text", PortType::String), port("separator", PortType::String)];
+const JOIN_INPUTS: &[PortSpec] = &[port("list", PortType::Vec), port("separator", PortType::String)];
+const COLLECTION: &[PortSpec] = &[port("collection", PortType::Any)];
+const LIST: &[PortSpec] = &[port("list", PortType::Vec)];
+const PUSH_INPUTS: &[PortSpec] = &[port("list", PortType::Vec), port("value", PortType::Any)];
+const VEC_GET_INPUTS: &[PortSpec] = &[port("list", PortType::Vec), port("index", PortType::Number)];
+const MAP_GET_INPUTS: &[PortSpec] = &[port("map", PortType::Map), port("key", PortType::Any)];
+const MAP_INSERT_INPUTS: &[PortSpec] = &[
+    port("map", PortType::Map),
+    port("key", PortType::Any),
+    port("value", PortType::Any),
+];
+const CONDITION: &[PortSpec] = &[port("condition", PortType::Bool)];
+const ASSERT_EQ_INPUTS: &[PortSpec] = &[port("left", PortType::Any), port("right", PortType::Any)];
+
+/// Signature of a built-in node, `None` for nodes without typed ports
+pub fn signature(plugin_type: &str) -> Option<NodeSignature> {
+    let (inputs, output) = match plugin_type {
+        // Arithmetic, comparison and logic
+        "add" | "subtract" | "multiply" | "divide" | "modulo" | "min" | "max" => (NUMBER_PAIR, Some(PortType::Number)),
+        "less-than" | "less-equal" | "greater-than" | "greater-equal" => (NUMBER_PAIR, Some(PortType::Bool)),
+        "equal" | "not-equal" => (ANY_PAIR, Some(PortType::Bool)),
+        "and" | "or" => (BOOL_PAIR, Some(PortType::Bool)),
+        "not" => (BOOL_VALUE, Some(PortType::Bool)),
+        "clamp" => (CLAMP_INPUTS, Some(PortType::Number)),
+        "cast" => (ANY_VALUE, Some(PortType::Any)),
+        // Strings
+        "format-string" => (FORMAT_INPUTS, Some(PortType::String)),
+        "split" => (SPLIT_INPUTS, Some(PortType::Vec)),
+        "join" => (JOIN_INPUTS, Some(PortType::String)),
+        "trim" => (TEXT, Some(PortType::String)),
+        "parse" => (TEXT, Some(PortType::Any)),
+        // Collections
+        "len" => (COLLECTION, Some(PortType::Number)),
+        "vec-new" => (NONE, Some(PortType::Vec)),
+        "map-new" => (NONE, Some(PortType::Map)),
+        "vec-get" => (VEC_GET_INPUTS, Some(PortType::Any)),
+        "map-get" => (MAP_GET_INPUTS, Some(PortType::Any)),
+        "vec-map" | "vec-filter" | "vec-sort" => (LIST, Some(PortType::Vec)),
+        "push" => (PUSH_INPUTS, None),
+        "map-insert" => (MAP_INSERT_INPUTS, None),
+        // User-defined types; construct ports are the fields of the constructed type
+        "construct" => (NONE, Some(PortType::Any)),
+        "field-access" => (ANY_VALUE, Some(PortType::Any)),
+        // Assertions
+        "assert" | "debug-assert" => (CONDITION, None),
+        "assert-eq" => (ASSERT_EQ_INPUTS, None),
+        _ => return None,
+    };
+    Some(NodeSignature { inputs, output })
+}
+
+/// Built-in node whose output is a value, bound to a variable unless inlined
+pub fn is_expression_node(node: &Node) -> bool {
+    signature(&node.plugin_type).is_some_and(|s| s.output.is_some())
+}
+
+/// Port type of a Rust type name, as far as the built-in nodes care
+pub fn port_type_of(rust_type: &str) -> PortType {
+    let rust_type = rust_type.trim();
+    match rust_type {
+        "bool" => PortType::Bool,
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize"
+        | "f32" | "f64" => PortType::Number,
+        "String" | "&str" | "&String" => PortType::String,
+        _ if rust_type.starts_with("Vec<") => PortType::Vec,
+        _ if rust_type.starts_with("HashMap<") || rust_type.starts_with("BTreeMap<") => PortType::Map,
+        _ => PortType::Any,
+    }
+}
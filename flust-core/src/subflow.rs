@@ -0,0 +1,104 @@
+use crate::ir::{Flow, Node};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+/// Input of a subflow node, taken from an argument of the packaged function
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SubflowInput {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+/// Interface of a function-definition packaged as a reusable `subflow` node
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SubflowSignature {
+    pub function: String,
+    pub inputs: Vec<SubflowInput>,
+    /// Declared return type, the subflow's single output
+    pub output: Option<String>,
+    pub fallible: bool,
+}
+
+impl SubflowSignature {
+    pub fn of(func_def: &Node) -> Self {
+        let inputs = func_def.properties.get("arguments")
+            .and_then(|v| v.as_array())
+            .map(|args| args.iter()
+                .map(|arg| SubflowInput {
+                    name: arg.get("name").and_then(|v| v.as_str()).unwrap_or("arg").to_string(),
+                    type_: arg.get("type").and_then(|v| v.as_str()).unwrap_or("i32").to_string(),
+                })
+                .collect())
+            .unwrap_or_default();
+
+        SubflowSignature {
+            function: str_property(func_def, "function_name").unwrap_or("unknown").to_string(),
+            inputs,
+            output: str_property(func_def, "return_type").map(String::from),
+            fallible: func_def.properties.get("fallible").and_then(|v| v.as_bool()).unwrap_or(false),
+        }
+    }
+}
+
+/// Function-definition a subflow node packages from `flow`
+/// Without a name the flow must define exactly one function besides main
+pub fn packaged_function<'f>(flow: &'f Flow, function: Option<&str>) -> Result<&'f Node> {
+    let mut candidates = flow.nodes.iter()
+        .filter(|n| n.plugin_type == "function-definition")
+        .filter(|n| match function {
+            Some(name) => str_property(n, "function_name") == Some(name),
+            None => str_property(n, "function_name") != Some("main"),
+        });
+
+    match (candidates.next(), candidates.next(), function) {
+        (Some(func_def), None, _) | (Some(func_def), _, Some(_)) => Ok(func_def),
+        (None, _, Some(name)) => Err(anyhow!("Subflow function '{}' is not defined", name)),
+        (None, _, None) => Err(anyhow!("Subflow flow defines no function besides main")),
+        (Some(_), Some(_), None) => Err(anyhow!("Subflow flow defines several functions; set 'function' to pick one")),
+    }
+}
+
+/// Function a `subflow` node refers to, if set
+pub fn function_property(node: &Node) -> Option<&str> {
+    str_property(node, "function")
+}
+
+/// Flow file a `subflow` node packages, `None` for functions of the same flow
+pub fn source_property(node: &Node) -> Option<&str> {
+    str_property(node, "source")
+}
+
+fn str_property<'n>(node: &'n Node, name: &str) -> Option<&'n str> {
+    node.properties.get(name)
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::function;
+    use serde_json::json;
+
+    #[test]
+    fn test_signature_of_packaged_function() {
+        let flow = Flow {
+            nodes: vec![
+                function("main", json!({})),
+                function("celsius", json!({
+                    "arguments": [{"name": "fahrenheit", "type": "f64"}],
+                    "return_type": "f64",
+                })),
+            ],
+            ..Default::default()
+        };
+
+        let signature = SubflowSignature::of(packaged_function(&flow, None).unwrap());
+
+        assert_eq!(signature.function, "celsius");
+        assert_eq!(signature.inputs, vec![SubflowInput { name: "fahrenheit".to_string(), type_: "f64".to_string() }]);
+        assert_eq!(signature.output.as_deref(), Some("f64"));
+        assert!(packaged_function(&flow, Some("kelvin")).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::ir::{Flow, Connection};
+use crate::ir::Flow;
 use std::collections::{HashMap, HashSet, VecDeque};
 use anyhow::{Result, anyhow};
 
@@ -38,10 +38,11 @@ impl TopologicalSort {
             in_degree.insert(node_id.clone(), dependencies[node_id].len());
         }
         
+        // Seed in declaration order so unconnected nodes keep a stable order
         let mut queue: VecDeque<String> = VecDeque::new();
-        for (node_id, &degree) in &in_degree {
-            if degree == 0 {
-                queue.push_back(node_id.clone());
+        for node in &flow.nodes {
+            if in_degree[&node.id] == 0 && !queue.contains(&node.id) {
+                queue.push_back(node.id.clone());
             }
         }
         
@@ -73,7 +74,7 @@ impl TopologicalSort {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ir::Node;
+    use crate::ir::{Connection, Node};
     
     #[test]
     fn test_simple_sort() {
@@ -84,12 +85,14 @@ mod tests {
                     plugin_type: "legacy-code".to_string(),
                     label: None,
                     properties: HashMap::new(),
+                    parent_id: None,
                 },
                 Node {
                     id: "b".to_string(),
                     plugin_type: "debug".to_string(),
                     label: None,
                     properties: HashMap::new(),
+                    parent_id: None,
                 },
             ],
             connections: vec![
@@ -98,8 +101,11 @@ mod tests {
                     to: "b".to_string(),
                     connection_type: Default::default(),
                     variable_mapping: None,
+                    from_port: None,
+                    to_port: None,
                 },
             ],
+            ..Default::default()
         };
         
         let sorted = TopologicalSort::sort(&flow).unwrap();
@@ -0,0 +1,393 @@
+use crate::diagnostics::Diagnostic;
+use crate::ir::{Flow, Node};
+use crate::stdlib::{self, PortType};
+use crate::subflow::{self, SubflowSignature};
+use crate::types::{Field, TypeDefinition, TypeKind, TypeRegistry, VariantShape, split_top_level};
+use std::collections::HashMap;
+
+/// Type checker for values flowing between node ports
+/// Follows Single Responsibility Principle - only reports type problems, never rewrites the flow
+pub struct TypeChecker;
+
+impl TypeChecker {
+    pub fn check(flow: &Flow) -> Vec<Diagnostic> {
+        Self::check_with_imports(flow, Vec::new())
+    }
+
+    /// Check a flow of a multi-file program, knowing the types it imports
+    pub fn check_with_imports(flow: &Flow, imported: Vec<TypeDefinition>) -> Vec<Diagnostic> {
+        let node_map: HashMap<&str, &Node> = flow.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+        let mut registry = TypeRegistry::from_flow(flow);
+        registry.imported = imported;
+        let types = FlowTypes { flow, node_map: &node_map, registry: &registry };
+        let mut diagnostics = registry.diagnostics.clone();
+
+        for conn in &flow.connections {
+            let Some(to_port) = conn.to_port.as_deref() else { continue };
+            let (Some(source), Some(target)) = (node_map.get(conn.from.as_str()), node_map.get(conn.to.as_str())) else {
+                continue;
+            };
+            if target.plugin_type == "construct" {
+                // Unknown types are reported once by check_construct
+                let Some(fields) = types.constructed_fields(target) else { continue };
+                match fields.iter().find(|f| f.name == to_port) {
+                    Some(field) => {
+                        let (field_type, value_type) = (stdlib::port_type_of(&field.type_), types.value_type(source));
+                        if !value_type.accepts(field_type) {
+                            diagnostics.push(Diagnostic::error(
+                                &target.id,
+                                format!(
+                                    "field '{}' expects {}, got {} from '{}'",
+                                    to_port, type_name(field_type), type_name(value_type), source.id
+                                ),
+                            ));
+                        }
+                    }
+                    None => diagnostics.push(Diagnostic::error(
+                        &target.id,
+                        format!("'{}' has no field '{}'", str_property(target, "type_name"), to_port),
+                    )),
+                }
+                continue;
+            }
+            if target.plugin_type == "subflow" {
+                let Some(signature) = types.local_subflow(target) else { continue };
+                match signature.inputs.iter().find(|i| i.name == to_port) {
+                    Some(input) => {
+                        let (input_type, value_type) = (stdlib::port_type_of(&input.type_), types.value_type(source));
+                        if !value_type.accepts(input_type) {
+                            diagnostics.push(Diagnostic::error(
+                                &target.id,
+                                format!(
+                                    "input '{}' of '{}' expects {}, got {} from '{}'",
+                                    to_port, signature.function, type_name(input_type), type_name(value_type), source.id
+                                ),
+                            ));
+                        }
+                    }
+                    None => diagnostics.push(Diagnostic::error(
+                        &target.id,
+                        format!("'{}' has no argument '{}'", signature.function, to_port),
+                    )),
+                }
+                continue;
+            }
+            let Some(signature) = stdlib::signature(&target.plugin_type) else { continue };
+
+            match signature.inputs.iter().find(|p| p.name == to_port) {
+                Some(port) => {
+                    let value_type = types.value_type(source);
+                    if !value_type.accepts(port.port_type) {
+                        diagnostics.push(Diagnostic::error(
+                            &target.id,
+                            format!(
+                                "input '{}' of '{}' expects {}, got {} from '{}'",
+                                to_port, target.plugin_type, type_name(port.port_type), type_name(value_type), source.id
+                            ),
+                        ));
+                    }
+                }
+                None => diagnostics.push(Diagnostic::error(
+                    &target.id,
+                    format!("'{}' has no input port '{}'", target.plugin_type, to_port),
+                )),
+            }
+        }
+
+        for node in &flow.nodes {
+            let Some(signature) = stdlib::signature(&node.plugin_type) else { continue };
+            for input in signature.inputs {
+                let connected = flow.connections.iter()
+                    .any(|c| c.to == node.id && c.to_port.as_deref() == Some(input.name));
+                let has_value = node.properties.get(input.name)
+                    .map(|v| !v.is_null() && v.as_str() != Some(""))
+                    .unwrap_or(false);
+                if !connected && !has_value && !input.optional {
+                    diagnostics.push(Diagnostic::error(
+                        &node.id,
+                        format!("input '{}' of '{}' is neither connected nor set", input.name, node.plugin_type),
+                    ));
+                }
+            }
+            match node.plugin_type.as_str() {
+                "subflow" => types.check_subflow(node, &mut diagnostics),
+                "construct" => types.check_construct(node, &mut diagnostics),
+                "field-access" => types.check_field_access(node, &mut diagnostics),
+                _ => {}
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Type of the value a node produces for downstream ports
+    pub fn output_type(node: &Node) -> PortType {
+        let declared = |name: &str| {
+            node.properties.get(name)
+                .and_then(|v| v.as_str())
+                .map(stdlib::port_type_of)
+                .unwrap_or(PortType::Any)
+        };
+
+        match node.plugin_type.as_str() {
+            "cast" => declared("target_type"),
+            "let-variable" => declared("variable_type"),
+            "call-function" => declared("return_type"),
+            // Without `?` or a default the parse result is still a Result
+            "parse" => match node.properties.get("on_error").and_then(|v| v.as_str()) {
+                Some("propagate") | Some("default") => declared("target_type"),
+                _ => PortType::Any,
+            },
+            other => stdlib::signature(other).and_then(|s| s.output).unwrap_or(PortType::Any),
+        }
+    }
+}
+
+/// Flow-wide view used to follow user-defined types through the graph
+struct FlowTypes<'a> {
+    flow: &'a Flow,
+    node_map: &'a HashMap<&'a str, &'a Node>,
+    registry: &'a TypeRegistry,
+}
+
+impl<'a> FlowTypes<'a> {
+    /// Port type of a node's value, refined through field accesses on known structs
+    fn value_type(&self, node: &Node) -> PortType {
+        match node.plugin_type.as_str() {
+            "field-access" | "subflow" => self.rust_type(node)
+                .map(|t| stdlib::port_type_of(&t))
+                .unwrap_or(PortType::Any),
+            _ => TypeChecker::output_type(node),
+        }
+    }
+
+    /// Rust type of a node's value, when the flow declares it
+    fn rust_type(&self, node: &Node) -> Option<String> {
+        let declared = |name: &str| Some(str_property(node, name)).filter(|s| !s.is_empty()).map(String::from);
+        match node.plugin_type.as_str() {
+            "construct" => declared("type_name"),
+            "let-variable" => declared("variable_type"),
+            "call-function" => declared("return_type"),
+            "cast" | "parse" => declared("target_type"),
+            "subflow" => self.local_subflow(node)?.output,
+            "field-access" => {
+                let owner = self.rust_type(self.input(node, "value")?)?;
+                let field = self.registry.get(&owner)?.field(str_property(node, "field"))?;
+                Some(field.type_.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Signature of a subflow packaging a function of this flow; other flow files are checked on their own
+    fn local_subflow(&self, node: &Node) -> Option<SubflowSignature> {
+        if subflow::source_property(node).is_some() {
+            return None;
+        }
+        subflow::packaged_function(self.flow, subflow::function_property(node))
+            .ok()
+            .map(SubflowSignature::of)
+    }
+
+    fn check_subflow(&self, node: &Node, diagnostics: &mut Vec<Diagnostic>) {
+        if subflow::source_property(node).is_some() {
+            return;
+        }
+        let signature = match subflow::packaged_function(self.flow, subflow::function_property(node)) {
+            Ok(func_def) => SubflowSignature::of(func_def),
+            Err(error) => return diagnostics.push(Diagnostic::error(&node.id, error.to_string())),
+        };
+        for input in &signature.inputs {
+            let connected = self.input(node, &input.name).is_some();
+            let has_value = node.properties.get(&input.name)
+                .map(|v| !v.is_null() && v.as_str() != Some(""))
+                .unwrap_or(false);
+            if !connected && !has_value {
+                diagnostics.push(Diagnostic::error(
+                    &node.id,
+                    format!("argument '{}' of '{}' is neither connected nor set", input.name, signature.function),
+                ));
+            }
+        }
+    }
+
+    /// Node wired into the given input port
+    fn input(&self, node: &Node, port: &str) -> Option<&'a Node> {
+        let conn = self.flow.connections.iter()
+            .find(|c| c.to == node.id && c.to_port.as_deref() == Some(port))?;
+        self.node_map.get(conn.from.as_str()).copied()
+    }
+
+    /// Named fields a construct node fills, `None` when the type or variant is unknown or positional
+    fn constructed_fields(&self, node: &Node) -> Option<&'a [Field]> {
+        let definition = self.registry.get(str_property(node, "type_name"))?;
+        match &definition.kind {
+            TypeKind::Struct(fields) => Some(fields),
+            TypeKind::Enum(_) => match &definition.variant(str_property(node, "variant"))?.shape {
+                VariantShape::Struct(fields) => Some(fields),
+                _ => None,
+            },
+        }
+    }
+
+    fn check_construct(&self, node: &Node, diagnostics: &mut Vec<Diagnostic>) {
+        let type_name = str_property(node, "type_name");
+        let Some(definition) = self.registry.get(type_name) else {
+            diagnostics.push(Diagnostic::error(&node.id, format!("unknown type '{}'", type_name)));
+            return;
+        };
+        let values = split_top_level(str_property(node, "values"));
+
+        if let TypeKind::Enum(_) = definition.kind {
+            let variant_name = str_property(node, "variant");
+            let Some(variant) = definition.variant(variant_name) else {
+                diagnostics.push(Diagnostic::error(
+                    &node.id,
+                    format!("enum '{}' has no variant '{}'", definition.name, variant_name),
+                ));
+                return;
+            };
+            let expected = match &variant.shape {
+                VariantShape::Unit => 0,
+                VariantShape::Tuple(types) => types.len(),
+                VariantShape::Struct(_) => return self.check_fields(node, definition, &values, diagnostics),
+            };
+            if values.len() != expected {
+                diagnostics.push(Diagnostic::error(
+                    &node.id,
+                    format!("'{}::{}' takes {} value(s), got {}", definition.name, variant.name, expected, values.len()),
+                ));
+            }
+            return;
+        }
+
+        self.check_fields(node, definition, &values, diagnostics);
+    }
+
+    /// Every field must be set exactly once, from `values` or a wired port
+    fn check_fields(&self, node: &Node, definition: &TypeDefinition, values: &[&str], diagnostics: &mut Vec<Diagnostic>) {
+        let Some(fields) = self.constructed_fields(node) else { return };
+        let mut provided: Vec<&str> = values.iter()
+            .map(|v| v.split_once(':').map(|(name, _)| name).unwrap_or(v).trim())
+            .collect();
+        for name in &provided {
+            if !fields.iter().any(|f| f.name == *name) {
+                diagnostics.push(Diagnostic::error(&node.id, format!("'{}' has no field '{}'", definition.name, name)));
+            }
+        }
+        // Wired ports were already checked against the fields
+        provided.extend(self.flow.connections.iter()
+            .filter(|c| c.to == node.id)
+            .filter_map(|c| c.to_port.as_deref()));
+
+        for field in fields {
+            if !provided.contains(&field.name.as_str()) {
+                diagnostics.push(Diagnostic::error(
+                    &node.id,
+                    format!("missing field '{}' of '{}'", field.name, definition.name),
+                ));
+            }
+        }
+    }
+
+    fn check_field_access(&self, node: &Node, diagnostics: &mut Vec<Diagnostic>) {
+        let field = str_property(node, "field");
+        if field.is_empty() {
+            diagnostics.push(Diagnostic::error(&node.id, "field access needs a field name"));
+            return;
+        }
+        let Some(owner) = self.input(node, "value").and_then(|source| self.rust_type(source)) else { return };
+        if let Some(definition) = self.registry.get(&owner)
+            && let TypeKind::Struct(_) = definition.kind
+            && definition.field(field).is_none()
+        {
+            diagnostics.push(Diagnostic::error(&node.id, format!("'{}' has no field '{}'", definition.name, field)));
+        }
+    }
+}
+
+fn str_property<'n>(node: &'n Node, name: &str) -> &'n str {
+    node.properties.get(name).and_then(|v| v.as_str()).unwrap_or("").trim()
+}
+
+fn type_name(port_type: PortType) -> &'static str {
+    match port_type {
+        PortType::Number => "number",
+        PortType::Bool => "bool",
+        PortType::String => "string",
+        PortType::Vec => "vec",
+        PortType::Map => "map",
+        PortType::Any => "any value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{connection, node};
+    use crate::ir::Connection;
+    use serde_json::json;
+
+    fn data(from: &str, to: &str, to_port: &str) -> Connection {
+        Connection {
+            from_port: Some("result".to_string()),
+            to_port: Some(to_port.to_string()),
+            ..connection(from, to)
+        }
+    }
+
+    #[test]
+    fn test_port_type_mismatch() {
+        let flow = Flow {
+            nodes: vec![
+                node("cmp", "greater-than", None, json!({"a": "temperature", "b": "30"})),
+                node("sum", "add", None, json!({"b": "1"})),
+                node("neg", "not", None, json!({})),
+            ],
+            connections: vec![data("cmp", "sum", "a"), data("sum", "neg", "input")],
+            ..Default::default()
+        };
+
+        let messages: Vec<String> = TypeChecker::check(&flow).into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, vec![
+            "input 'a' of 'add' expects number, got bool from 'cmp'".to_string(),
+            "'not' has no input port 'input'".to_string(),
+            "input 'value' of 'not' is neither connected nor set".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_user_defined_types() {
+        let flow = Flow {
+            nodes: vec![
+                node("point_def", "struct-definition", None, json!({"type_name": "Point", "fields": "x: f64\ny: f64\nname: String"})),
+                node("shape_def", "enum-definition", None, json!({"type_name": "Shape", "variants": "Empty\nCircle(f64)"})),
+                node("count", "let-variable", None, json!({"variable_name": "count", "variable_type": "u32", "value": "3"})),
+                node("origin", "construct", None, json!({"type_name": "Point", "values": "x: 0.0, z: 1.0"})),
+                node("x", "field-access", None, json!({"field": "x"})),
+                node("label", "field-access", None, json!({"field": "name"})),
+                node("bad", "field-access", None, json!({"field": "w"})),
+                node("double", "add", None, json!({"b": "1.0"})),
+                node("circle", "construct", None, json!({"type_name": "Shape", "variant": "Circle"})),
+            ],
+            connections: vec![
+                data("origin", "x", "value"),
+                data("origin", "label", "value"),
+                data("origin", "bad", "value"),
+                data("label", "double", "a"),
+                data("count", "origin", "name"),
+            ],
+            ..Default::default()
+        };
+
+        let messages: Vec<String> = TypeChecker::check(&flow).into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, vec![
+            "input 'a' of 'add' expects number, got string from 'label'".to_string(),
+            "field 'name' expects string, got number from 'count'".to_string(),
+            "'Point' has no field 'z'".to_string(),
+            "missing field 'y' of 'Point'".to_string(),
+            "'Point' has no field 'w'".to_string(),
+            "'Shape::Circle' takes 1 value(s), got 0".to_string(),
+        ]);
+    }
+}
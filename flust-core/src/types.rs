@@ -0,0 +1,229 @@
+use crate::diagnostics::Diagnostic;
+use crate::ir::{Flow, Node};
+use serde::Serialize;
+
+/// Named field of a struct or struct-like enum variant
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+/// Payload of an enum variant
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantShape {
+    Unit,
+    Tuple(Vec<String>),
+    Struct(Vec<Field>),
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Variant {
+    pub name: String,
+    pub shape: VariantShape,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeKind {
+    Struct(Vec<Field>),
+    Enum(Vec<Variant>),
+}
+
+/// Type declared by a `struct-definition` or `enum-definition` node
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TypeDefinition {
+    pub name: String,
+    pub node_id: String,
+    pub derives: Vec<String>,
+    pub kind: TypeKind,
+}
+
+impl TypeDefinition {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        match &self.kind {
+            TypeKind::Struct(fields) => fields.iter().find(|f| f.name == name),
+            TypeKind::Enum(_) => None,
+        }
+    }
+
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        match &self.kind {
+            TypeKind::Enum(variants) => variants.iter().find(|v| v.name == name),
+            TypeKind::Struct(_) => None,
+        }
+    }
+}
+
+/// Registry of the types defined inside a flow
+/// Malformed definitions are reported as diagnostics and left out
+#[derive(Debug, Default, Serialize)]
+pub struct TypeRegistry {
+    pub types: Vec<TypeDefinition>,
+    /// Types imported from other flow files, known but not defined here
+    pub imported: Vec<TypeDefinition>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeRegistry {
+    pub fn from_flow(flow: &Flow) -> Self {
+        let mut registry = TypeRegistry::default();
+        for node in &flow.nodes {
+            let definition = match node.plugin_type.as_str() {
+                "struct-definition" => parse_struct(node),
+                "enum-definition" => parse_enum(node),
+                _ => continue,
+            };
+            match definition {
+                Ok(definition) if registry.get(&definition.name).is_some() => registry.diagnostics.push(
+                    Diagnostic::error(&node.id, format!("type '{}' is defined more than once", definition.name)),
+                ),
+                Ok(definition) => registry.types.push(definition),
+                Err(message) => registry.diagnostics.push(Diagnostic::error(&node.id, message)),
+            }
+        }
+        registry
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TypeDefinition> {
+        self.types.iter().chain(&self.imported).find(|t| t.name == name.trim())
+    }
+}
+
+/// Split on commas outside of `<>`, `()`, `[]` and `{}`
+pub fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '<' | '(' | '[' | '{' => depth += 1,
+            '>' | ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(text[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+fn str_property<'n>(node: &'n Node, name: &str) -> &'n str {
+    node.properties.get(name).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn header(node: &Node) -> Result<(String, Vec<String>), String> {
+    let name = str_property(node, "type_name").trim();
+    if !is_identifier(name) {
+        return Err(format!("'{}' is not a valid type name", name));
+    }
+    let derives = split_top_level(str_property(node, "derives")).into_iter().map(String::from).collect();
+    Ok((name.to_string(), derives))
+}
+
+/// `name: Type` entries, one per line or comma separated
+fn parse_fields(text: &str) -> Result<Vec<Field>, String> {
+    let mut fields: Vec<Field> = Vec::new();
+    for entry in text.lines().flat_map(split_top_level) {
+        let (name, type_) = entry.split_once(':')
+            .ok_or_else(|| format!("field '{}' needs a type (name: Type)", entry))?;
+        let (name, type_) = (name.trim().trim_start_matches("pub ").trim(), type_.trim());
+        if !is_identifier(name) || type_.is_empty() {
+            return Err(format!("invalid field '{}'", entry));
+        }
+        if fields.iter().any(|f| f.name == name) {
+            return Err(format!("field '{}' is declared more than once", name));
+        }
+        fields.push(Field { name: name.to_string(), type_: type_.to_string() });
+    }
+    Ok(fields)
+}
+
+fn parse_struct(node: &Node) -> Result<TypeDefinition, String> {
+    let (name, derives) = header(node)?;
+    let fields = parse_fields(str_property(node, "fields"))?;
+    Ok(TypeDefinition { name, node_id: node.id.clone(), derives, kind: TypeKind::Struct(fields) })
+}
+
+/// One variant per line: `Idle`, `Celsius(f64)` or `Moved { x: i32, y: i32 }`
+fn parse_enum(node: &Node) -> Result<TypeDefinition, String> {
+    let (name, derives) = header(node)?;
+    let mut variants: Vec<Variant> = Vec::new();
+    for line in str_property(node, "variants").lines() {
+        let line = line.trim().trim_end_matches(',').trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (variant_name, shape) = match line.find(['(', '{']) {
+            Some(open) if line.ends_with(')') && line[open..].starts_with('(') => {
+                let types = split_top_level(&line[open + 1..line.len() - 1]).into_iter().map(String::from).collect();
+                (line[..open].trim(), VariantShape::Tuple(types))
+            }
+            Some(open) if line.ends_with('}') && line[open..].starts_with('{') => {
+                (line[..open].trim(), VariantShape::Struct(parse_fields(&line[open + 1..line.len() - 1])?))
+            }
+            Some(_) => return Err(format!("invalid variant '{}'", line)),
+            None => (line, VariantShape::Unit),
+        };
+        if !is_identifier(variant_name) {
+            return Err(format!("invalid variant '{}'", line));
+        }
+        if variants.iter().any(|v| v.name == variant_name) {
+            return Err(format!("variant '{}' is declared more than once", variant_name));
+        }
+        variants.push(Variant { name: variant_name.to_string(), shape });
+    }
+    if variants.is_empty() {
+        return Err(format!("enum '{}' has no variants", name));
+    }
+    Ok(TypeDefinition { name, node_id: node.id.clone(), derives, kind: TypeKind::Enum(variants) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::node;
+    use serde_json::json;
+
+    #[test]
+    fn test_struct_and_enum_definitions() {
+        let flow = Flow {
+            nodes: vec![
+                node("reading", "struct-definition", None, json!({
+                    "type_name": "Reading",
+                    "derives": "Debug, Clone",
+                    "fields": "sensor: String\nvalues: HashMap<String, f64>"
+                })),
+                node("event", "enum-definition", None, json!({
+                    "type_name": "Event",
+                    "variants": "Idle\nMeasured(Reading, u64)\nMoved { x: i32, y: i32 }"
+                })),
+                node("again", "struct-definition", None, json!({"type_name": "Reading", "fields": "id: u32"})),
+            ],
+            connections: vec![],
+            ..Default::default()
+        };
+
+        let registry = TypeRegistry::from_flow(&flow);
+
+        let reading = registry.get("Reading").unwrap();
+        assert_eq!(reading.derives, vec!["Debug", "Clone"]);
+        assert_eq!(reading.field("values").unwrap().type_, "HashMap<String, f64>");
+        let event = registry.get("Event").unwrap();
+        assert_eq!(event.variant("Idle").unwrap().shape, VariantShape::Unit);
+        assert_eq!(event.variant("Measured").unwrap().shape, VariantShape::Tuple(vec!["Reading".into(), "u64".into()]));
+        assert!(matches!(&event.variant("Moved").unwrap().shape, VariantShape::Struct(fields) if fields.len() == 2));
+        assert_eq!(registry.diagnostics, vec![Diagnostic::error("again", "type 'Reading' is defined more than once")]);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::locate;
+use anyhow::Result;
+use flust_codegen::generator::{self, Target};
+use flust_core::diagnose;
+use flust_core::diagnostics::Severity;
+use flust_core::ir::Flow;
+use flust_core::modules::ModuleGraph;
+use flust_core::parser;
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range, Url};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Open flow file; `flow` is the last version that parsed, so features keep working while typing
+pub struct Document {
+    pub text: String,
+    pub flow: Option<Flow>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let mut document = Document { text: String::new(), flow: None };
+        document.update(text);
+        document
+    }
+
+    pub fn update(&mut self, text: String) {
+        if let Ok(flow) = parser::parse_str(&text) {
+            self.flow = Some(flow);
+        }
+        self.text = text;
+    }
+}
+
+pub type Documents = HashMap<Url, Document>;
+
+/// File path of a document; unsaved buffers get a path from their URI so imports still resolve
+pub fn path_of(uri: &Url) -> PathBuf {
+    uri.to_file_path().unwrap_or_else(|_| PathBuf::from(uri.path()))
+}
+
+/// Import graph of a document, reading open buffers before the files on disk
+pub fn module_graph(uri: &Url, documents: &Documents) -> Result<ModuleGraph> {
+    ModuleGraph::load_with(&path_of(uri), |path| read(path, documents))
+}
+
+/// Text of a flow file, from its open buffer if any
+pub fn text_of(path: &Path, documents: &Documents) -> Option<String> {
+    match documents.iter().find(|(uri, _)| path_of(uri) == path) {
+        Some((_, document)) => Some(document.text.clone()),
+        None => std::fs::read_to_string(path).ok(),
+    }
+}
+
+fn read(path: &Path, documents: &Documents) -> Result<Flow> {
+    match documents.iter().find(|(uri, _)| path_of(uri) == path) {
+        Some((_, document)) => parser::parse_str(&document.text),
+        None => parser::parse_file(path),
+    }
+}
+
+/// Parse errors, validation and type checking of the flow and its imports, then code generation,
+/// the same checks as `flust check`
+pub fn diagnostics(uri: &Url, documents: &Documents) -> Vec<Diagnostic> {
+    let Some(document) = documents.get(uri) else { return Vec::new() };
+    let text = &document.text;
+    if let Err(error) = parser::parse_str(text) {
+        let start = error.downcast_ref::<serde_yaml::Error>()
+            .and_then(serde_yaml::Error::location)
+            .map(|location| Position::new(location.line().saturating_sub(1) as u32, location.column().saturating_sub(1) as u32))
+            .unwrap_or_default();
+        return vec![diagnostic(Range::new(start, start), DiagnosticSeverity::ERROR, "parse", error.to_string())];
+    }
+    let graph = match module_graph(uri, documents) {
+        Ok(graph) => graph,
+        Err(error) => return vec![diagnostic(Range::default(), DiagnosticSeverity::ERROR, "load", error.to_string())],
+    };
+    let results = diagnose::diagnose(&graph);
+
+    let mut diagnostics = Vec::new();
+    let mut errors = false;
+    for result in &results {
+        errors |= result.has_errors();
+        if result.module.name != graph.root().name {
+            continue;
+        }
+        for finding in &result.findings {
+            let range = finding.diagnostic.node_id.as_ref()
+                .and_then(|id| locate::node_id(text, id))
+                .map(|bytes| locate::range(text, bytes))
+                .unwrap_or_default();
+            let severity = match finding.diagnostic.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            };
+            diagnostics.push(diagnostic(range, severity, finding.rule, finding.diagnostic.message.clone()));
+        }
+    }
+    // Errors in imported flows are reported in their own files; only say the import is broken
+    if errors && !results.iter().any(|r| r.module.name == graph.root().name && r.has_errors()) {
+        diagnostics.push(diagnostic(Range::default(), DiagnosticSeverity::ERROR, "load", "an imported flow has errors".to_string()));
+    }
+    if !errors && let Err(error) = generator::generate_project(&graph, Target::Binary) {
+        diagnostics.push(diagnostic(Range::default(), DiagnosticSeverity::ERROR, "codegen", error.to_string()));
+    }
+    diagnostics
+}
+
+fn diagnostic(range: Range, severity: DiagnosticSeverity, rule: &str, message: String) -> Diagnostic {
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: Some(NumberOrString::String(rule.to_string())),
+        source: Some("flust".to_string()),
+        message,
+        ..Default::default()
+    }
+}
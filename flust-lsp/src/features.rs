@@ -0,0 +1,138 @@
+use crate::document::{self, Document, Documents};
+use crate::locate;
+use crate::plugins::PluginManifest;
+use flust_codegen::generator;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, Hover, HoverContents, Location, MarkupContent, MarkupKind, Url,
+};
+
+/// `plugin_type` values, and property names of the enclosing node's plugin inside `properties`
+pub fn completion(document: &Document, offset: usize, plugins: &[PluginManifest]) -> Vec<CompletionItem> {
+    let text = &document.text;
+    let json = text.trim_start().starts_with('{');
+    if let Some((key, value)) = locate::value_context(text, offset) {
+        if key != "plugin_type" {
+            return Vec::new();
+        }
+        let quoted = text[..offset - value.len()].ends_with('"');
+        return plugins.iter()
+            .map(|plugin| CompletionItem {
+                label: plugin.id.clone(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(plugin.name.clone()),
+                documentation: Some(Documentation::String(plugin.description.clone())),
+                insert_text: Some(if json && !quoted { format!("\"{}\"", plugin.id) } else { plugin.id.clone() }),
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    let (Some(quoted), Some(flow)) = (locate::key_context(text, offset), &document.flow) else { return Vec::new() };
+    let Some((node, entry)) = locate::node_at(text, flow, offset) else { return Vec::new() };
+    if !locate::in_properties(text, entry, offset) {
+        return Vec::new();
+    }
+    let Some(plugin) = plugins.iter().find(|p| p.id == node.plugin_type) else { return Vec::new() };
+    plugin.properties.iter()
+        .filter(|property| !node.properties.contains_key(&property.name))
+        .map(|property| CompletionItem {
+            label: property.name.clone(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some(format!("{} ({})", property.label, property.kind)),
+            insert_text: Some(match (json, quoted) {
+                (true, true) => format!("{}\": ", property.name),
+                (true, false) => format!("\"{}\": ", property.name),
+                (false, _) => format!("{}: ", property.name),
+            }),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The `function-definition` a `call-function` node's `target_function` names, in this flow or an imported one
+pub fn definition(uri: &Url, offset: usize, documents: &Documents) -> Option<Location> {
+    let document = documents.get(uri)?;
+    let (key, _) = locate::value_context(&document.text, offset)?;
+    if key != "target_function" {
+        return None;
+    }
+    let name = locate::word_at(&document.text, offset);
+    let defines = |flow: &flust_core::ir::Flow| {
+        flow.nodes.iter()
+            .find(|n| n.plugin_type == "function-definition" && n.properties.get("function_name").and_then(|v| v.as_str()) == Some(name))
+            .map(|n| n.id.clone())
+    };
+
+    if let Some(id) = document.flow.as_ref().and_then(defines) {
+        let bytes = locate::node_id(&document.text, &id)?;
+        return Some(Location::new(uri.clone(), locate::range(&document.text, bytes)));
+    }
+    let graph = document::module_graph(uri, documents).ok()?;
+    graph.modules.iter().find_map(|module| {
+        let id = defines(&module.flow)?;
+        let text = document::text_of(&module.path, documents)?;
+        let bytes = locate::node_id(&text, &id)?;
+        Some(Location::new(Url::from_file_path(&module.path).ok()?, locate::range(&text, bytes)))
+    })
+}
+
+/// Rust generated for the node under the cursor
+pub fn hover(document: &Document, offset: usize) -> Option<Hover> {
+    let flow = document.flow.as_ref()?;
+    let (node, entry) = locate::node_at(&document.text, flow, offset)?;
+    let value = match generator::generate_node(flow, &node.id) {
+        Ok(code) => format!("```rust\n{}\n```", code.trim_end()),
+        Err(error) => format!("Code generation failed: {}", error),
+    };
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(locate::range(&document.text, entry)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::PropertySpec;
+
+    const FLOW: &str = r#"{
+  "nodes": [
+    {"id": "main", "plugin_type": "function-definition", "properties": {"function_name": "main"}},
+    {"id": "helper", "plugin_type": "function-definition", "properties": {"function_name": "double", "arguments": [{"name": "x", "type": "i32"}], "return_type": "i32"}},
+    {"id": "body", "plugin_type": "legacy-code", "parent_id": "helper", "properties": {"code": "x * 2"}},
+    {"id": "call", "plugin_type": "call-function", "parent_id": "main", "properties": {"target_function": "double", "return_variable": "d", "arguments": []}}
+  ],
+  "connections": []
+}"#;
+
+    #[test]
+    fn test_completion_definition_and_hover() {
+        let uri = Url::parse("file:///tmp/app.flow.json").unwrap();
+        let documents: Documents = [(uri.clone(), Document::new(FLOW.to_string()))].into_iter().collect();
+        let document = &documents[&uri];
+        let plugins = vec![PluginManifest {
+            id: "call-function".to_string(),
+            name: "Call Function".to_string(),
+            description: String::new(),
+            properties: ["target_function", "spawn"].map(|name| PropertySpec {
+                name: name.to_string(),
+                label: name.to_string(),
+                kind: "text".to_string(),
+            }).to_vec(),
+        }];
+
+        let after = |pattern: &str| FLOW.find(pattern).unwrap() + pattern.len();
+        let types = completion(document, after("\"plugin_type\": \"c"), &plugins);
+        assert_eq!(types[0].insert_text.as_deref(), Some("call-function"));
+        let properties = completion(document, after("\"call-function\", \"parent_id\": \"main\", \"properties\": {"), &plugins);
+        let names: Vec<&str> = properties.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(names, vec!["spawn"]);
+        assert_eq!(properties[0].insert_text.as_deref(), Some("\"spawn\": "));
+
+        let location = definition(&uri, after("\"target_function\": \"dou"), &documents).unwrap();
+        assert_eq!(location.range, locate::range(FLOW, locate::node_id(FLOW, "helper").unwrap()));
+
+        let HoverContents::Markup(markup) = hover(document, after("\"id\": \"hel")).unwrap().contents else { panic!() };
+        assert!(markup.value.starts_with("```rust\nasync fn double(x: i32) -> i32 {"));
+    }
+}
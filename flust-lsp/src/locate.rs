@@ -0,0 +1,237 @@
+use flust_core::ir::{Flow, Node};
+use lsp_types::Position;
+use std::ops::Range;
+
+/// LSP position (UTF-16 columns) of a byte offset
+pub fn position(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let line_start = line_start(text, offset);
+    let line = text[..line_start].matches('\n').count();
+    let character = text[line_start..offset].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+/// Byte offset of an LSP position, clamped to its line
+pub fn offset(text: &str, position: Position) -> usize {
+    let start = match position.line {
+        0 => 0,
+        line => match text.match_indices('\n').nth(line as usize - 1) {
+            Some((index, _)) => index + 1,
+            None => return text.len(),
+        },
+    };
+    let mut units = 0;
+    for (index, c) in text[start..].char_indices() {
+        if units >= position.character as usize || c == '\n' {
+            return start + index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+pub fn range(text: &str, bytes: Range<usize>) -> lsp_types::Range {
+    lsp_types::Range::new(position(text, bytes.start), position(text, bytes.end))
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text[..offset].rfind('\n').map_or(0, |index| index + 1)
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Offsets of `key` used as a mapping key, in JSON (`"key":`) or YAML (`key:`) syntax
+fn key_offsets<'t>(text: &'t str, key: &'t str) -> impl Iterator<Item = usize> + 't {
+    text.match_indices(key).filter_map(move |(index, _)| {
+        let before = text[..index].chars().next_back();
+        if before.is_some_and(is_word) {
+            return None;
+        }
+        let rest = &text[index + key.len()..];
+        let rest = rest.strip_prefix('"').filter(|_| before == Some('"')).unwrap_or(rest);
+        rest.trim_start_matches([' ', '\t']).starts_with(':').then_some(index)
+    })
+}
+
+/// Byte range of `value` where it is the value of `key`, quoted or not
+pub fn find_value(text: &str, key: &str, value: &str) -> Option<Range<usize>> {
+    key_offsets(text, key).find_map(|index| {
+        let after_key = index + key.len() + usize::from(text[index + key.len()..].starts_with('"'));
+        let colon = after_key + text[after_key..].find(':')? + 1;
+        let rest = &text[colon..];
+        let start = colon + rest.len() - rest.trim_start_matches([' ', '\t']).len();
+        let start = start + usize::from(text[start..].starts_with(['"', '\'']));
+        let end = start + value.len();
+        let matches = text.get(start..end) == Some(value) && !text[end..].starts_with(is_word);
+        matches.then_some(start..end)
+    })
+}
+
+/// Range of the node's `id` value, where diagnostics and definitions point
+pub fn node_id(text: &str, id: &str) -> Option<Range<usize>> {
+    find_value(text, "id", id)
+}
+
+/// Byte range of the node's whole entry in the `nodes` list
+pub fn node_entry(text: &str, id: &str) -> Option<Range<usize>> {
+    let id_range = node_id(text, id)?;
+    if text.trim_start().starts_with('{') {
+        json_object(text, id_range.start)
+    } else {
+        yaml_item(text, id_range.start)
+    }
+}
+
+/// Innermost JSON object around `offset`
+fn json_object(text: &str, offset: usize) -> Option<Range<usize>> {
+    let mut depth = 0;
+    let mut start = None;
+    for (index, c) in text[..offset].char_indices().rev() {
+        match c {
+            '}' => depth += 1,
+            '{' if depth == 0 => {
+                start = Some(index);
+                break;
+            }
+            '{' => depth -= 1,
+            _ => {}
+        }
+    }
+    let start = start?;
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    for (index, c) in text[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start..start + index + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// YAML sequence item (`- id: ...` and its indented lines) around `offset`
+fn yaml_item(text: &str, offset: usize) -> Option<Range<usize>> {
+    let mut start = line_start(text, offset);
+    loop {
+        if text[start..].trim_start_matches(' ').starts_with('-') {
+            break;
+        }
+        if start == 0 {
+            return None;
+        }
+        start = line_start(text, start - 1);
+    }
+    let dash = indentation(&text[start..]);
+    let mut end = start + text[start..].find('\n').map_or(text.len() - start, |index| index + 1);
+    while end < text.len() {
+        let line = text[end..].split('\n').next().unwrap_or_default();
+        let blank = line.trim().is_empty() || line.trim_start().starts_with('#');
+        if !blank && indentation(line) <= dash {
+            break;
+        }
+        end = (end + line.len() + 1).min(text.len());
+    }
+    Some(start..end)
+}
+
+/// Innermost node whose entry contains `offset`
+pub fn node_at<'f>(text: &str, flow: &'f Flow, offset: usize) -> Option<(&'f Node, Range<usize>)> {
+    flow.nodes.iter()
+        .filter_map(|node| Some((node, node_entry(text, &node.id)?)))
+        .filter(|(_, entry)| entry.contains(&offset))
+        .min_by_key(|(_, entry)| entry.len())
+}
+
+/// Key and the value typed so far when `offset` is in value position (`"plugin_type": "deb`)
+pub fn value_context(text: &str, offset: usize) -> Option<(String, String)> {
+    let prefix = &text[line_start(text, offset)..offset];
+    let colon = prefix.rfind(':')?;
+    let key = prefix[..colon].trim_end().trim_end_matches('"');
+    let key = &key[key.rfind(|c: char| !is_word(c)).map_or(0, |index| index + 1)..];
+    let value = prefix[colon + 1..].trim_start();
+    let value = value.strip_prefix(['"', '\'']).unwrap_or(value);
+    (!key.is_empty() && value.chars().all(is_word)).then(|| (key.to_string(), value.to_string()))
+}
+
+/// Whole word around `offset`, for lookups on a value under the cursor
+pub fn word_at(text: &str, offset: usize) -> &str {
+    let start = text[..offset].rfind(|c: char| !is_word(c)).map_or(0, |index| index + 1);
+    let end = offset + text[offset..].find(|c: char| !is_word(c)).unwrap_or(text.len() - offset);
+    &text[start..end]
+}
+
+/// Whether `offset` is where a mapping key is being typed; returns whether a quote was opened
+pub fn key_context(text: &str, offset: usize) -> Option<bool> {
+    let prefix = &text[line_start(text, offset)..offset];
+    let prefix = &prefix[prefix.rfind(['{', ',']).map_or(0, |index| index + 1)..];
+    let prefix = prefix.trim_start().trim_start_matches('-').trim_start();
+    let (quoted, partial) = match prefix.strip_prefix('"') {
+        Some(rest) => (true, rest),
+        None => (false, prefix),
+    };
+    partial.chars().all(is_word).then_some(quoted)
+}
+
+/// Whether `offset` lies inside the `properties` mapping of the node entry
+pub fn in_properties(text: &str, entry: Range<usize>, offset: usize) -> bool {
+    let Some(key) = key_offsets(text, "properties").filter(|&index| entry.contains(&index) && index < offset).last() else {
+        return false;
+    };
+    if text.trim_start().starts_with('{') {
+        let Some(open) = text[key..offset].find('{') else { return false };
+        json_object(text, offset).is_some_and(|object| object.start == key + open)
+    } else {
+        let key_indent = indentation(&text[line_start(text, key)..]);
+        let cursor_line = line_start(text, offset);
+        let key_line_end = key + text[key..].find('\n').unwrap_or(text.len() - key);
+        if cursor_line <= key || !text[key + "properties".len()..key_line_end].trim_start_matches(':').trim().is_empty() {
+            return false;
+        }
+        text[key_line_end..offset].split('\n').skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .all(|line| indentation(line) > key_indent)
+            && indentation(&text[cursor_line..offset]) > key_indent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locates_nodes_in_json_and_yaml() {
+        let json = "{\n  \"nodes\": [\n    {\"id\": \"a\", \"parent_id\": \"main\", \"properties\": {\"name\": \"x\"}},\n    {\"id\": \"main\"}\n  ]\n}";
+        let main = node_id(json, "main").unwrap();
+        assert_eq!(position(json, main.start), Position::new(3, 12));
+        assert_eq!(offset(json, Position::new(3, 12)), main.start);
+        let entry = node_entry(json, "a").unwrap();
+        assert_eq!(&json[entry.clone()], "{\"id\": \"a\", \"parent_id\": \"main\", \"properties\": {\"name\": \"x\"}}");
+        let inside = json.find("\"name\"").unwrap() + 1;
+        assert!(in_properties(json, entry.clone(), inside));
+        assert!(!in_properties(json, entry, json.find("\"parent_id\"").unwrap()));
+
+        let yaml = "nodes:\n- id: a\n  plugin_type: deb\n  properties:\n    na\n- id: main\n  plugin_type: function-definition\n";
+        let entry = node_entry(yaml, "a").unwrap();
+        assert_eq!(&yaml[entry.clone()], "- id: a\n  plugin_type: deb\n  properties:\n    na\n");
+        let typing = yaml.find("deb").unwrap() + 3;
+        assert_eq!(value_context(yaml, typing), Some(("plugin_type".to_string(), "deb".to_string())));
+        assert_eq!(word_at(yaml, typing - 1), "deb");
+        let key = yaml.find("na\n").unwrap() + 2;
+        assert_eq!(key_context(yaml, key), Some(false));
+        assert!(in_properties(yaml, entry, key));
+    }
+}
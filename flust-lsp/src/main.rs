@@ -0,0 +1,145 @@
+mod document;
+mod features;
+mod locate;
+mod plugins;
+
+use anyhow::Result;
+use clap::Parser;
+use document::{Document, Documents};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionOptions, CompletionParams, GotoDefinitionParams, HoverParams, HoverProviderCapability, InitializeParams,
+    OneOf, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use plugins::PluginManifest;
+use std::path::PathBuf;
+
+/// Language server for flow files over stdio
+#[derive(Parser)]
+#[command(name = "flust-lsp")]
+#[command(about = "Language server for Flust flow files", long_about = None)]
+struct Cli {
+    /// Plugin manifest directory, relative to the workspace root unless absolute
+    #[arg(long, default_value = "frontend/public/plugins")]
+    plugins: PathBuf,
+}
+
+struct Server {
+    documents: Documents,
+    plugins: Vec<PluginManifest>,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["\"".to_string(), ":".to_string()]),
+            ..Default::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        ..Default::default()
+    };
+    let params: InitializeParams = serde_json::from_value(connection.initialize(serde_json::to_value(capabilities)?)?)?;
+    let root = params.workspace_folders.as_deref()
+        .and_then(|folders| folders.first())
+        .and_then(|folder| folder.uri.to_file_path().ok());
+    let plugin_dir = match root {
+        Some(root) if cli.plugins.is_relative() => root.join(&cli.plugins),
+        _ => cli.plugins,
+    };
+
+    let mut server = Server { documents: Documents::new(), plugins: plugins::load(&plugin_dir) };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                connection.sender.send(Message::Response(server.request(request)))?;
+            }
+            Message::Notification(notification) => {
+                for uri in server.notification(notification)? {
+                    let diagnostics = document::diagnostics(&uri, &server.documents);
+                    let params = PublishDiagnosticsParams { uri, diagnostics, version: None };
+                    connection.sender.send(Message::Notification(Notification::new(PublishDiagnostics::METHOD.to_string(), params)))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    // The writer thread stops once the connection's sender is gone
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+impl Server {
+    fn request(&self, request: Request) -> Response {
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => serde_json::from_value::<HoverParams>(request.params).map(|params| {
+                let position = params.text_document_position_params;
+                let document = self.documents.get(&position.text_document.uri);
+                let hover = document.and_then(|d| features::hover(d, locate::offset(&d.text, position.position)));
+                serde_json::to_value(hover)
+            }),
+            Completion::METHOD => serde_json::from_value::<CompletionParams>(request.params).map(|params| {
+                let position = params.text_document_position;
+                let items = self.documents.get(&position.text_document.uri)
+                    .map(|d| features::completion(d, locate::offset(&d.text, position.position), &self.plugins))
+                    .unwrap_or_default();
+                serde_json::to_value(items)
+            }),
+            GotoDefinition::METHOD => serde_json::from_value::<GotoDefinitionParams>(request.params).map(|params| {
+                let position = params.text_document_position_params;
+                let uri = position.text_document.uri;
+                let location = self.documents.get(&uri)
+                    .and_then(|d| features::definition(&uri, locate::offset(&d.text, position.position), &self.documents));
+                serde_json::to_value(location)
+            }),
+            method => {
+                let message = format!("Unsupported request '{}'", method);
+                return Response::new_err(request.id, ErrorCode::MethodNotFound as i32, message);
+            }
+        };
+        match result.and_then(|value| value) {
+            Ok(value) => Response::new_ok(request.id, value),
+            Err(error) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, error.to_string()),
+        }
+    }
+
+    /// Track open documents; returns the documents whose diagnostics must be published again
+    fn notification(&mut self, notification: Notification) -> Result<Vec<Url>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.insert(uri.clone(), Document::new(params.text_document.text));
+                Ok(vec![uri])
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                if let (Some(change), Some(document)) = (params.content_changes.into_iter().last(), self.documents.get_mut(&uri)) {
+                    document.update(change.text);
+                }
+                // Files importing the edited one may gain or lose errors too
+                Ok(self.documents.keys().cloned().collect())
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = serde_json::from_value(notification.params)?;
+                // Publishing for a closed document clears its diagnostics
+                self.documents.remove(&params.text_document.uri);
+                Ok(vec![params.text_document.uri])
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Node type described by a `plugin.json` manifest
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginManifest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub properties: Vec<PropertySpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PropertySpec {
+    pub name: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(rename = "type", default)]
+    pub kind: String,
+}
+
+/// Every `<dir>/<id>/plugin.json`, sorted by id; unreadable manifests are skipped
+pub fn load(dir: &Path) -> Vec<PluginManifest> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut plugins: Vec<PluginManifest> = entries
+        .filter_map(|entry| fs::read_to_string(entry.ok()?.path().join("plugin.json")).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    plugins.sort_by(|a, b| a.id.cmp(&b.id));
+    plugins
+}
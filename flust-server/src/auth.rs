@@ -0,0 +1,342 @@
+use crate::ErrorResponse;
+use anyhow::{Context, Result};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Largest execution request body read for the audit log (the JSON extractor's own limit)
+const MAX_BODY: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Generate code and analyze flows
+    Compile,
+    /// Build and run programs, including debugging
+    Execute,
+}
+
+impl Permission {
+    fn name(self) -> &'static str {
+        match self {
+            Permission::Compile => "compile",
+            Permission::Execute => "execute",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    pub name: String,
+    /// Static API token, sent as `Authorization: Bearer <token>`
+    pub token: String,
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Key signing session tokens; without it only static tokens are accepted
+    pub session_secret: Option<String>,
+    #[serde(default = "default_session_ttl")]
+    pub session_ttl_secs: u64,
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
+}
+
+fn default_session_ttl() -> u64 {
+    8 * 60 * 60
+}
+
+/// Who made a request; requests to a server without users come from `local`, which may do anything
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct User {
+    pub name: String,
+    pub permissions: Vec<Permission>,
+    /// Signed in with a session token rather than the static API token
+    #[serde(skip)]
+    pub session: bool,
+}
+
+pub struct Auth {
+    /// `None` when auth is disabled
+    config: Option<AuthConfig>,
+    audit: Audit,
+}
+
+impl Auth {
+    pub fn new(config: Option<AuthConfig>, audit_log: PathBuf) -> Self {
+        Auth { config, audit: Audit { path: audit_log, lock: Mutex::new(()) } }
+    }
+
+    pub fn load(path: &Path) -> Result<AuthConfig> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read auth file {:?}", path))?;
+        let config: AuthConfig = toml::from_str(&text).with_context(|| format!("Invalid auth file {:?}", path))?;
+        if config.users.is_empty() {
+            anyhow::bail!("Auth file {:?} defines no users", path);
+        }
+        Ok(config)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    pub fn users(&self) -> usize {
+        self.config.as_ref().map_or(0, |c| c.users.len())
+    }
+
+    fn authenticate(&self, headers: &HeaderMap) -> Result<User, &'static str> {
+        let Some(config) = &self.config else {
+            return Ok(User { name: "local".to_string(), permissions: vec![Permission::Compile, Permission::Execute], session: false });
+        };
+        let token = headers.get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or("Missing bearer token")?;
+
+        let (user, session) = match config.users.iter().find(|u| same_secret(&u.token, token)) {
+            Some(user) => (user, false),
+            None => {
+                let secret = config.session_secret.as_deref().ok_or("Invalid token")?;
+                let name = verify_session(secret, token, now()).ok_or("Invalid or expired token")?;
+                (config.users.iter().find(|u| u.name == name).ok_or("Unknown user")?, true)
+            }
+        };
+        Ok(User { name: user.name.clone(), permissions: user.permissions.clone(), session })
+    }
+}
+
+/// Compares digests so the time taken does not depend on how much of the token matches
+fn same_secret(expected: &str, given: &str) -> bool {
+    Sha256::digest(expected.as_bytes()) == Sha256::digest(given.as_bytes())
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionClaims {
+    user: String,
+    exp: u64,
+}
+
+fn signature(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// `<claims>.<signature>`, both base64url; valid until `exp` (unix seconds)
+fn sign_session(secret: &str, user: &str, exp: u64) -> String {
+    let claims = serde_json::to_vec(&SessionClaims { user: user.to_string(), exp }).unwrap_or_default();
+    let payload = URL_SAFE_NO_PAD.encode(claims);
+    let signature = URL_SAFE_NO_PAD.encode(signature(secret, &payload).finalize().into_bytes());
+    format!("{}.{}", payload, signature)
+}
+
+/// User named by a session token whose signature checks out and that has not expired
+fn verify_session(secret: &str, token: &str, now: u64) -> Option<String> {
+    let (payload, given) = token.split_once('.')?;
+    signature(secret, payload).verify_slice(&URL_SAFE_NO_PAD.decode(given).ok()?).ok()?;
+    let claims: SessionClaims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    (claims.exp > now).then_some(claims.user)
+}
+
+fn reject(status: StatusCode, error: &str) -> Response {
+    let mut response = (status, Json(ErrorResponse { error: error.to_string() })).into_response();
+    if status == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+    }
+    response
+}
+
+/// Lets through requests whose user holds `permission`, making the `User` available to handlers
+async fn authorize(auth: &Auth, permission: Option<Permission>, mut request: Request, next: Next) -> Result<Response, (Response, String)> {
+    let user = auth.authenticate(request.headers())
+        .map_err(|reason| (reject(StatusCode::UNAUTHORIZED, reason), reason.to_string()))?;
+    if let Some(permission) = permission
+        && !user.permissions.contains(&permission)
+    {
+        let reason = format!("User '{}' lacks the {} permission", user.name, permission.name());
+        return Err((reject(StatusCode::FORBIDDEN, &reason), reason));
+    }
+    request.extensions_mut().insert(user);
+    Ok(next.run(request).await)
+}
+
+/// Middleware of the routes that only need to be signed in
+pub async fn authenticated(State(auth): State<Arc<Auth>>, request: Request, next: Next) -> Response {
+    authorize(&auth, None, request, next).await.unwrap_or_else(|(response, _)| response)
+}
+
+/// Middleware of the code generation and analysis routes
+pub async fn compile_access(State(auth): State<Arc<Auth>>, request: Request, next: Next) -> Response {
+    authorize(&auth, Some(Permission::Compile), request, next).await.unwrap_or_else(|(response, _)| response)
+}
+
+/// Middleware of the routes that run programs; every request is written to the audit log, allowed or not
+pub async fn execute_access(
+    State(auth): State<Arc<Auth>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY).await else {
+        return reject(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+    };
+    let mut entry = AuditEntry::new(parts.uri.path(), remote, &bytes);
+    entry.user = auth.authenticate(&parts.headers).ok().map(|u| u.name);
+
+    let request = Request::from_parts(parts, Body::from(bytes));
+    let response = match authorize(&auth, Some(Permission::Execute), request, next).await {
+        Ok(response) => response,
+        Err((response, reason)) => {
+            entry.denied = Some(reason);
+            response
+        }
+    };
+    entry.status = response.status().as_u16();
+    auth.audit.record(&entry);
+    response
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    token: String,
+    user: User,
+    expires_at: u64,
+}
+
+/// Exchanges a static API token for a signed session token
+/// Session tokens cannot renew themselves, so a leaked one still expires
+pub async fn create_session(State(auth): State<Arc<Auth>>, Extension(user): Extension<User>) -> Response {
+    let Some(config) = &auth.config else {
+        return reject(StatusCode::NOT_FOUND, "Auth is disabled");
+    };
+    if user.session {
+        return reject(StatusCode::FORBIDDEN, "Sessions are only issued for static API tokens");
+    }
+    let Some(secret) = &config.session_secret else {
+        return reject(StatusCode::NOT_FOUND, "Session tokens are not enabled (no session_secret)");
+    };
+    let expires_at = now() + config.session_ttl_secs;
+    let token = sign_session(secret, &user.name, expires_at);
+    Json(SessionResponse { token, user, expires_at }).into_response()
+}
+
+/// One line of the audit log
+#[derive(Serialize)]
+struct AuditEntry {
+    ts: u64,
+    endpoint: String,
+    remote: String,
+    user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filename: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<String>,
+    /// SHA-256 of the submitted code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code_sha256: Option<String>,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    denied: Option<String>,
+}
+
+impl AuditEntry {
+    fn new(endpoint: &str, remote: SocketAddr, body: &[u8]) -> Self {
+        let request: serde_json::Value = serde_json::from_slice(body).unwrap_or_default();
+        let field = |name: &str| request.get(name).and_then(|v| v.as_str()).map(String::from);
+        AuditEntry {
+            ts: now(),
+            endpoint: endpoint.to_string(),
+            remote: remote.to_string(),
+            user: None,
+            filename: field("filename"),
+            action: field("action"),
+            code_sha256: field("code").map(|code| Sha256::digest(code.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()),
+            status: 0,
+            denied: None,
+        }
+    }
+}
+
+/// Append-only JSON lines file
+struct Audit {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl Audit {
+    fn record(&self, entry: &AuditEntry) {
+        let _guard = self.lock.lock().unwrap_or_else(|p| p.into_inner());
+        let line = serde_json::to_string(entry).unwrap_or_default() + "\n";
+        let written = OpenOptions::new().create(true).append(true).open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+        if let Err(e) = written {
+            log::error!("❌ Failed to write audit log {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_and_permissions() {
+        let config: AuthConfig = toml::from_str(r#"
+            session_secret = "s3cret"
+
+            [[users]]
+            name = "ana"
+            token = "ana-token"
+            permissions = ["compile"]
+        "#).unwrap();
+        let auth = Auth::new(Some(config), PathBuf::from("unused.log"));
+        let headers = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+            headers
+        };
+
+        let ana = auth.authenticate(&headers("ana-token")).unwrap();
+        assert_eq!(ana.permissions, vec![Permission::Compile]);
+        assert!(!ana.session);
+        assert_eq!(auth.authenticate(&HeaderMap::new()), Err("Missing bearer token"));
+        assert!(auth.authenticate(&headers("wrong")).is_err());
+
+        let session = sign_session("s3cret", "ana", now() + 60);
+        let from_session = auth.authenticate(&headers(&session)).unwrap();
+        assert_eq!(from_session.name, "ana");
+        assert!(from_session.session);
+        assert_eq!(verify_session("other", &session, now()), None);
+        assert_eq!(verify_session("s3cret", &sign_session("s3cret", "ana", 10), 10), None);
+        let (_, signature) = session.split_once('.').unwrap();
+        let forged = format!("{}.{}", URL_SAFE_NO_PAD.encode(br#"{"user":"root","exp":99999999999}"#), signature);
+        assert_eq!(verify_session("s3cret", &forged, now()), None);
+
+        let local = Auth::new(None, PathBuf::from("unused.log")).authenticate(&HeaderMap::new()).unwrap();
+        assert_eq!(local.permissions, vec![Permission::Compile, Permission::Execute]);
+    }
+}
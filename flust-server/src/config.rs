@@ -0,0 +1,276 @@
+use anyhow::{anyhow, Context, Result};
+use axum::http::Uri;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Config file read when neither `--config` nor `FLUST_CONFIG` name one
+pub const DEFAULT_FILE: &str = "flust-server.toml";
+
+/// Settings of flust-server: defaults, then the config file, then `FLUST_*` environment variables
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub paths: PathsConfig,
+    pub execution: ExecutionConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Browser origins allowed by CORS
+    pub cors_origins: Vec<String>,
+    /// Listen on a non-loopback host without `paths.auth_file`, letting anyone who reaches the port run code
+    pub allow_unauthenticated: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            cors_origins: vec!["http://localhost:5173".to_string(), "http://127.0.0.1:5173".to_string()],
+            allow_unauthenticated: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// Cargo project the submitted code is written into and built
+    pub workspace: PathBuf,
+    /// Directories of `<id>/plugin.json` served by `/api/plugins`; the first one defining an id wins
+    pub plugin_dirs: Vec<PathBuf>,
+    /// Working directory of executed programs; the workspace when unset
+    pub sandbox_root: Option<PathBuf>,
+    /// Users, tokens and session secret; auth is disabled when unset
+    pub auth_file: Option<PathBuf>,
+    pub audit_log: PathBuf,
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            workspace: PathBuf::from("./output_project"),
+            plugin_dirs: vec![PathBuf::from("frontend/public/plugins")],
+            sandbox_root: None,
+            auth_file: None,
+            audit_log: PathBuf::from("flust-audit.log"),
+        }
+    }
+}
+
+impl PathsConfig {
+    pub fn sandbox_root(&self) -> &Path {
+        self.sandbox_root.as_deref().unwrap_or(&self.workspace)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutionConfig {
+    /// Build and run time allowed to `/api/execute`, and to a debugged program between pauses, before it is killed;
+    /// also how long a pause holds the debugger against other users
+    pub timeout_secs: u64,
+    /// Program output kept per stream; the rest is cut
+    pub max_output_bytes: usize,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        ExecutionConfig { timeout_secs: 120, max_output_bytes: 1024 * 1024 }
+    }
+}
+
+impl ExecutionConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// env_logger filter, e.g. `info` or `flust_server=debug`
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig { level: "info".to_string(), format: LogFormat::Text }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl Config {
+    /// The given file, or `FLUST_CONFIG`, or `flust-server.toml` when present, with the environment applied
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = path.map(Path::to_path_buf)
+            .or_else(|| std::env::var_os("FLUST_CONFIG").map(PathBuf::from))
+            .or_else(|| Some(PathBuf::from(DEFAULT_FILE)).filter(|p| p.exists()));
+        let mut config = match path {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read config file {:?}", path))?;
+                toml::from_str(&text).with_context(|| format!("Invalid config file {:?}", path))?
+            }
+            None => Config::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Refuses settings that would expose code execution or that the server could not apply
+    pub fn validate(&self) -> Result<()> {
+        let host = &self.server.host;
+        let loopback = host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+        if !loopback && self.paths.auth_file.is_none() && !self.server.allow_unauthenticated {
+            return Err(anyhow!(
+                "Refusing to listen on {} without auth: set paths.auth_file, or server.allow_unauthenticated = true \
+                 (FLUST_ALLOW_UNAUTHENTICATED=true) to let anyone who reaches the port run code", host
+            ));
+        }
+        for origin in &self.server.cors_origins {
+            check_origin(origin)?;
+        }
+        Ok(())
+    }
+
+    /// Overrides settings with the `FLUST_*` variables `var` knows
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let list = |value: String| value.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from).collect::<Vec<_>>();
+
+        if let Some(host) = var("FLUST_HOST") {
+            self.server.host = host;
+        }
+        if let Some(port) = var("FLUST_PORT") {
+            self.server.port = parse("FLUST_PORT", port)?;
+        }
+        if let Some(allow) = var("FLUST_ALLOW_UNAUTHENTICATED") {
+            self.server.allow_unauthenticated = parse("FLUST_ALLOW_UNAUTHENTICATED", allow)?;
+        }
+        if let Some(origins) = var("FLUST_CORS_ORIGINS") {
+            self.server.cors_origins = list(origins);
+        }
+        if let Some(workspace) = var("FLUST_WORKSPACE") {
+            self.paths.workspace = workspace.into();
+        }
+        if let Some(dirs) = var("FLUST_PLUGIN_DIRS") {
+            self.paths.plugin_dirs = list(dirs).into_iter().map(PathBuf::from).collect();
+        }
+        if let Some(root) = var("FLUST_SANDBOX_ROOT") {
+            self.paths.sandbox_root = Some(root.into());
+        }
+        if let Some(file) = var("FLUST_AUTH_FILE") {
+            self.paths.auth_file = Some(file.into());
+        }
+        if let Some(file) = var("FLUST_AUDIT_LOG") {
+            self.paths.audit_log = file.into();
+        }
+        if let Some(secs) = var("FLUST_EXEC_TIMEOUT_SECS") {
+            self.execution.timeout_secs = parse("FLUST_EXEC_TIMEOUT_SECS", secs)?;
+        }
+        if let Some(bytes) = var("FLUST_MAX_OUTPUT_BYTES") {
+            self.execution.max_output_bytes = parse("FLUST_MAX_OUTPUT_BYTES", bytes)?;
+        }
+        if let Some(level) = var("FLUST_LOG_LEVEL") {
+            self.log.level = level;
+        }
+        if let Some(format) = var("FLUST_LOG_FORMAT") {
+            self.log.format = match format.as_str() {
+                "text" => LogFormat::Text,
+                "json" => LogFormat::Json,
+                _ => return Err(anyhow!("FLUST_LOG_FORMAT must be text or json, not {:?}", format)),
+            };
+        }
+        Ok(())
+    }
+
+    /// Effective settings as TOML, with defaults that depend on other settings filled in
+    pub fn to_toml(&self) -> Result<String> {
+        let mut effective = self.clone();
+        effective.paths.sandbox_root = Some(self.paths.sandbox_root().to_path_buf());
+        Ok(toml::to_string_pretty(&effective)?)
+    }
+}
+
+/// `scheme://host[:port]`, exactly as browsers send it in `Origin`
+fn check_origin(origin: &str) -> Result<()> {
+    if origin == "*" {
+        return Err(anyhow!("server.cors_origins cannot contain \"*\"; list the origins of the editor instead"));
+    }
+    let uri: Uri = origin.parse().map_err(|_| anyhow!("Invalid CORS origin {:?}", origin))?;
+    let web = matches!(uri.scheme_str(), Some("http" | "https"));
+    if !web || uri.authority().is_none() || uri.path_and_query().is_some_and(|p| p.as_str() != "/") || origin.ends_with('/') {
+        return Err(anyhow!("Invalid CORS origin {:?}: expected scheme://host[:port], e.g. \"http://localhost:5173\"", origin));
+    }
+    Ok(())
+}
+
+fn parse<T: FromStr>(name: &str, value: String) -> Result<T> {
+    value.parse().map_err(|_| anyhow!("{} is not valid: {:?}", name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_then_environment() {
+        let mut config: Config = toml::from_str(r#"
+            [server]
+            port = 8080
+
+            [paths]
+            workspace = "/srv/flust/project"
+            plugin_dirs = ["/srv/flust/plugins", "frontend/public/plugins"]
+
+            [log]
+            format = "json"
+        "#).unwrap();
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.paths.sandbox_root(), Path::new("/srv/flust/project"));
+
+        let env = |name: &str| match name {
+            "FLUST_HOST" => Some("0.0.0.0".to_string()),
+            "FLUST_CORS_ORIGINS" => Some("https://flust.example, https://admin.flust.example".to_string()),
+            "FLUST_EXEC_TIMEOUT_SECS" => Some("30".to_string()),
+            _ => None,
+        };
+        config.apply_env(env).unwrap();
+        assert_eq!((config.server.host.as_str(), config.server.port), ("0.0.0.0", 8080));
+        assert_eq!(config.server.cors_origins, vec!["https://flust.example", "https://admin.flust.example"]);
+        assert_eq!(config.execution.timeout(), Duration::from_secs(30));
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert!(config.validate().unwrap_err().to_string().starts_with("Refusing to listen on 0.0.0.0 without auth"));
+        config.apply_env(|name| (name == "FLUST_ALLOW_UNAUTHENTICATED").then(|| "true".to_string())).unwrap();
+        assert!(config.validate().is_ok());
+        assert!(Config::default().validate().is_ok());
+        for origin in ["*", "localhost:5173", "http://localhost:5173/", "http://x/app", "ftp://x", "http://bad host"] {
+            config.server.cors_origins = vec![origin.to_string()];
+            assert!(config.validate().is_err(), "{}", origin);
+        }
+
+        let printed = config.to_toml().unwrap();
+        assert!(printed.contains("sandbox_root = \"/srv/flust/project\""));
+        assert_eq!(toml::from_str::<Config>(&printed).unwrap().server, config.server);
+
+        assert!(config.apply_env(|name| (name == "FLUST_PORT").then(|| "http".to_string())).is_err());
+        assert!(toml::from_str::<Config>("[server]\nprot = 1").is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use crate::auth::User;
+use crate::config::Config;
+use crate::ApiError;
+use axum::{extract::State, Extension, Json};
+use flust_codegen::debug::{self, Action, DebugVariable};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpListener;
+use tokio::process::Child;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// The program being debugged, if any; one session at a time, driven only by the user who started it
+/// The lock is only held to swap sessions, never while building or waiting for the program
+#[derive(Clone, Default)]
+pub struct Debugger(Arc<std::sync::Mutex<Slot>>);
+
+#[derive(Default)]
+struct Slot {
+    /// Program paused and waiting for a command, with when it paused
+    paused: Option<(Session, Instant)>,
+    /// Request building or running the program until its next pause
+    running: Option<Run>,
+}
+
+struct Run {
+    /// Notified to make the request give the program up
+    cancel: Arc<Notify>,
+    owner: String,
+}
+
+impl Slot {
+    /// Refuses users other than the one whose program is paused or running
+    /// A pause left without commands for longer than `idle` no longer holds the debugger
+    fn check_owner(&self, user: &User, idle: Duration) -> Result<(), ApiError> {
+        let owner = self.paused.as_ref()
+            .filter(|(_, since)| since.elapsed() < idle)
+            .map(|(session, _)| &session.owner.name)
+            .or(self.running.as_ref().map(|run| &run.owner));
+        match owner {
+            Some(owner) if *owner != user.name => {
+                Err(ApiError::Conflict(format!("User '{}' is debugging a program", owner)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_running(&self, cancel: &Arc<Notify>) -> bool {
+        self.running.as_ref().is_some_and(|run| Arc::ptr_eq(&run.cancel, cancel))
+    }
+}
+
+impl Debugger {
+    fn slot(&self) -> std::sync::MutexGuard<'_, Slot> {
+        self.0.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Cancels the running request and takes the paused session, which may be another user's expired one;
+    /// the caller's request becomes the running one
+    fn claim(&self, user: &User, idle: Duration) -> Result<(Arc<Notify>, Option<Session>), ApiError> {
+        let mut slot = self.slot();
+        slot.check_owner(user, idle)?;
+        let cancel = Arc::new(Notify::new());
+        if let Some(running) = slot.running.replace(Run { cancel: cancel.clone(), owner: user.name.clone() }) {
+            running.cancel.notify_one();
+        }
+        Ok((cancel, slot.paused.take().map(|(session, _)| session)))
+    }
+
+    /// The caller's paused session, if any, for their request to resume
+    fn take_paused(&self, user: &User, idle: Duration) -> Result<Option<(Arc<Notify>, Session)>, ApiError> {
+        let mut slot = self.slot();
+        slot.check_owner(user, idle)?;
+        let Some((session, _)) = slot.paused.take_if(|(session, _)| session.owner.name == user.name) else {
+            return Ok(None);
+        };
+        let cancel = Arc::new(Notify::new());
+        slot.running = Some(Run { cancel: cancel.clone(), owner: user.name.clone() });
+        Ok(Some((cancel, session)))
+    }
+
+    /// Keeps the paused session unless a newer request took over meanwhile, which gets it back to finish
+    fn pause(&self, cancel: &Arc<Notify>, session: Session) -> Option<Session> {
+        let mut slot = self.slot();
+        if !slot.is_running(cancel) {
+            return Some(session);
+        }
+        slot.running = None;
+        slot.paused = Some((session, Instant::now()));
+        None
+    }
+
+    fn release(&self, cancel: &Arc<Notify>) {
+        let mut slot = self.slot();
+        if slot.is_running(cancel) {
+            slot.running = None;
+        }
+    }
+}
+
+pub struct Session {
+    /// User who started the program
+    owner: User,
+    child: Child,
+    events: Lines<BufReader<OwnedReadHalf>>,
+    commands: OwnedWriteHalf,
+    stdout: Output,
+    stderr: Output,
+}
+
+/// Text a child stream has produced so far, filled by a background task
+pub(crate) struct Output {
+    text: Arc<std::sync::Mutex<String>>,
+    reader: JoinHandle<()>,
+}
+
+impl Output {
+    /// Keeps the first `limit` bytes of the stream and drains the rest, so the child never blocks on a full pipe
+    pub(crate) fn capture(stream: Option<impl AsyncRead + Unpin + Send + 'static>, limit: usize) -> Self {
+        let text = Arc::new(std::sync::Mutex::new(String::new()));
+        let shared = text.clone();
+        let reader = tokio::spawn(async move {
+            let Some(mut stream) = stream else { return };
+            let mut buffer = [0; 4096];
+            while let Ok(read) = stream.read(&mut buffer).await {
+                if read == 0 {
+                    break;
+                }
+                let mut text = shared.lock().unwrap_or_else(|p| p.into_inner());
+                if text.len() >= limit {
+                    continue;
+                }
+                let chunk = String::from_utf8_lossy(&buffer[..read]);
+                let room = limit - text.len();
+                if chunk.len() <= room {
+                    text.push_str(&chunk);
+                } else {
+                    let cut = (0..=room).rev().find(|&i| chunk.is_char_boundary(i)).unwrap_or(0);
+                    text.push_str(&chunk[..cut]);
+                    text.push_str(&format!("\n[output truncated after {} bytes]\n", limit));
+                }
+            }
+        });
+        Output { text, reader }
+    }
+
+    pub(crate) fn snapshot(&self) -> String {
+        self.text.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    pub(crate) async fn finish(mut self) -> String {
+        let _ = (&mut self.reader).await;
+        self.snapshot()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StartRequest {
+    code: String,
+    filename: String,
+    #[serde(default)]
+    breakpoints: Vec<String>,
+    /// Pause before the first node instead of the first breakpoint
+    #[serde(default)]
+    step: bool,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CommandAction {
+    Step,
+    Continue,
+    Stop,
+}
+
+#[derive(Deserialize)]
+pub struct CommandRequest {
+    action: CommandAction,
+    /// Replaces the breakpoints of the session
+    #[serde(default)]
+    breakpoints: Vec<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum DebugStatus {
+    Paused,
+    #[default]
+    Finished,
+}
+
+#[derive(Serialize, Default)]
+pub struct DebugState {
+    status: DebugStatus,
+    /// Node about to run while paused
+    node_id: Option<String>,
+    function: Option<String>,
+    /// Variables in scope at the node, with the last value written to each
+    variables: Vec<DebugVariable>,
+    /// Program output so far
+    execution_output: String,
+    compile_output: String,
+    /// Exit status once finished
+    success: Option<bool>,
+    /// Why the program was stopped, when it did not end on its own
+    error: Option<String>,
+}
+
+/// How a wait for the program ended
+enum Wait<T> {
+    Done(T),
+    TimedOut,
+    Cancelled,
+}
+
+/// `future`, unless the run is cancelled or takes longer than `timeout`
+async fn wait<T>(future: impl Future<Output = T>, cancel: &Notify, timeout: Duration) -> Wait<T> {
+    tokio::select! {
+        value = future => Wait::Done(value),
+        _ = tokio::time::sleep(timeout) => Wait::TimedOut,
+        _ = cancel.notified() => Wait::Cancelled,
+    }
+}
+
+/// Builds and starts the program, answering with its first pause or its result
+pub async fn start(
+    State(debugger): State<Debugger>,
+    State(config): State<Arc<Config>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<StartRequest>,
+) -> Result<Json<DebugState>, ApiError> {
+    log::info!("🐞 Received debug request for: {}", request.filename);
+    // A new run replaces the previous one of the same user, or another user's pause left idle
+    // longer than the execution timeout; the replaced program stops once its connection closes
+    let (cancel, previous) = debugger.claim(&user, config.execution.timeout())?;
+    if let Some(previous) = previous {
+        finish(previous).await;
+    }
+    let state = run(&debugger, &cancel, &config, user, &request).await;
+    debugger.release(&cancel);
+    state.map(Json)
+}
+
+/// Builds and starts the program, then runs it to its first pause
+async fn run(debugger: &Debugger, cancel: &Arc<Notify>, config: &Config, owner: User, request: &StartRequest) -> Result<DebugState, ApiError> {
+    let manifest = crate::write_program(config, &request.code)?;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.map_err(|e| {
+        ApiError::InternalError(format!("Failed to open debug socket: {}", e))
+    })?;
+    let addr = listener.local_addr().map_err(|e| ApiError::InternalError(e.to_string()))?;
+    let addr = addr.to_string();
+    let (mut child, stdout, stderr) = crate::spawn_program(config, &manifest, &[(debug::ADDRESS_VAR, addr.as_ref())])?;
+
+    // The program connects before its first node; a failed build or a flow without nodes never does
+    let connected = wait(
+        async {
+            tokio::select! {
+                accepted = listener.accept() => accepted.ok().map(|(stream, _)| stream),
+                _ = child.wait() => None,
+            }
+        },
+        cancel,
+        config.execution.timeout(),
+    ).await;
+    let stream = match connected {
+        Wait::Done(stream) => stream,
+        Wait::TimedOut => return Ok(abort(child, stdout, stderr, timed_out(config)).await),
+        Wait::Cancelled => return Ok(abort(child, stdout, stderr, replaced()).await),
+    };
+    let Some(stream) = stream else {
+        let status = child.wait().await.ok();
+        return Ok(DebugState {
+            success: Some(status.is_some_and(|s| s.success())),
+            execution_output: stdout.finish().await,
+            compile_output: stderr.finish().await,
+            ..Default::default()
+        });
+    };
+    let (events, commands) = stream.into_split();
+    let session = Session { owner, child, events: BufReader::new(events).lines(), commands, stdout, stderr };
+
+    let action = if request.step { Action::Step } else { Action::Continue };
+    Ok(resume(debugger, cancel, config, session, action, &request.breakpoints).await)
+}
+
+/// Steps, continues or stops the paused program
+pub async fn command(
+    State(debugger): State<Debugger>,
+    State(config): State<Arc<Config>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CommandRequest>,
+) -> Result<Json<DebugState>, ApiError> {
+    if request.action == CommandAction::Stop {
+        // A run that has not paused yet is cancelled; its own request answers with the output
+        let (cancel, session) = debugger.claim(&user, config.execution.timeout())?;
+        debugger.release(&cancel);
+        let stopped = DebugState { error: Some("Stopped".to_string()), ..Default::default() };
+        return Ok(Json(match session {
+            Some(session) if session.owner.name == user.name => finish(session).await,
+            // Another user's expired pause ends without handing its output over
+            Some(session) => {
+                finish(session).await;
+                stopped
+            }
+            None => stopped,
+        }));
+    }
+    let Some((cancel, session)) = debugger.take_paused(&user, config.execution.timeout())? else {
+        return Err(ApiError::InternalError("No program is paused".to_string()));
+    };
+    let action = if request.action == CommandAction::Step { Action::Step } else { Action::Continue };
+    let state = resume(&debugger, &cancel, &config, session, action, &request.breakpoints).await;
+    debugger.release(&cancel);
+    Ok(Json(state))
+}
+
+/// Sends the command, then waits for the next pause (keeping the session) or for the program to end
+async fn resume(
+    debugger: &Debugger,
+    cancel: &Arc<Notify>,
+    config: &Config,
+    mut session: Session,
+    action: Action,
+    breakpoints: &[String],
+) -> DebugState {
+    // A program that already exited shows up as the end of its events
+    let _ = session.commands.write_all(debug::command(action, breakpoints).as_bytes()).await;
+    let pause = async {
+        while let Ok(Some(line)) = session.events.next_line().await {
+            if let Some(pause) = debug::parse_pause(&line) {
+                return Some(pause);
+            }
+        }
+        None
+    };
+    let pause = match wait(pause, cancel, config.execution.timeout()).await {
+        Wait::Done(Some(pause)) => pause,
+        Wait::Done(None) => return finish(session).await,
+        Wait::TimedOut => return abort(session.child, session.stdout, session.stderr, timed_out(config)).await,
+        Wait::Cancelled => return abort(session.child, session.stdout, session.stderr, replaced()).await,
+    };
+    // Output printed just before the pause travels on another pipe; give it a moment to arrive
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let state = DebugState {
+        status: DebugStatus::Paused,
+        node_id: Some(pause.node),
+        function: Some(pause.function),
+        variables: pause.variables,
+        execution_output: session.stdout.snapshot(),
+        compile_output: session.stderr.snapshot(),
+        success: None,
+        error: None,
+    };
+    match debugger.pause(cancel, session) {
+        // Replaced while pausing: this run is over
+        Some(session) => DebugState { error: Some(replaced()), ..finish(session).await },
+        None => state,
+    }
+}
+
+/// Closes the connection, which stops a paused program, and collects its result
+/// A program that does not exit shortly after (e.g. busy in a thread of its own) is killed
+async fn finish(session: Session) -> DebugState {
+    let Session { mut child, events, commands, stdout, stderr, .. } = session;
+    drop(events);
+    drop(commands);
+    let status = match tokio::time::timeout(Duration::from_secs(5), child.wait()).await {
+        Ok(status) => status.ok(),
+        Err(_) => {
+            crate::stop(&mut child).await;
+            None
+        }
+    };
+    DebugState {
+        success: Some(status.is_some_and(|s| s.success())),
+        execution_output: stdout.finish().await,
+        compile_output: stderr.finish().await,
+        ..Default::default()
+    }
+}
+
+/// Kills a program that did not pause or end in time, or whose run was replaced
+async fn abort(mut child: Child, stdout: Output, stderr: Output, error: String) -> DebugState {
+    log::warn!("⏹️  Debugged program stopped: {}", error);
+    crate::stop(&mut child).await;
+    DebugState {
+        success: Some(false),
+        execution_output: stdout.finish().await,
+        compile_output: stderr.finish().await,
+        error: Some(error),
+        ..Default::default()
+    }
+}
+
+fn timed_out(config: &Config) -> String {
+    format!("No pause or exit within {}s", config.execution.timeout_secs)
+}
+
+fn replaced() -> String {
+    "Stopped by another debug request".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpStream;
+    use tokio::process::{ChildStdout, Command};
+
+    const IDLE: Duration = Duration::from_secs(60);
+
+    fn user(name: &str) -> User {
+        User { name: name.to_string(), permissions: Vec::new(), session: false }
+    }
+
+    /// Session around a program that never pauses again on its own
+    async fn session(owner: User) -> Session {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (events, commands) = stream.into_split();
+        Session {
+            owner,
+            child: Command::new("sleep").arg("60").kill_on_drop(true).spawn().unwrap(),
+            events: BufReader::new(events).lines(),
+            commands,
+            stdout: Output::capture(None::<ChildStdout>, 0),
+            stderr: Output::capture(None::<ChildStdout>, 0),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_belong_to_their_user() {
+        let (ana, bob) = (user("ana"), user("bob"));
+        let debugger = Debugger::default();
+
+        let Ok((first, None)) = debugger.claim(&ana, IDLE) else { panic!("ana could not start") };
+        assert!(matches!(debugger.claim(&bob, IDLE), Err(ApiError::Conflict(message)) if message == "User 'ana' is debugging a program"));
+        assert!(matches!(debugger.take_paused(&bob, IDLE), Err(ApiError::Conflict(_))));
+
+        // Ana's new run cancels her previous one
+        let Ok((second, None)) = debugger.claim(&ana, IDLE) else { panic!("ana could not restart") };
+        assert!(tokio::time::timeout(Duration::from_secs(1), first.notified()).await.is_ok());
+        debugger.release(&first);
+        assert!(matches!(debugger.claim(&bob, IDLE), Err(ApiError::Conflict(_))));
+        debugger.release(&second);
+        assert!(debugger.claim(&bob, IDLE).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_idle_pauses_expire() {
+        let (ana, bob) = (user("ana"), user("bob"));
+        let debugger = Debugger::default();
+        let Ok((cancel, None)) = debugger.claim(&ana, IDLE) else { panic!("ana could not start") };
+        assert!(debugger.pause(&cancel, session(ana.clone()).await).is_none());
+
+        assert!(matches!(debugger.claim(&bob, IDLE), Err(ApiError::Conflict(_))));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let idle = Duration::from_millis(20);
+        // Bob cannot resume Ana's program, but his new run takes the debugger over
+        assert!(matches!(debugger.take_paused(&bob, idle), Ok(None)));
+        let Ok((cancel, Some(expired))) = debugger.claim(&bob, idle) else { panic!("bob could not take over") };
+        assert_eq!(expired.owner.name, "ana");
+        assert!(matches!(debugger.claim(&ana, IDLE), Err(ApiError::Conflict(message)) if message == "User 'bob' is debugging a program"));
+        debugger.release(&cancel);
+    }
+}
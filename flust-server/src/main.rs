@@ -1,43 +1,236 @@
+mod auth;
+mod config;
+mod debugger;
+mod plugins;
+
 use axum::{
-    http::StatusCode,
+    extract::{FromRef, Query, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware,
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use flust_codegen::generator;
+use clap::Parser;
+use config::{Config, LogConfig, LogFormat};
+use flust_codegen::generator::{self, Instrumentation};
+use flust_codegen::{assertions, profile, trace};
 use flust_core::ir::Flow;
+use flust_core::scope::ScopeAnalysis;
 use serde::{Deserialize, Serialize};
+use std::io::Write;
 use std::net::SocketAddr;
-use tower_http::cors::{Any, CorsLayer};
-use env_logger;
+use tower_http::cors::CorsLayer;
 use std::fs;
-use std::process::Command;
-use tempfile::TempDir;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::process::{Child, Command};
+use tokio::sync::oneshot;
+
+#[derive(Parser)]
+#[command(author, version, about = "HTTP API of the Flust editor", long_about = None)]
+struct Cli {
+    /// Config file; defaults to FLUST_CONFIG or ./flust-server.toml when present
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Print the effective settings as TOML and exit
+    #[arg(long)]
+    print_config: bool,
+}
+
+/// Shared by every handler; each one extracts the part it needs
+#[derive(Clone)]
+struct AppState {
+    config: Arc<Config>,
+    debugger: debugger::Debugger,
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for debugger::Debugger {
+    fn from_ref(state: &AppState) -> Self {
+        state.debugger.clone()
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    let cli = Cli::parse();
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if cli.print_config {
+        match config.to_toml() {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("❌ {:#}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    init_logging(&config.log);
 
-    let app = Router::new()
-        .route("/api/health", get(health_check))
+    let auth_config = config.paths.auth_file.as_deref().map(auth::Auth::load);
+    let auth_config = match auth_config.transpose() {
+        Ok(auth_config) => auth_config,
+        Err(e) => {
+            log::error!("❌ {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let auth = Arc::new(auth::Auth::new(auth_config, config.paths.audit_log.clone()));
+    let state = AppState { config: Arc::new(config), debugger: debugger::Debugger::default() };
+    let config = state.config.clone();
+
+    let compile_routes = Router::new()
         .route("/api/compile", post(compile_flow))
+        .route("/api/scope", post(analyze_scope))
+        .route("/api/plugins", get(plugins::list_plugins))
+        .route_layer(middleware::from_fn_with_state(auth.clone(), auth::compile_access))
+        .with_state(state.clone());
+    let execute_routes = Router::new()
         .route("/api/execute", post(execute_code))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
-        );
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    println!("🚀 Flust Server listening on {}", addr);
-    println!("📡 API endpoints:");
-    println!("   - GET  /api/health");
-    println!("   - POST /api/compile");
-    println!("   - POST /api/execute");
-    
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+        .route("/api/debug/start", post(debugger::start))
+        .route("/api/debug/command", post(debugger::command))
+        .route_layer(middleware::from_fn_with_state(auth.clone(), auth::execute_access))
+        .with_state(state);
+    let session_routes = Router::new()
+        .route("/api/session", post(auth::create_session))
+        .route_layer(middleware::from_fn_with_state(auth.clone(), auth::authenticated))
+        .with_state(auth.clone());
+
+    let app = Router::new()
+        .route("/api/health", get(health_check))
+        .merge(compile_routes)
+        .merge(execute_routes)
+        .merge(session_routes)
+        .layer(cors_layer(&config.server.cors_origins));
+
+    let addr = format!("{}:{}", config.server.host, config.server.port);
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("❌ Failed to bind {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    log::info!("🚀 Flust Server listening on {}", addr);
+    if auth.enabled() {
+        log::info!("🔒 Auth enabled for {} users", auth.users());
+    } else {
+        log::warn!("⚠️  Auth disabled (set paths.auth_file or FLUST_AUTH_FILE to a users file); do not expose this server beyond localhost");
+    }
+    log::info!("📁 Workspace: {}", config.paths.workspace.display());
+    log::info!("📝 Audit log: {}", config.paths.audit_log.display());
+    log::info!("📡 API endpoints:");
+    log::info!("   - GET  /api/health");
+    log::info!("   - GET  /api/plugins");
+    log::info!("   - POST /api/session");
+    log::info!("   - POST /api/compile");
+    log::info!("   - POST /api/scope");
+    log::info!("   - POST /api/execute");
+    log::info!("   - POST /api/debug/start");
+    log::info!("   - POST /api/debug/command");
+
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+}
+
+/// Logs to stderr at the configured level, as plain text or one JSON object per line
+fn init_logging(config: &LogConfig) {
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(&config.level);
+    if config.format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "ts": buf.timestamp().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.init();
+}
+
+/// Origins allowed to call the API from a browser, already checked by `Config::validate`
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let origins: Vec<HeaderValue> = origins.iter()
+        .filter_map(|o| HeaderValue::from_str(o).ok())
+        .collect();
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
+}
+
+/// Writes the program into the workspace's `src/main.rs`, returning the absolute path of its manifest
+fn write_program(config: &Config, code: &str) -> Result<PathBuf, ApiError> {
+    let workspace = &config.paths.workspace;
+    if !workspace.join("Cargo.toml").exists() {
+        return Err(ApiError::InternalError(format!(
+            "Workspace {} is not a Cargo project; set paths.workspace or FLUST_WORKSPACE", workspace.display()
+        )));
+    }
+    if !config.paths.sandbox_root().is_dir() {
+        return Err(ApiError::InternalError(format!("Sandbox root {} not found", config.paths.sandbox_root().display())));
+    }
+    let main_rs = workspace.join("src").join("main.rs");
+    fs::write(&main_rs, code).map_err(|e| {
+        ApiError::InternalError(format!("Failed to write file: {}", e))
+    })?;
+    log::debug!("📝 Wrote code to: {}", main_rs.display());
+    // The program runs from the sandbox root, so a relative workspace would no longer resolve
+    std::path::absolute(workspace.join("Cargo.toml")).map_err(|e| ApiError::InternalError(e.to_string()))
+}
+
+/// `cargo run` of the written program from the sandbox root, with its output captured up to the configured size
+/// Cargo leads a process group of its own, so `stop` also reaches the program it starts
+fn spawn_program(config: &Config, manifest: &Path, envs: &[(&str, &OsStr)]) -> Result<(Child, debugger::Output, debugger::Output), ApiError> {
+    let mut command = Command::new("cargo");
+    command
+        .arg("run")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(manifest)
+        .envs(envs.iter().copied())
+        .current_dir(config.paths.sandbox_root())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = command.spawn().map_err(|e| {
+        ApiError::InternalError(format!("Failed to execute cargo run: {}", e))
+    })?;
+    let stdout = debugger::Output::capture(child.stdout.take(), config.execution.max_output_bytes);
+    let stderr = debugger::Output::capture(child.stderr.take(), config.execution.max_output_bytes);
+    Ok((child, stdout, stderr))
+}
+
+/// Kills cargo and the program it started, which share the process group cargo leads
+async fn stop(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: kill has no memory effects; a group that already exited just yields ESRCH
+        unsafe { libc::kill(-(pid as i32), libc::SIGKILL) };
+    }
+    let _ = child.kill().await;
 }
 
 async fn health_check() -> &'static str {
@@ -56,66 +249,144 @@ struct ExecuteResponse {
     compile_output: String,
     execution_output: String,
     error: Option<String>,
+    /// Assertion node whose check failed during execution
+    failed_node: Option<String>,
+    /// Node executions reported by a program compiled with tracing
+    timeline: Vec<trace::NodeSpan>,
+    /// Node and function timings of a program compiled with profiling
+    profile: Option<profile::Report>,
 }
 
 async fn execute_code(
+    State(config): State<Arc<Config>>,
     Json(request): Json<ExecuteRequest>,
 ) -> Result<Json<ExecuteResponse>, ApiError> {
-    println!("📥 Received execution request for: {}", request.filename);
+    log::info!("📥 Received execution request for: {}", request.filename);
 
-    // Use output_project directory in the workspace root
-    let project_path = std::path::Path::new("./output_project");
-    
-    if !project_path.exists() {
-         return Err(ApiError::InternalError("output_project directory not found. Make sure you are running from the workspace root.".to_string()));
-    }
-
-    let src_path = project_path.join("src");
-    let main_rs = src_path.join("main.rs");
+    let manifest = write_program(&config, &request.code)?;
 
-    // Write code to src/main.rs
-    fs::write(&main_rs, &request.code).map_err(|e| {
-        ApiError::InternalError(format!("Failed to write file: {}", e))
+    // Traced programs send their events to a local socket, so stderr keeps only the program's output
+    let trace_listener = TcpListener::bind("127.0.0.1:0").await.map_err(|e| {
+        ApiError::InternalError(format!("Failed to open trace socket: {}", e))
+    })?;
+    let trace_addr = trace_listener.local_addr().map_err(|e| ApiError::InternalError(e.to_string()))?;
+    let (finished, done) = oneshot::channel();
+    let collector = tokio::spawn(collect_trace(trace_listener, done));
+    // Profiled programs write their samples to this file when main ends
+    let profile_file = tempfile::NamedTempFile::new().map_err(|e| {
+        ApiError::InternalError(format!("Failed to create profile file: {}", e))
     })?;
-
-    println!("📝 Wrote code to: {}", main_rs.display());
 
     // Execute with cargo run
-    println!("▶️  Executing with cargo run...");
-    let exec_result = Command::new("cargo")
-        .arg("run")
-        .arg("--quiet")
-        .current_dir(project_path)
-        .output()
-        .map_err(|e| {
-            ApiError::InternalError(format!("Failed to execute cargo run: {}", e))
-        })?;
+    log::debug!("▶️  Executing with cargo run...");
+    let trace_addr = trace_addr.to_string();
+    let envs = [(trace::ADDRESS_VAR, trace_addr.as_ref()), (profile::OUTPUT_VAR, profile_file.path().as_os_str())];
+    let (mut child, stdout, stderr) = spawn_program(&config, &manifest, &envs)?;
+    let status = match tokio::time::timeout(config.execution.timeout(), child.wait()).await {
+        Ok(status) => Some(status.map_err(|e| ApiError::InternalError(format!("Failed to execute cargo run: {}", e)))?),
+        Err(_) => {
+            stop(&mut child).await;
+            None
+        }
+    };
 
-    let stdout = String::from_utf8_lossy(&exec_result.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&exec_result.stderr).to_string();
+    let stdout = stdout.finish().await;
+    let stderr = stderr.finish().await;
+    let _ = finished.send(());
+    // Events also arrive on stderr when the program could not reach the socket
+    let mut events = trace::parse_events(&collector.await.unwrap_or_default());
+    events.extend(trace::parse_events(&stderr));
+    let timeline = trace::timeline(&events);
+    let stderr = trace::strip_events(&stderr);
+    let profile = fs::read_to_string(profile_file.path()).ok()
+        .and_then(|samples| profile::parse_samples(&samples))
+        .map(|samples| profile::report(&samples));
 
-    if !exec_result.status.success() {
-        println!("❌ Execution failed");
+    let Some(status) = status else {
+        log::warn!("⏱️  Execution timed out after {}s", config.execution.timeout_secs);
         return Ok(Json(ExecuteResponse {
             success: false,
             compile_output: stderr,
             execution_output: stdout,
-            error: Some("Execution failed".to_string()),
+            error: Some(format!("Execution timed out after {}s", config.execution.timeout_secs)),
+            failed_node: None,
+            timeline,
+            profile,
+        }));
+    };
+    if !status.success() {
+        log::info!("❌ Execution failed");
+        let failure = assertions::failed_assertion(&stderr);
+        let error = match &failure {
+            Some(failure) => format!("Assertion failed at node '{}': {}", failure.node_id, failure.message),
+            None => "Execution failed".to_string(),
+        };
+        return Ok(Json(ExecuteResponse {
+            success: false,
+            compile_output: stderr,
+            execution_output: stdout,
+            error: Some(error),
+            failed_node: failure.map(|f| f.node_id),
+            timeline,
+            profile,
         }));
     }
 
-    println!("✅ Execution successful");
+    log::info!("✅ Execution successful");
 
     Ok(Json(ExecuteResponse {
         success: true,
         compile_output: stderr, // Warnings might be here
         execution_output: stdout,
         error: None,
+        failed_node: None,
+        timeline,
+        profile,
     }))
 }
 
+/// Text sent by the traced program until `done`; connections still queued then are read too
+async fn collect_trace(listener: TcpListener, mut done: oneshot::Receiver<()>) -> String {
+    let mut readers = tokio::task::JoinSet::new();
+    let read = |mut stream: tokio::net::TcpStream| async move {
+        let mut text = String::new();
+        let _ = stream.read_to_string(&mut text).await;
+        text
+    };
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                if let Ok((stream, _)) = accepted {
+                    readers.spawn(read(stream));
+                }
+            }
+            _ = &mut done => break,
+        }
+    }
+    while let Ok(Ok((stream, _))) = tokio::time::timeout(Duration::from_millis(10), listener.accept()).await {
+        readers.spawn(read(stream));
+    }
+
+    let mut text = String::new();
+    while let Some(part) = readers.join_next().await {
+        text.push_str(&part.unwrap_or_default());
+    }
+    text
+}
+
 
 
+/// Query of `/api/compile`: `?trace=true` instruments every node, `&trace_values=true` adds variable values
+/// `?profile=true` times every node and function call instead, `?debug=true` prepares it for `/api/debug/start`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CompileOptions {
+    trace: bool,
+    trace_values: bool,
+    profile: bool,
+    debug: bool,
+}
+
 #[derive(Serialize)]
 struct CompileResponse {
     code: String,
@@ -129,6 +400,8 @@ struct ErrorResponse {
 /// Custom error type for API responses
 enum ApiError {
     CompilationError(anyhow::Error),
+    /// The request clashes with another user's work in progress
+    Conflict(String),
     InternalError(String),
 }
 
@@ -137,15 +410,19 @@ impl IntoResponse for ApiError {
         match self {
             ApiError::CompilationError(err) => {
                 let error_msg = err.to_string();
-                eprintln!("❌ Compilation error: {}", error_msg);
+                log::error!("❌ Compilation error: {}", error_msg);
                 
                 (
                     StatusCode::BAD_REQUEST,
                     Json(ErrorResponse { error: error_msg }),
                 ).into_response()
             }
+            ApiError::Conflict(msg) => {
+                log::warn!("⚠️  Conflict: {}", msg);
+                (StatusCode::CONFLICT, Json(ErrorResponse { error: msg })).into_response()
+            }
             ApiError::InternalError(msg) => {
-                eprintln!("❌ Internal error: {}", msg);
+                log::error!("❌ Internal error: {}", msg);
                 
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
@@ -165,19 +442,36 @@ where
     }
 }
 
-async fn compile_flow(Json(flow): Json<Flow>) -> Result<Json<CompileResponse>, ApiError> {
-    println!("📥 Received compilation request:");
-    println!("   - Nodes: {}", flow.nodes.len());
-    println!("   - Connections: {}", flow.connections.len());
+async fn compile_flow(
+    Query(options): Query<CompileOptions>,
+    Json(flow): Json<Flow>,
+) -> Result<Json<CompileResponse>, ApiError> {
+    log::info!("📥 Received compilation request:");
+    log::info!("   - Nodes: {}", flow.nodes.len());
+    log::info!("   - Connections: {}", flow.connections.len());
     
     for node in &flow.nodes {
-        println!("   - Node {}: type={}", node.id, node.plugin_type);
+        log::debug!("   - Node {}: type={}", node.id, node.plugin_type);
     }
     
-    let code = generator::generate_rust(&flow)?;
+    let instrumentation = if options.trace {
+        Instrumentation::Trace { values: options.trace_values }
+    } else if options.profile {
+        Instrumentation::Profile
+    } else if options.debug {
+        Instrumentation::Debug
+    } else {
+        Instrumentation::None
+    };
+    let code = generator::generate_instrumented(&flow, instrumentation)?;
     
-    println!("✅ Compilation successful");
-    println!("📝 Generated {} lines of code", code.lines().count());
+    log::info!("✅ Compilation successful");
+    log::info!("📝 Generated {} lines of code", code.lines().count());
     
     Ok(Json(CompileResponse { code }))
 }
+
+async fn analyze_scope(Json(flow): Json<Flow>) -> Result<Json<ScopeAnalysis>, ApiError> {
+    let analysis = ScopeAnalysis::analyze(&flow)?;
+    Ok(Json(analysis))
+}
@@ -0,0 +1,42 @@
+use crate::config::Config;
+use axum::{extract::State, Json};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Manifests of `<dir>/<id>/plugin.json` across the plugin directories, with `template.rs` as `template`
+/// A plugin id already found in an earlier directory is skipped
+pub fn load(dirs: &[PathBuf]) -> Vec<Value> {
+    let mut seen = HashSet::new();
+    let mut plugins = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            log::warn!("⚠️  Plugin directory {} not found", dir.display());
+            continue;
+        };
+        let mut found: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.join("plugin.json").is_file()).collect();
+        found.sort();
+        for plugin_dir in found {
+            let manifest = std::fs::read_to_string(plugin_dir.join("plugin.json")).ok()
+                .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+            let Some(mut manifest) = manifest else {
+                log::warn!("⚠️  Invalid plugin manifest in {}", plugin_dir.display());
+                continue;
+            };
+            let id = manifest.get("id").and_then(Value::as_str).unwrap_or_default().to_string();
+            if !seen.insert(id) {
+                continue;
+            }
+            if let (Ok(template), Some(fields)) = (std::fs::read_to_string(plugin_dir.join("template.rs")), manifest.as_object_mut()) {
+                fields.insert("template".to_string(), Value::String(template));
+            }
+            plugins.push(manifest);
+        }
+    }
+    plugins
+}
+
+pub async fn list_plugins(State(config): State<Arc<Config>>) -> Json<Vec<Value>> {
+    Json(load(&config.paths.plugin_dirs))
+}
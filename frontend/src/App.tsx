@@ -16,7 +16,7 @@ import ReactFlow, {
 } from 'reactflow';
 import 'reactflow/dist/style.css';
 import './styles/app.css';
-import { compileFlow } from './api';
+import { compileFlow, analyzeScope, startDebug, debugCommand, type DebugAction, type DebugState, type FlowIR } from './api';
 import Sidebar from './components/Sidebar';
 import Toolbar from './components/Toolbar';
 import LogsPanel from './components/LogsPanel';
@@ -67,7 +67,11 @@ function Flow() {
   const [terminalOutput, setTerminalOutput] = useState<string[]>([]);
   const [selectedNode, setSelectedNode] = useState<Node | null>(null);
   const [selectedEdge, setSelectedEdge] = useState<Edge | null>(null);
+  const [scopeVariables, setScopeVariables] = useState<string[]>([]);
   const [flowName, setFlowName] = useState('my_flow');
+  const [imports, setImports] = useState<string[]>([]);
+  const [breakpoints, setBreakpoints] = useState<string[]>([]);
+  const [pausedNode, setPausedNode] = useState<string | null>(null);
   const reactFlowWrapper = useRef<HTMLDivElement>(null);
   const [reactFlowInstance, setReactFlowInstance] = useState<any>(null);
   const { plugins } = usePlugins();
@@ -140,6 +144,11 @@ function Flow() {
         nodeData[prop.name] = prop.default;
       });
 
+      if (plugin.ports) {
+        nodeData.icon = plugin.icon;
+        nodeData.ports = plugin.ports;
+      }
+
       const newNode: Node = {
         id: getId(),
         type: 'custom',
@@ -170,10 +179,55 @@ function Flow() {
     addLog('Bloque eliminado');
   };
 
+  // Convert editor nodes and edges into the backend IR
+  const buildFlowIR = (): FlowIR => ({
+    nodes: nodes.map((n) => {
+      const { pluginId, nodeType, label, ports: _ports, ...otherProps } = n.data;
+      return {
+        id: n.id,
+        plugin_type: pluginId || nodeType || 'legacy_code',
+        label: label || null,
+        properties: otherProps,
+        parent_id: n.parentNode || null,
+      };
+    }),
+    connections: edges.map((e) => ({
+      from: e.source,
+      to: e.target,
+      connection_type: e.data?.connectionType || 'simple',
+      variable_mapping: e.data?.variableMapping,
+      from_port: e.sourceHandle || undefined,
+      to_port: e.targetHandle || undefined,
+    })),
+    imports,
+  });
+
+  // Variables in scope at the selected node, used for autocomplete
+  const refreshScope = async (nodeId: string) => {
+    try {
+      const scope = await analyzeScope(buildFlowIR());
+      setScopeVariables((scope.visible[nodeId] || []).map((v) => v.name));
+      scope.diagnostics
+        .filter((d) => d.node_id === nodeId)
+        .forEach((d) => addLog(`⚠️ ${d.message}`));
+    } catch (error) {
+      setScopeVariables([]);
+    }
+  };
+
   const handleNodeClick = useCallback((_event: React.MouseEvent, node: Node) => {
     setSelectedNode(node);
     setSelectedEdge(null);
-  }, []);
+    refreshScope(node.id);
+  }, [nodes, edges]);
+
+  // Right click toggles a breakpoint on the node
+  const handleNodeContextMenu = useCallback((event: React.MouseEvent, node: Node) => {
+    event.preventDefault();
+    const enabled = !breakpoints.includes(node.id);
+    setBreakpoints((prev) => (enabled ? [...prev, node.id] : prev.filter((id) => id !== node.id)));
+    addLog(enabled ? `🔴 Breakpoint en ${node.data.label || node.id}` : `Breakpoint eliminado: ${node.data.label || node.id}`);
+  }, [breakpoints]);
 
   const handleEdgeClick = useCallback((_event: React.MouseEvent, edge: Edge) => {
     setSelectedEdge(edge);
@@ -213,30 +267,7 @@ function Flow() {
 
     try {
       // First, generate the code
-      const irNodes = nodes.map((n) => {
-        const { pluginId, nodeType, label, ...otherProps } = n.data;
-        return {
-          id: n.id,
-          plugin_type: pluginId || nodeType || 'legacy_code',
-          label: label || null,
-          properties: otherProps,
-          parent_id: n.parentNode || null,
-        };
-      });
-
-      const irConnections = edges.map((e) => ({
-        from: e.source,
-        to: e.target,
-        connection_type: e.data?.connectionType || 'simple',
-        variable_mapping: e.data?.variableMapping,
-      }));
-
-      const flow = {
-        nodes: irNodes,
-        connections: irConnections,
-      };
-
-      const compileResult = await compileFlow(flow);
+      const compileResult = await compileFlow(buildFlowIR());
       addLog('✅ Código generado');
 
       // Show command being executed
@@ -270,6 +301,16 @@ function Flow() {
           execResult.compile_output.split('\n').forEach((line: string) => addTerminalOutput(line));
         }
 
+        // Select the assertion node that failed
+        if (execResult.failed_node) {
+          addLog(`❌ ${execResult.error}`);
+          const failed = nodes.find((n) => n.id === execResult.failed_node);
+          if (failed) {
+            setSelectedNode(failed);
+            setSelectedEdge(null);
+          }
+        }
+
         addTerminalOutput('$ ');
       }
 
@@ -281,6 +322,56 @@ function Flow() {
     }
   };
 
+  const showDebugState = (state: DebugState) => {
+    clearTerminal();
+    if (state.compile_output && state.status === 'finished' && !state.success) {
+      state.compile_output.split('\n').forEach((line: string) => addTerminalOutput(line));
+    }
+    if (state.execution_output) {
+      state.execution_output.split('\n').forEach((line: string) => addTerminalOutput(line));
+    }
+
+    if (state.status === 'paused') {
+      setPausedNode(state.node_id);
+      addLog(`⏸️ Pausado antes de ${state.node_id} (${state.function})`);
+      state.variables.forEach((v) => addLog(`   ${v.name} = ${v.value ?? '(sin valor)'}`));
+      const paused = nodes.find((n) => n.id === state.node_id);
+      if (paused) {
+        setSelectedNode(paused);
+        setSelectedEdge(null);
+      }
+    } else {
+      setPausedNode(null);
+      if (state.error) {
+        addLog(`⏹️ ${state.error}`);
+      } else {
+        addLog(state.success ? '✅ Ejecución completada' : '❌ El programa terminó con error');
+      }
+      addTerminalOutput('$ ');
+    }
+  };
+
+  const handleDebug = async () => {
+    addLog('🐞 Generando y depurando código...');
+    try {
+      const compileResult = await compileFlow(buildFlowIR(), { debug: true });
+      // Without breakpoints the program pauses before its first node
+      showDebugState(await startDebug(compileResult.code, flowName, breakpoints, breakpoints.length === 0));
+    } catch (error) {
+      addLog('❌ Error en depuración');
+      addTerminalOutput(String(error));
+      console.error('Debug failed:', error);
+    }
+  };
+
+  const handleDebugCommand = async (action: DebugAction) => {
+    try {
+      showDebugState(await debugCommand(action, breakpoints));
+    } catch (error) {
+      setPausedNode(null);
+      addLog('❌ Error en depuración: ' + String(error));
+    }
+  };
 
   const handleSave = () => {
     // Save .flow.json file
@@ -302,8 +393,11 @@ function Flow() {
         id: edge.id,
         source: edge.source,
         target: edge.target,
+        sourceHandle: edge.sourceHandle,
+        targetHandle: edge.targetHandle,
         data: edge.data,
       })),
+      imports,
     };
 
     const dataStr = JSON.stringify(flowFile, null, 2);
@@ -320,30 +414,7 @@ function Flow() {
   const handleDownloadCode = async () => {
     addLog('Generando código Rust...');
     try {
-      const irNodes = nodes.map((n) => {
-        const { pluginId, nodeType, label, ...otherProps } = n.data;
-        return {
-          id: n.id,
-          plugin_type: pluginId || nodeType || 'legacy_code',
-          label: label || null,
-          properties: otherProps,
-          parent_id: n.parentNode || null,
-        };
-      });
-
-      const irConnections = edges.map((e) => ({
-        from: e.source,
-        to: e.target,
-        connection_type: e.data?.connectionType || 'simple',
-        variable_mapping: e.data?.variableMapping,
-      }));
-
-      const flow = {
-        nodes: irNodes,
-        connections: irConnections,
-      };
-
-      const result = await compileFlow(flow);
+      const result = await compileFlow(buildFlowIR());
 
       // Download the generated Rust code
       const codeBlob = new Blob([result.code], { type: 'text/plain' });
@@ -396,12 +467,15 @@ function Flow() {
               id: flowEdge.id,
               source: flowEdge.source,
               target: flowEdge.target,
+              sourceHandle: flowEdge.sourceHandle,
+              targetHandle: flowEdge.targetHandle,
               data: flowEdge.data,
             }));
 
             setNodes(restoredNodes);
             setEdges(restoredEdges);
             setFlowName(flowFile.metadata.name);
+            setImports(flowFile.imports || []);
 
             // Update nodeId counter to avoid collisions
             const maxId = restoredNodes.reduce((max, node) => {
@@ -498,6 +572,8 @@ function Flow() {
     <div className="app-container">
       <Toolbar
         onPlay={handlePlay}
+        onDebug={handleDebug}
+        onDebugCommand={pausedNode ? handleDebugCommand : undefined}
         onSave={handleSave}
         onMenu={handleMenu}
         onDownloadCode={handleDownloadCode}
@@ -508,7 +584,10 @@ function Flow() {
         <Sidebar />
         <div className="canvas-container" ref={reactFlowWrapper}>
           <ReactFlow
-            nodes={nodes}
+            nodes={nodes.map((n) => ({
+              ...n,
+              className: [breakpoints.includes(n.id) && 'breakpoint', n.id === pausedNode && 'paused'].filter(Boolean).join(' '),
+            }))}
             edges={edges}
             onNodesChange={onNodesChange}
             onEdgesChange={onEdgesChange}
@@ -518,6 +597,7 @@ function Flow() {
             onDragOver={onDragOver}
             onNodeDragStop={onNodeDragStop}
             onNodeClick={handleNodeClick}
+            onNodeContextMenu={handleNodeContextMenu}
             onEdgeClick={handleEdgeClick}
             onPaneClick={handlePaneClick}
             nodeTypes={nodeTypes}
@@ -532,6 +612,9 @@ function Flow() {
           selectedNode={selectedNode}
           selectedEdge={selectedEdge}
           nodes={nodes}
+          scopeVariables={scopeVariables}
+          imports={imports}
+          onImportsChange={setImports}
           onUpdateNode={handleUpdateNode}
           onUpdateEdge={handleUpdateEdge}
         />
@@ -1,35 +1,155 @@
 import axios from 'axios';
+import type { Plugin } from './types/plugin';
 
 const API_BASE_URL = 'http://localhost:3000/api';
 
+// Servers with auth enabled expect `Authorization: Bearer <token>`; the token is only ever provided at runtime
+// (never built into the bundle): a session token kept in localStorage, obtained by `login`
+const TOKEN_KEY = 'flust_token';
+
+axios.interceptors.request.use((config) => {
+    const token = localStorage.getItem(TOKEN_KEY);
+    if (token && !config.headers.Authorization) {
+        config.headers.Authorization = `Bearer ${token}`;
+    }
+    return config;
+});
+
+// A rejected or expired token asks for the API token once and retries with a new session
+axios.interceptors.response.use(undefined, async (error) => {
+    const request = error.config;
+    if (error.response?.status !== 401 || !request || request._retried || request.url?.endsWith('/session')) {
+        throw error;
+    }
+    const apiToken = window.prompt('Token de API de Flust');
+    if (!apiToken) {
+        throw error;
+    }
+    await login(apiToken);
+    request._retried = true;
+    delete request.headers.Authorization;
+    return axios(request);
+});
+
+/** Exchanges a static API token for a session token, which is the only one stored */
+export const login = async (apiToken: string): Promise<void> => {
+    const response = await axios.post(`${API_BASE_URL}/session`, null, {
+        headers: { Authorization: `Bearer ${apiToken}` },
+    });
+    localStorage.setItem(TOKEN_KEY, response.data.token);
+};
+
 export interface FlowIR {
     nodes: Array<{
         id: string;
         plugin_type: string;
         label?: string | null;
         properties: Record<string, any>;
+        parent_id?: string | null;
     }>;
     connections: Array<{
         from: string;
         to: string;
         connection_type?: string;
         variable_mapping?: Record<string, string>;
+        from_port?: string;
+        to_port?: string;
     }>;
+    imports?: string[];
 }
 
 export interface CompileResponse {
     code: string;
 }
 
+/** One execution of a node in a traced run; times in microseconds from the first event */
+export interface NodeSpan {
+    node_id: string;
+    start_us: number;
+    duration_us: number | null;
+    values: Record<string, string>;
+}
+
+/** Timings of one node or function in a profiled run, in nanoseconds */
+export interface ProfileStats {
+    name: string;
+    calls: number;
+    total_ns: number;
+    mean_ns: number;
+    p99_ns: number;
+}
+
+export interface ProfileReport {
+    nodes: ProfileStats[];
+    functions: ProfileStats[];
+    /** Flamegraph input: one `frame;frame self_ns` line per stack */
+    folded: string;
+}
+
 export interface ExecuteResponse {
     success: boolean;
     compile_output: string;
     execution_output: string;
     error?: string;
+    failed_node?: string | null;
+    timeline: NodeSpan[];
+    profile: ProfileReport | null;
+}
+
+export interface CompileOptions {
+    trace?: boolean;
+    trace_values?: boolean;
+    profile?: boolean;
+    debug?: boolean;
+}
+
+export interface DebugVariable {
+    name: string;
+    /** null until a node of the function has written it */
+    value: string | null;
+}
+
+/** Program paused before `node_id`, or finished */
+export interface DebugState {
+    status: 'paused' | 'finished';
+    node_id: string | null;
+    function: string | null;
+    variables: DebugVariable[];
+    execution_output: string;
+    compile_output: string;
+    success: boolean | null;
+    /** Why the program was stopped, when it did not end on its own */
+    error: string | null;
 }
 
-export const compileFlow = async (flow: FlowIR): Promise<CompileResponse> => {
-    const response = await axios.post(`${API_BASE_URL}/compile`, flow);
+export type DebugAction = 'step' | 'continue' | 'stop';
+
+export interface ScopeVariable {
+    name: string;
+    mutable: boolean;
+    type: string | null;
+    declared_by: string | null;
+    initialized: boolean;
+}
+
+export interface Diagnostic {
+    severity: 'error' | 'warning';
+    node_id: string | null;
+    message: string;
+}
+
+export interface ScopeResponse {
+    visible: Record<string, ScopeVariable[]>;
+    diagnostics: Diagnostic[];
+}
+
+export const analyzeScope = async (flow: FlowIR): Promise<ScopeResponse> => {
+    const response = await axios.post(`${API_BASE_URL}/scope`, flow);
+    return response.data;
+};
+
+export const compileFlow = async (flow: FlowIR, options: CompileOptions = {}): Promise<CompileResponse> => {
+    const response = await axios.post(`${API_BASE_URL}/compile`, flow, { params: options });
     return response.data;
 };
 
@@ -40,3 +160,20 @@ export const executeCode = async (code: string, filename: string): Promise<Execu
     });
     return response.data;
 };
+
+/** Runs code compiled with `debug`, pausing at the first breakpoint (or the first node with `step`) */
+export const startDebug = async (code: string, filename: string, breakpoints: string[], step = false): Promise<DebugState> => {
+    const response = await axios.post(`${API_BASE_URL}/debug/start`, { code, filename, breakpoints, step });
+    return response.data;
+};
+
+export const debugCommand = async (action: DebugAction, breakpoints: string[]): Promise<DebugState> => {
+    const response = await axios.post(`${API_BASE_URL}/debug/command`, { action, breakpoints });
+    return response.data;
+};
+
+/** Plugins from the server's configured plugin directories, with their templates */
+export const fetchPlugins = async (): Promise<Plugin[]> => {
+    const response = await axios.get(`${API_BASE_URL}/plugins`);
+    return response.data;
+};
@@ -9,6 +9,9 @@ const nodeIcons: Record<string, string> = {
     decision: '🔀',
 };
 
+// Plugins whose nodes hold child nodes (function bodies, loop bodies)
+const containerPlugins = ['function-definition', 'channel-receive'];
+
 interface CustomNodeProps {
     id: string;
     data: any;
@@ -23,7 +26,7 @@ const CustomNode: React.FC<CustomNodeProps> = ({ id, data }) => {
 
     const nodeType = data.nodeType || 'input';
     const icon = nodeIcons[nodeType] || '';
-    const isContainer = data.pluginId === 'function-definition';
+    const isContainer = containerPlugins.includes(data.pluginId);
 
     if (isContainer) {
         return (
@@ -51,6 +54,42 @@ const CustomNode: React.FC<CustomNodeProps> = ({ id, data }) => {
 
     const isStartNode = data.pluginId === 'start-node';
 
+    // Expression nodes expose one handle per typed input port
+    if (data.ports) {
+        const inputs: { name: string; type: string }[] = data.ports.inputs || [];
+        const hasOutput = (data.ports.outputs || []).length > 0;
+        return (
+            <div className="custom-node">
+                {inputs.map((port, index) => (
+                    <Handle
+                        key={port.name}
+                        id={port.name}
+                        type="target"
+                        position={Position.Left}
+                        className="handle-input"
+                        style={{ top: `${((index + 1) * 100) / (inputs.length + 1)}%` }}
+                        title={`${port.name}: ${port.type}`}
+                    />
+                ))}
+
+                <div className="node-content">
+                    <div className="node-icon">{data.icon}</div>
+                    <div className="node-label">{data.label}</div>
+                    <button className="node-settings" onClick={handleDelete} title="Eliminar">
+                        x
+                    </button>
+                </div>
+
+                {hasOutput ? (
+                    <Handle id="result" type="source" position={Position.Right} className="handle-output" />
+                ) : (
+                    // Statement nodes produce no value, only sequencing
+                    <Handle type="source" position={Position.Right} className="handle-output" />
+                )}
+            </div>
+        );
+    }
+
     return (
         <div className="custom-node">
             {!isStartNode && <Handle type="target" position={Position.Left} className="handle-input" />}
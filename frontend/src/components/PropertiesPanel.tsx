@@ -8,6 +8,9 @@ interface PropertiesPanelProps {
     selectedNode: any | null;
     selectedEdge: any | null;
     nodes: any[];
+    scopeVariables?: string[];
+    imports: string[];
+    onImportsChange: (imports: string[]) => void;
     onUpdateNode: (id: string, data: any) => void;
     onUpdateEdge: (id: string, data: any) => void;
 }
@@ -20,6 +23,9 @@ const PropertiesPanel: React.FC<PropertiesPanelProps> = ({
     selectedNode,
     selectedEdge,
     nodes,
+    scopeVariables = [],
+    imports,
+    onImportsChange,
     onUpdateNode,
     onUpdateEdge,
 }) => {
@@ -84,6 +90,18 @@ const PropertiesPanel: React.FC<PropertiesPanelProps> = ({
                     <p className="properties-empty">
                         Selecciona un bloque o conexión para editar sus propiedades
                     </p>
+
+                    {/* Flow-level use paths, hoisted to the top of the generated file */}
+                    <div className="property-group">
+                        <label>Imports (uno por línea):</label>
+                        <textarea
+                            value={imports.join('\n')}
+                            onChange={(e) => onImportsChange(e.target.value.split('\n'))}
+                            className="property-textarea"
+                            placeholder="std::collections::HashMap"
+                            rows={4}
+                        />
+                    </div>
                 </>
             )}
 
@@ -95,6 +113,13 @@ const PropertiesPanel: React.FC<PropertiesPanelProps> = ({
                         <br />
                         <h3>Propiedades del Bloque</h3>
 
+                        {/* In-scope variable names offered as autocomplete on text fields */}
+                        <datalist id="scope-variables">
+                            {scopeVariables.map((name) => (
+                                <option key={name} value={name} />
+                            ))}
+                        </datalist>
+
                         <div className="property-group">
                             <label>Nombre:</label>
                             <input
@@ -145,6 +170,45 @@ const PropertiesPanel: React.FC<PropertiesPanelProps> = ({
                                 );
                             }
 
+                            // Subflows of this flow pick a function and take their ports from its signature
+                            if (property.name === 'function' && selectedNode.data.pluginId === 'subflow' && !editingData.source) {
+                                const functionDefinitions = nodes.filter(
+                                    (n: any) => n.data.pluginId === 'function-definition' && n.data.function_name !== 'main'
+                                );
+                                return (
+                                    <div key={property.name} className="property-group">
+                                        <label>{property.label}:</label>
+                                        <select
+                                            value={editingData[property.name] || ''}
+                                            onChange={(e) => {
+                                                const funcName = e.target.value;
+                                                const defNode = functionDefinitions.find((n: any) => n.data.function_name === funcName);
+                                                const args: { name: string; type: string }[] = defNode?.data.arguments || [];
+                                                const returnType: string = defNode?.data.return_type || '';
+
+                                                setEditingData((prev: any) => ({
+                                                    ...prev,
+                                                    [property.name]: funcName,
+                                                    ports: {
+                                                        inputs: args.map((arg) => ({ name: arg.name, type: arg.type })),
+                                                        outputs: returnType ? [{ name: 'result', type: returnType }] : [],
+                                                    },
+                                                }));
+                                                setHasChanges(true);
+                                            }}
+                                            className="property-input"
+                                        >
+                                            <option value="">-- Seleccionar función --</option>
+                                            {functionDefinitions.map((def: any) => (
+                                                <option key={def.id} value={def.data.function_name}>
+                                                    {def.data.function_name}
+                                                </option>
+                                            ))}
+                                        </select>
+                                    </div>
+                                );
+                            }
+
                             return (
                                 <div key={property.name} className="property-group">
                                     <label>{property.label}:</label>
@@ -203,7 +267,10 @@ const PropertiesPanel: React.FC<PropertiesPanelProps> = ({
                     return Array.from(matches, (m: any) => m[2]);
                 };
 
-                const availableVariables = sourceNode ? extractVariables(sourceNode) : [];
+                const availableVariables = Array.from(new Set([
+                    ...scopeVariables,
+                    ...(sourceNode ? extractVariables(sourceNode) : []),
+                ]));
 
                 const connectionType = selectedEdge.connectionType || selectedEdge.data?.connectionType || 'simple';
                 const variableMapping = selectedEdge.variableMapping || selectedEdge.data?.variableMapping || {};
@@ -251,6 +318,7 @@ const PropertiesPanel: React.FC<PropertiesPanelProps> = ({
                                 >
                                     <option value="simple">Simple (flujo de ejecución)</option>
                                     <option value="function_call">Llamada a función</option>
+                                    <option value="error">Error (rama de error)</option>
                                 </select>
                             </div>
                         )}
@@ -51,6 +51,21 @@ const PropertyField: React.FC<PropertyFieldProps> = ({ property, value, onChange
         );
     }
 
+    // Fixed set of choices
+    if (property.type === 'select') {
+        return (
+            <select
+                value={value ?? property.default ?? ''}
+                onChange={(e) => onChange(e.target.value)}
+                className="property-input"
+            >
+                {(property.options ?? []).map((option) => (
+                    <option key={option} value={option}>{option}</option>
+                ))}
+            </select>
+        );
+    }
+
     // Arguments list for function definitions
     if (property.type === 'arguments') {
         const args = value ?? property.default ?? [];
@@ -108,6 +123,7 @@ const PropertyField: React.FC<PropertyFieldProps> = ({ property, value, onChange
     return (
         <input
             type="text"
+            list="scope-variables"
             value={value ?? property.default ?? ''}
             onChange={(e) => onChange(e.target.value)}
             className="property-input"
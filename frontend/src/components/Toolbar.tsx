@@ -1,8 +1,12 @@
 import React from 'react';
+import type { DebugAction } from '../api';
 import '../styles/toolbar.css';
 
 interface ToolbarProps {
     onPlay: () => void;
+    onDebug: () => void;
+    /** Present while the program is paused in the debugger */
+    onDebugCommand?: (action: DebugAction) => void;
     onSave: () => void;
     onMenu: () => void;
     onDownloadCode: () => void;
@@ -12,6 +16,8 @@ interface ToolbarProps {
 
 const Toolbar: React.FC<ToolbarProps> = ({
     onPlay,
+    onDebug,
+    onDebugCommand,
     onSave,
     onMenu,
     onDownloadCode,
@@ -41,6 +47,24 @@ const Toolbar: React.FC<ToolbarProps> = ({
                 Compile and run
             </button>
 
+            {onDebugCommand ? (
+                <>
+                    <button className="toolbar-btn" onClick={() => onDebugCommand('step')} title="Run the next node">
+                        Step
+                    </button>
+                    <button className="toolbar-btn toolbar-btn-play" onClick={() => onDebugCommand('continue')} title="Run to the next breakpoint">
+                        Continue
+                    </button>
+                    <button className="toolbar-btn toolbar-btn-stop" onClick={() => onDebugCommand('stop')} title="Stop the program">
+                        Stop
+                    </button>
+                </>
+            ) : (
+                <button className="toolbar-btn" onClick={onDebug} title="Run pausing at breakpoints (right-click a node to toggle one)">
+                    Debug
+                </button>
+            )}
+
             <button className="toolbar-btn toolbar-btn-download" onClick={onDownloadCode} title="Download .rs">
                 Download rust code
             </button>
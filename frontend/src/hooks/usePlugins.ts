@@ -1,15 +1,94 @@
 import { useState, useEffect } from 'react';
 import type { Plugin } from '../types/plugin';
+import { fetchPlugins } from '../api';
 
 
 const PLUGIN_IDS = [
     'legacy-code',
+    'module-code',
     'debug',
     'function-definition',
     'call-function',
+    'subflow',
+    'test-case',
     'start-node',
+    'channel',
+    'channel-send',
+    'channel-receive',
+    'let-variable',
+    'assign-variable',
+    'add',
+    'subtract',
+    'multiply',
+    'divide',
+    'modulo',
+    'min',
+    'max',
+    'clamp',
+    'equal',
+    'not-equal',
+    'less-than',
+    'less-equal',
+    'greater-than',
+    'greater-equal',
+    'and',
+    'or',
+    'not',
+    'cast',
+    'format-string',
+    'split',
+    'join',
+    'trim',
+    'parse',
+    'len',
+    'vec-new',
+    'map-new',
+    'vec-get',
+    'map-get',
+    'vec-map',
+    'vec-filter',
+    'vec-sort',
+    'push',
+    'map-insert',
+    'struct-definition',
+    'enum-definition',
+    'construct',
+    'field-access',
+    'assert',
+    'assert-eq',
+    'debug-assert',
 ];
 
+/** Plugins bundled with the frontend, for servers that cannot list their own */
+const loadStaticPlugins = async (): Promise<Plugin[]> => {
+    const loadedPlugins: Plugin[] = [];
+
+    for (const pluginId of PLUGIN_IDS) {
+        try {
+            const response = await fetch(`/plugins/${pluginId}/plugin.json`);
+            if (response.ok) {
+                const pluginData = await response.json();
+
+                // Load template if exists
+                try {
+                    const templateResponse = await fetch(`/plugins/${pluginId}/template.rs`);
+                    if (templateResponse.ok) {
+                        pluginData.template = await templateResponse.text();
+                    }
+                } catch (e) {
+                    console.warn(`No template found for ${pluginId}`);
+                }
+
+                loadedPlugins.push(pluginData);
+            }
+        } catch (e) {
+            console.warn(`Failed to load plugin ${pluginId}:`, e);
+        }
+    }
+
+    return loadedPlugins;
+};
+
 export const usePlugins = () => {
     const [plugins, setPlugins] = useState<Plugin[]>([]);
     const [loading, setLoading] = useState(true);
@@ -18,29 +97,14 @@ export const usePlugins = () => {
     useEffect(() => {
         const loadPlugins = async () => {
             try {
-                const loadedPlugins: Plugin[] = [];
-
-                for (const pluginId of PLUGIN_IDS) {
-                    try {
-                        const response = await fetch(`/plugins/${pluginId}/plugin.json`);
-                        if (response.ok) {
-                            const pluginData = await response.json();
-
-                            // Load template if exists
-                            try {
-                                const templateResponse = await fetch(`/plugins/${pluginId}/template.rs`);
-                                if (templateResponse.ok) {
-                                    pluginData.template = await templateResponse.text();
-                                }
-                            } catch (e) {
-                                console.warn(`No template found for ${pluginId}`);
-                            }
-
-                            loadedPlugins.push(pluginData);
-                        }
-                    } catch (e) {
-                        console.warn(`Failed to load plugin ${pluginId}:`, e);
-                    }
+                let loadedPlugins: Plugin[] = [];
+                try {
+                    loadedPlugins = await fetchPlugins();
+                } catch (e) {
+                    console.warn('Server plugin list unavailable, using bundled plugins:', e);
+                }
+                if (loadedPlugins.length === 0) {
+                    loadedPlugins = await loadStaticPlugins();
                 }
 
                 setPlugins(loadedPlugins);
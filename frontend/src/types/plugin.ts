@@ -1,7 +1,7 @@
 /**
  * Connection type - defines how the connection behaves
  */
-export type ConnectionType = 'simple' | 'function_call';
+export type ConnectionType = 'simple' | 'function_call' | 'error';
 
 /**
  * Function argument definition
@@ -16,11 +16,20 @@ export interface FunctionArgument {
  */
 export interface PluginProperty {
     name: string;
-    type: 'text' | 'code' | 'number' | 'boolean' | 'arguments';
+    type: 'text' | 'code' | 'number' | 'boolean' | 'arguments' | 'select';
     label: string;
     default: string | number | boolean | FunctionArgument[];
     required: boolean;
     multiline?: boolean;
+    options?: string[]; // Allowed values for 'select' properties
+}
+
+/**
+ * Typed port of an expression node ('number' | 'bool' | 'any')
+ */
+export interface PluginPort {
+    name: string;
+    type: string;
 }
 
 /**
@@ -35,6 +44,10 @@ export interface Plugin {
     description: string;
     properties: PluginProperty[];
     template?: string;
+    ports?: {
+        inputs: PluginPort[];
+        outputs: PluginPort[];
+    };
 }
 
 /**
@@ -55,6 +68,8 @@ export interface FlowEdge {
     id: string;
     source: string;
     target: string;
+    sourceHandle?: string | null; // output port
+    targetHandle?: string | null; // input port
     connectionType?: ConnectionType;
     variableMapping?: Record<string, string>; // argument name -> variable name
     data?: Record<string, any>;
@@ -72,4 +87,5 @@ export interface FlowFile {
     };
     nodes: FlowNode[];
     edges: FlowEdge[];
+    imports?: string[];
 }
@@ -6,7 +6,7 @@ async fn pow(num: i8, exp: i8) -> i32 {
         i += 1;
     }
     println!("{:?}", potencia);
-    return potencia;
+    potencia
 
 }
 